@@ -80,9 +80,10 @@ fn main() -> ! {
 
     // initialize our profiling timer & structure
     log::debug!("initializing our tracing stuff");
-    let systick_profiler = cortex_m::singleton!(: ep_systick::SysTickProfiler::<CORE_FREQ> =
+    let systick_profiler: &'static _ =
+        cortex_m::singleton!(: ep_systick::SysTickProfiler::<CORE_FREQ> =
         ep_systick::SysTickProfiler::new(core.SYST, CORE_FREQ))
-    .unwrap();
+        .unwrap();
     unsafe {
         ep::set_profiler(systick_profiler).unwrap();
     }
@@ -91,6 +92,8 @@ fn main() -> ! {
     loop {
         red_led.toggle().unwrap();
         profile_target(&mut sleeping_delay);
+        // the profiler doubles as a delay, as it owns `SYST`
+        systick_profiler.delay_ms(50);
     }
 }
 
@@ -5,8 +5,13 @@
 extern crate proc_macro;
 use proc_macro::TokenStream;
 
-use quote::quote;
-use syn::{parse_macro_input, parse_quote, ItemFn};
+use quote::{quote, quote_spanned};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_quote,
+    spanned::Spanned,
+    Block, Expr, Ident, ImplItem, ItemFn, ItemImpl, LitInt, LitStr, Signature, Token, Type,
+};
 
 #[proc_macro_attribute]
 /// profiles the annotated function using `embedded_profiling`.
@@ -19,25 +24,548 @@ use syn::{parse_macro_input, parse_quote, ItemFn};
 /// // Hello, world!
 /// // <EPSS my_long_running_function: xx us>
 /// ```
-pub fn profile_function(_attr: TokenStream, item: TokenStream) -> TokenStream {
-    let mut function = parse_macro_input!(item as ItemFn);
-    let instrumented_function_name = function.sig.ident.to_string();
+///
+/// The snapshot's [`target`](https://docs.rs/embedded-profiling/latest/embedded_profiling/struct.EPSnapshot.html#structfield.target)
+/// is set to the `module_path!()` of the annotated function, and its
+/// [`location`](https://docs.rs/embedded-profiling/latest/embedded_profiling/struct.EPSnapshot.html#structfield.location)
+/// to the file and line of the function's name.
+///
+/// Only the body is instrumented and the signature is left as-is, so `unsafe fn`s stay
+/// `unsafe`. The injected profiling calls are safe and are never wrapped in an `unsafe` block.
+/// The snapshot is ended and logged on every way out of the body, including a `return` or `?`.
+///
+/// On an `async fn`, the snapshot starts when the returned future is first polled and ends
+/// when it completes, so it measures the wall-clock time across every `.await`, including
+/// the time spent waiting, rather than the construction of the future. If the future is
+/// dropped before completing, its snapshot is ended without being logged. The future is
+/// `Send` whenever the function's own state is, but a `profiler = ...` expression is
+/// evaluated again when the snapshot ends, so it can't borrow anything the body consumes.
+/// As futures interleave, the snapshot isn't counted in
+/// [`nesting_depth`](https://docs.rs/embedded-profiling/latest/embedded_profiling/fn.nesting_depth.html)
+/// and emits no begin or end events, like `profile_async`.
+/// ```
+/// #[embedded_profiling::profile_function]
+/// async fn exchange() {}
+/// ```
+///
+/// ## Thresholds
+///
+/// `min_us = N` only logs snapshots that took at least `N` microseconds (compared in
+/// ticks of `EPDuration`). Shorter calls are still measured, just not logged.
+///
+/// `max_us = N` declares a budget of `N` microseconds. In debug builds, a call taking
+/// longer trips a `debug_assert!` naming the function, once its snapshot has been logged,
+/// so deadline violations are caught during development. In release builds (without
+/// `debug_assertions`) it is profiled as usual and the budget isn't checked. With the `std`
+/// feature of `embedded-profiling`, it isn't checked while unwinding from a panic either.
+/// ```
+/// #[embedded_profiling::profile_function(max_us = 100)]
+/// fn control_loop() {}
+/// ```
+///
+/// Adding `compile_filter` marks the site as a small span that may be compiled out:
+/// when the `compile-filter` feature is enabled, the function is emitted exactly as
+/// written, with no profiling calls at all. Without the feature it behaves like plain
+/// `min_us = N`. `compile_filter` requires `min_us`.
+/// ```
+/// #[embedded_profiling::profile_function(min_us = 50, compile_filter)]
+/// fn tiny_hot_function() {}
+/// ```
+///
+/// ## Name
+///
+/// `name = "label"` names the snapshots `label` instead of the function's name, for example
+/// to tell apart functions with the same name in different modules.
+/// ```
+/// #[embedded_profiling::profile_function(name = "radio::init")]
+/// fn init() {}
+/// // init() logs <EPSS radio::init: xx us>
+/// ```
+///
+/// ## Profiler
+///
+/// `profiler = EXPR` profiles with the given profiler instead of the global one, for example
+/// a `static` per subsystem. `EXPR` must evaluate to something implementing `EmbeddedProfiler`,
+/// which is borrowed for the duration of the call, like with `profile_with_profiler`.
+/// ```
+/// # use embedded_profiling::{EPInstant, EmbeddedProfiler};
+/// struct RadioProfiler;
+/// impl EmbeddedProfiler for RadioProfiler {
+///     fn read_clock(&self) -> EPInstant {
+///         EPInstant::from_ticks(0)
+///     }
+/// }
+/// static RADIO_PROFILER: RadioProfiler = RadioProfiler;
+///
+/// #[embedded_profiling::profile_function(profiler = RADIO_PROFILER)]
+/// fn transmit() {}
+/// ```
+///
+/// ## Disabling
+///
+/// With the `disable` feature, every annotated function is emitted exactly as written,
+/// without any reference to `embedded_profiling`, so no profiling code is generated at all.
+/// Arguments are still checked.
+///
+/// Instrumented functions call into the global profiler on entry and exit, which makes
+/// them poor candidates for inlining and adds the call overhead to every caller. Omitted
+/// sites have none of that, so the optimizer is free to inline them again. Note this also
+/// means instrumenting a small function can change the timing of its callers.
+pub fn profile_function(attr: TokenStream, item: TokenStream) -> TokenStream {
+    expand(attr.into(), item.into(), filter()).into()
+}
 
-    let body = &function.block;
-    let new_body: syn::Block = parse_quote! {
-        {
-            let start = embedded_profiling::start_snapshot();
-            #body
-            if let Some(dur) = embedded_profiling::end_snapshot(start, #instrumented_function_name) {
-                embedded_profiling::log_snapshot(&dur);
+#[proc_macro_attribute]
+/// profiles every method of the annotated `impl` block using `embedded_profiling`, like
+/// annotating each of them with [`profile_function`]. Snapshots are named `Type::method`.
+/// ```
+/// struct Driver;
+///
+/// #[embedded_profiling::profile_impl]
+/// impl Driver {
+///     const ADDRESS: u8 = 0x42;
+///
+///     fn read(&self) -> u8 {
+///         Self::ADDRESS
+///     }
+///
+///     #[profile_skip]
+///     fn is_ready(&self) -> bool {
+///         true
+///     }
+/// }
+/// // Driver::read(...) logs <EPSS Driver::read: xx us>
+/// ```
+///
+/// Methods marked `#[profile_skip]` and items other than methods are left untouched.
+/// Generics are left out of the type's name, so all instances of `Driver<T>` log as
+/// `Driver::method`. The arguments are the same as [`profile_function`]'s, except `name`,
+/// and apply to every method.
+pub fn profile_impl(attr: TokenStream, item: TokenStream) -> TokenStream {
+    expand_impl(attr.into(), item.into(), filter()).into()
+}
+
+/// Which instrumentation is compiled out, set by the features of this crate.
+#[derive(Clone, Copy, Default)]
+struct Filter {
+    /// Omit sites marked `compile_filter`, with the `compile-filter` feature.
+    compile_filter: bool,
+    /// Omit every site, with the `disable` feature.
+    disabled: bool,
+}
+
+/// The [`Filter`] selected by the features of this crate.
+fn filter() -> Filter {
+    Filter {
+        compile_filter: cfg!(feature = "compile-filter"),
+        disabled: cfg!(feature = "disable"),
+    }
+}
+
+/// The arguments accepted by [`profile_function`] and [`profile_impl`].
+#[derive(Default)]
+struct Options {
+    min_us: Option<LitInt>,
+    max_us: Option<LitInt>,
+    compile_filter: bool,
+    profiler: Option<Expr>,
+    name: Option<LitStr>,
+}
+
+impl Parse for Options {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut options = Options::default();
+
+        while !input.is_empty() {
+            let key: Ident = input.parse()?;
+            if key == "min_us" {
+                input.parse::<Token![=]>()?;
+                options.min_us = Some(input.parse()?);
+            } else if key == "max_us" {
+                input.parse::<Token![=]>()?;
+                options.max_us = Some(input.parse()?);
+            } else if key == "compile_filter" {
+                options.compile_filter = true;
+            } else if key == "profiler" {
+                input.parse::<Token![=]>()?;
+                options.profiler = Some(input.parse()?);
+            } else if key == "name" {
+                input.parse::<Token![=]>()?;
+                options.name = Some(input.parse()?);
+            } else {
+                return Err(syn::Error::new(key.span(), "unknown argument"));
             }
+
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+
+        if options.compile_filter && options.min_us.is_none() {
+            return Err(syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "`compile_filter` requires `min_us`",
+            ));
         }
+
+        Ok(options)
+    }
+}
+
+/// Expands [`profile_function`], omitting the sites `filter` compiles out.
+fn expand(
+    attr: proc_macro2::TokenStream,
+    item: proc_macro2::TokenStream,
+    filter: Filter,
+) -> proc_macro2::TokenStream {
+    let options: Options = match syn::parse2(attr) {
+        Ok(options) => options,
+        Err(err) => return err.to_compile_error(),
+    };
+    let mut function: ItemFn = match syn::parse2(item) {
+        Ok(function) => function,
+        Err(err) => return err.to_compile_error(),
     };
 
-    function.block = Box::new(new_body);
+    let name = options
+        .name
+        .as_ref()
+        .map_or_else(|| function.sig.ident.to_string(), LitStr::value);
+    if let Some(body) = instrument(&options, &function.sig, &name, &function.block, filter) {
+        *function.block = body;
+    }
 
-    (quote! {
+    quote! {
         #function
+    }
+}
+
+/// Expands [`profile_impl`], omitting the sites `filter` compiles out.
+fn expand_impl(
+    attr: proc_macro2::TokenStream,
+    item: proc_macro2::TokenStream,
+    filter: Filter,
+) -> proc_macro2::TokenStream {
+    let options: Options = match syn::parse2(attr) {
+        Ok(options) => options,
+        Err(err) => return err.to_compile_error(),
+    };
+    if let Some(name) = &options.name {
+        // every method would log under the same name
+        return syn::Error::new(name.span(), "`name` isn't supported by `profile_impl`")
+            .to_compile_error();
+    }
+    let mut item_impl: ItemImpl = match syn::parse2(item) {
+        Ok(item_impl) => item_impl,
+        Err(err) => return err.to_compile_error(),
+    };
+
+    let type_name = type_name(&item_impl.self_ty);
+    for item in &mut item_impl.items {
+        let method = match item {
+            ImplItem::Method(method) => method,
+            // consts, types and macros are left as they are
+            _ => continue,
+        };
+
+        let attr_count = method.attrs.len();
+        method
+            .attrs
+            .retain(|attr| !attr.path.is_ident("profile_skip"));
+        if method.attrs.len() != attr_count {
+            continue;
+        }
+
+        let name = format!("{}::{}", type_name, method.sig.ident);
+        if let Some(body) = instrument(&options, &method.sig, &name, &method.block, filter) {
+            method.block = body;
+        }
+    }
+
+    quote! {
+        #item_impl
+    }
+}
+
+/// The name of `ty` in snapshot names, without generics (`Driver` for `Driver<T>`).
+fn type_name(ty: &Type) -> String {
+    match ty {
+        Type::Path(path) => match path.path.segments.last() {
+            Some(segment) => segment.ident.to_string(),
+            None => quote!(#ty).to_string(),
+        },
+        _ => quote!(#ty).to_string().replace(' ', ""),
+    }
+}
+
+/// The instrumented version of `body`, of the function with the signature `sig`, profiled as
+/// `name`.
+///
+/// Returns `None` if `filter` compiles out this site, in which case `body` should be left
+/// as it is.
+fn instrument(
+    options: &Options,
+    sig: &Signature,
+    name: &str,
+    body: &Block,
+    filter: Filter,
+) -> Option<Block> {
+    if filter.disabled || (options.compile_filter && filter.compile_filter) {
+        return None;
+    }
+
+    // spanned so `line!()` resolves to the line of the function's name
+    let location = quote_spanned! {sig.ident.span()=>
+        .with_location(file!(), line!())
+    };
+    let snapshot = quote! {
+        &snapshot.with_target(module_path!())#location
+    };
+
+    // either the global profiler or the given one, through the same trait object
+    let (profiler, start, end, log_snapshot) = match &options.profiler {
+        Some(profiler) => (
+            // spanned so a profiler not implementing `EmbeddedProfiler` is reported on it
+            quote_spanned! {profiler.span()=>
+                let __ep_profiler: &dyn embedded_profiling::EmbeddedProfiler = &(#profiler);
+            },
+            quote! { __ep_profiler.start_snapshot(#name) },
+            quote! { __ep_profiler.end_snapshot },
+            quote! { __ep_profiler.log_snapshot },
+        ),
+        None => (
+            quote! {},
+            quote! { embedded_profiling::begin_snapshot(#name) },
+            quote! { embedded_profiling::end_snapshot },
+            quote! { embedded_profiling::log_snapshot },
+        ),
+    };
+
+    let log: syn::Stmt = match &options.min_us {
+        Some(min_us) => parse_quote! {
+            if snapshot.duration >= embedded_profiling::EPDuration::from_ticks(#min_us) {
+                #log_snapshot(#snapshot);
+            }
+        },
+        None => parse_quote! {
+            #log_snapshot(#snapshot);
+        },
+    };
+
+    let budget: Option<syn::Stmt> = options.max_us.as_ref().map(|max_us| {
+        parse_quote! {
+            debug_assert!(
+                embedded_profiling::unwinding()
+                    || snapshot.duration <= embedded_profiling::EPDuration::from_ticks(#max_us),
+                "`{}` exceeded its budget of {} us, taking {}",
+                #name,
+                #max_us,
+                snapshot.duration,
+            );
+        }
+    });
+
+    // the signature (including any `unsafe`) is untouched. The original body is kept as its
+    // own block so its unsafe context is exactly what it was before instrumenting, and the
+    // snapshot is ended and logged by a guard, so a `return` or `?` in the body doesn't skip
+    // it and leave the nesting depth offset.
+    if sig.asyncness.is_none() {
+        return Some(parse_quote! {
+            {
+                #profiler
+                let start = #start;
+                let __ep_end = embedded_profiling::CancelGuard::new(move || {
+                    if let Some(snapshot) = #end(start, #name) {
+                        #log
+                        #budget
+                    }
+                });
+                #body
+            }
+        });
+    }
+
+    // futures interleave, so a span held across `.await` can't be counted in the global
+    // nesting depth: the global profiler's snapshots are taken directly, like `ProfiledFuture`
+    let (start, end) = match &options.profiler {
+        Some(_) => (start, end),
+        None => (
+            quote! { embedded_profiling::profiler().start_snapshot(#name) },
+            quote! { embedded_profiling::profiler().end_snapshot },
+        ),
+    };
+
+    // the body of an `async fn` runs when its future is polled, so it's awaited as an async
+    // block of its own to measure until it completes, with any `return` or `?` ending that
+    // block rather than skipping the end of the snapshot. The profiler is evaluated again
+    // wherever it's needed rather than held across the `.await`, which would make the
+    // future `!Send`, and a future dropped before completing ends its snapshot unlogged.
+    Some(parse_quote! {
+        {
+            let start = {
+                #profiler
+                #start
+            };
+            let __ep_cancel = embedded_profiling::CancelGuard::new(|| {
+                #profiler
+                let _ = #end(start, #name);
+            });
+            let __ep_result = async #body.await;
+            __ep_cancel.disarm();
+            #profiler
+            if let Some(snapshot) = #end(start, #name) {
+                #log
+                #budget
+            }
+            __ep_result
+        }
     })
-    .into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn expand_to_string(attr: proc_macro2::TokenStream, compile_filter_enabled: bool) -> String {
+        let filter = Filter {
+            compile_filter: compile_filter_enabled,
+            disabled: false,
+        };
+        expand(attr, quote! { fn small() {} }, filter).to_string()
+    }
+
+    #[test]
+    fn compile_filter_generated() {
+        let expanded = expand_to_string(quote! { min_us = 10, compile_filter }, false);
+        assert!(expanded.contains("begin_snapshot"));
+        assert!(expanded.contains("from_ticks (10)"));
+    }
+
+    #[test]
+    fn compile_filter_omitted() {
+        let expanded = expand_to_string(quote! { min_us = 10, compile_filter }, true);
+        assert_eq!(expanded, quote! { fn small() {} }.to_string());
+    }
+
+    #[test]
+    fn min_us_without_compile_filter_is_kept() {
+        let expanded = expand_to_string(quote! { min_us = 10 }, true);
+        assert!(expanded.contains("begin_snapshot"));
+    }
+
+    #[test]
+    fn profiler_argument() {
+        let expanded = expand_to_string(quote! { profiler = MY_PROFILER, min_us = 1 }, false);
+        assert!(expanded.contains("& (MY_PROFILER)"));
+        assert!(!expanded.contains("embedded_profiling :: begin_snapshot"));
+    }
+
+    #[test]
+    fn early_return_ends_snapshot() {
+        let function = quote! {
+            fn parsed(text: &str) -> Result<u32, ParseIntError> {
+                Ok(text.parse::<u32>()?)
+            }
+        };
+        let expanded = expand(quote! {}, function, Filter::default()).to_string();
+        let guard = expanded.find("CancelGuard :: new").unwrap();
+        let body = expanded.find("text . parse :: < u32 > () ?").unwrap();
+        assert!(guard < body);
+        assert!(expanded[guard..body].contains("end_snapshot"));
+        assert!(!expanded[body..].contains("end_snapshot"));
+    }
+
+    #[test]
+    fn async_not_depth_tracked() {
+        let expanded = expand(quote! {}, quote! { async fn small() {} }, Filter::default());
+        let expanded = expanded.to_string();
+        assert!(expanded.contains("profiler () . start_snapshot (\"small\")"));
+        assert!(!expanded.contains("begin_snapshot"));
+    }
+
+    #[test]
+    fn name_argument() {
+        let expanded = expand_to_string(quote! { name = "custom_label" }, false);
+        assert!(expanded.contains("begin_snapshot (\"custom_label\")"));
+        assert!(!expanded.contains("\"small\""));
+    }
+
+    #[test]
+    fn impl_methods_named_after_type() {
+        let expanded = expand_impl(
+            quote! {},
+            quote! {
+                impl<T> Driver<T> {
+                    fn read(&self) {}
+                    #[profile_skip]
+                    fn skipped(&self) {}
+                }
+            },
+            Filter::default(),
+        )
+        .to_string();
+        assert!(expanded.contains("\"Driver::read\""));
+        assert!(!expanded.contains("Driver::skipped"));
+        assert!(!expanded.contains("profile_skip"));
+    }
+
+    #[test]
+    fn max_us_checked() {
+        let expanded = expand_to_string(quote! { max_us = 250 }, false);
+        assert!(expanded.contains("debug_assert !"));
+        assert!(expanded.contains("from_ticks (250)"));
+        assert!(!expand_to_string(quote! {}, false).contains("debug_assert"));
+    }
+
+    #[test]
+    fn disabled_expands_to_original() {
+        let disabled = Filter {
+            compile_filter: false,
+            disabled: true,
+        };
+        let function = quote! { fn small(value: u8) -> u8 { value + 1 } };
+        for attr in [quote! {}, quote! { min_us = 10, max_us = 20 }] {
+            let expanded = expand(attr.clone(), function.clone(), disabled).to_string();
+            assert_eq!(expanded, function.to_string());
+            assert!(!expanded.contains("embedded_profiling"));
+
+            let enabled = expand(attr, function.clone(), Filter::default()).to_string();
+            assert!(enabled.contains("embedded_profiling :: begin_snapshot"));
+        }
+
+        let expanded = expand_impl(
+            quote! {},
+            quote! {
+                impl Driver {
+                    fn read(&self) {}
+                    #[profile_skip]
+                    fn skipped(&self) {}
+                }
+            },
+            disabled,
+        )
+        .to_string();
+        assert!(!expanded.contains("embedded_profiling"));
+        assert!(!expanded.contains("profile_skip"));
+    }
+
+    #[test]
+    fn bad_arguments() {
+        for attr in [
+            quote! { compile_filter },
+            quote! { min_us = "10" },
+            quote! { max_us = "10" },
+            quote! { profiler },
+            quote! { name = read },
+        ] {
+            assert!(expand_to_string(attr, false).contains("compile_error"));
+        }
+
+        let expanded = expand_impl(
+            quote! { name = "read" },
+            quote! { impl Driver { fn read(&self) {} } },
+            Filter::default(),
+        );
+        assert!(expanded.to_string().contains("compile_error"));
+    }
 }
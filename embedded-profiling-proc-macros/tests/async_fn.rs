@@ -0,0 +1,177 @@
+// the instrumentation is compiled out with `disable`
+#![cfg(not(feature = "disable"))]
+
+#[cfg(test)]
+mod test {
+    use core::future::Future;
+    use core::pin::Pin;
+    use core::task::{Context, Poll, Waker};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::{Arc, Once};
+    use std::task::Wake;
+
+    struct TestEP {
+        name: &'static str,
+        now: AtomicU32,
+        measured: AtomicU32,
+        ended: AtomicU32,
+    }
+
+    impl TestEP {
+        const fn new(name: &'static str) -> Self {
+            Self {
+                name,
+                now: AtomicU32::new(0),
+                measured: AtomicU32::new(0),
+                ended: AtomicU32::new(0),
+            }
+        }
+    }
+
+    impl embedded_profiling::EmbeddedProfiler for TestEP {
+        #[allow(clippy::useless_conversion)] // only a conversion with `container-u64`
+        fn read_clock(&self) -> embedded_profiling::EPInstant {
+            embedded_profiling::EPInstant::from_ticks(self.now.load(Ordering::SeqCst).into())
+        }
+
+        fn log_snapshot(&self, snapshot: &embedded_profiling::EPSnapshot) {
+            assert_eq!(snapshot.name, self.name);
+            #[allow(clippy::useless_conversion)] // only a conversion with `container-u64`
+            let ticks = u32::try_from(snapshot.duration.ticks()).unwrap();
+            self.measured.store(ticks, Ordering::SeqCst);
+        }
+
+        fn at_end(&self, _name: &'static str) {
+            self.ended.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    static TEST_PROFILER: TestEP = TestEP::new("exchange");
+    static CANCEL_PROFILER: TestEP = TestEP::new("cancelled");
+    static GLOBAL_PROFILER: TestEP = TestEP::new("interleaved");
+
+    /// Pending the first time it's polled, like waiting on a peripheral.
+    struct YieldOnce(bool);
+
+    impl Future for YieldOnce {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                Poll::Ready(())
+            } else {
+                self.0 = true;
+                Poll::Pending
+            }
+        }
+    }
+
+    #[embedded_profiling_proc_macros::profile_function(profiler = TEST_PROFILER)]
+    async fn exchange(value: u32) -> Result<u32, ()> {
+        YieldOnce(false).await;
+        if value == 0 {
+            return Err(());
+        }
+        Ok(value)
+    }
+
+    #[embedded_profiling_proc_macros::profile_function(profiler = CANCEL_PROFILER)]
+    async fn cancelled() {
+        YieldOnce(false).await;
+    }
+
+    #[embedded_profiling_proc_macros::profile_function]
+    async fn interleaved() {
+        YieldOnce(false).await;
+    }
+
+    fn assert_send<T: Send>(_: &T) {}
+
+    struct NoopWake;
+
+    impl Wake for NoopWake {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    fn noop_waker() -> Waker {
+        Arc::new(NoopWake).into()
+    }
+
+    #[test]
+    fn measured_across_await() {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        // constructing the future doesn't start the snapshot
+        TEST_PROFILER.now.store(10, Ordering::SeqCst);
+        let mut future = Box::pin(exchange(7));
+
+        TEST_PROFILER.now.store(100, Ordering::SeqCst);
+        assert_eq!(future.as_mut().poll(&mut cx), Poll::Pending);
+        TEST_PROFILER.now.store(150, Ordering::SeqCst);
+        assert_eq!(future.as_mut().poll(&mut cx), Poll::Ready(Ok(7)));
+        assert_eq!(TEST_PROFILER.measured.load(Ordering::SeqCst), 50);
+
+        // an early `return` still ends the snapshot
+        let mut future = Box::pin(exchange(0));
+        assert_eq!(future.as_mut().poll(&mut cx), Poll::Pending);
+        TEST_PROFILER.now.store(175, Ordering::SeqCst);
+        assert_eq!(future.as_mut().poll(&mut cx), Poll::Ready(Err(())));
+        assert_eq!(TEST_PROFILER.measured.load(Ordering::SeqCst), 25);
+    }
+
+    #[test]
+    fn future_is_send() {
+        assert_send(&exchange(7));
+        assert_send(&cancelled());
+    }
+
+    #[test]
+    fn dropped_mid_await() {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let mut future = Box::pin(cancelled());
+        assert_eq!(future.as_mut().poll(&mut cx), Poll::Pending);
+        assert_eq!(CANCEL_PROFILER.ended.load(Ordering::SeqCst), 0);
+
+        // the snapshot is ended, but not logged
+        CANCEL_PROFILER.now.store(30, Ordering::SeqCst);
+        drop(future);
+        assert_eq!(CANCEL_PROFILER.ended.load(Ordering::SeqCst), 1);
+        assert_eq!(CANCEL_PROFILER.measured.load(Ordering::SeqCst), 0);
+
+        // a future that's never polled never starts its snapshot
+        drop(cancelled());
+        assert_eq!(CANCEL_PROFILER.ended.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn interleaved_futures() {
+        static INIT_PROFILER: Once = Once::new();
+        INIT_PROFILER.call_once(|| unsafe {
+            embedded_profiling::set_profiler(&GLOBAL_PROFILER).unwrap();
+        });
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let depth = embedded_profiling::nesting_depth();
+
+        GLOBAL_PROFILER.now.store(10, Ordering::SeqCst);
+        let mut first = Box::pin(interleaved());
+        assert_eq!(first.as_mut().poll(&mut cx), Poll::Pending);
+        GLOBAL_PROFILER.now.store(20, Ordering::SeqCst);
+        let mut second = Box::pin(interleaved());
+        assert_eq!(second.as_mut().poll(&mut cx), Poll::Pending);
+        assert_eq!(embedded_profiling::nesting_depth(), depth);
+
+        // completing out of order leaves the depth as it was
+        GLOBAL_PROFILER.now.store(50, Ordering::SeqCst);
+        assert_eq!(first.as_mut().poll(&mut cx), Poll::Ready(()));
+        assert_eq!(GLOBAL_PROFILER.measured.load(Ordering::SeqCst), 40);
+        GLOBAL_PROFILER.now.store(70, Ordering::SeqCst);
+        assert_eq!(second.as_mut().poll(&mut cx), Poll::Ready(()));
+        assert_eq!(GLOBAL_PROFILER.measured.load(Ordering::SeqCst), 50);
+        assert_eq!(embedded_profiling::nesting_depth(), depth);
+    }
+}
@@ -0,0 +1,71 @@
+// the instrumentation is compiled out with `disable`
+#![cfg(not(feature = "disable"))]
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Once;
+
+    /// Profiler whose clock only moves when a profiled function advances it.
+    struct CountingEP {
+        now: AtomicU32,
+        logged: AtomicU32,
+    }
+
+    impl embedded_profiling::EmbeddedProfiler for CountingEP {
+        // `EPContainer` is only a `u64` with `container-u64`
+        #[allow(clippy::useless_conversion)]
+        fn read_clock(&self) -> embedded_profiling::EPInstant {
+            embedded_profiling::EPInstant::from_ticks(self.now.load(Ordering::SeqCst).into())
+        }
+
+        fn log_snapshot(&self, _snapshot: &embedded_profiling::EPSnapshot) {
+            self.logged.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    static INIT_PROFILER: Once = Once::new();
+    static TEST_PROFILER: CountingEP = CountingEP {
+        now: AtomicU32::new(0),
+        logged: AtomicU32::new(0),
+    };
+
+    fn logged_after(f: impl FnOnce()) -> u32 {
+        INIT_PROFILER.call_once(|| unsafe {
+            embedded_profiling::set_profiler(&TEST_PROFILER).unwrap();
+        });
+        let before = TEST_PROFILER.logged.load(Ordering::SeqCst);
+        f();
+        TEST_PROFILER.logged.load(Ordering::SeqCst) - before
+    }
+
+    fn spend(us: u32) {
+        TEST_PROFILER.now.fetch_add(us, Ordering::SeqCst);
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn min_us_threshold() {
+        #[embedded_profiling_proc_macros::profile_function(min_us = 10)]
+        fn takes(us: u32) {
+            spend(us);
+        }
+
+        assert_eq!(logged_after(|| takes(5)), 0);
+        assert_eq!(logged_after(|| takes(10)), 1);
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn compile_filter() {
+        #[embedded_profiling_proc_macros::profile_function(min_us = 10, compile_filter)]
+        fn takes(us: u32) {
+            spend(us);
+        }
+
+        #[cfg(not(feature = "compile-filter"))]
+        assert_eq!(logged_after(|| takes(20)), 1);
+        #[cfg(feature = "compile-filter")]
+        assert_eq!(logged_after(|| takes(20)), 0);
+    }
+}
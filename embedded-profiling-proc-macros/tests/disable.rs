@@ -0,0 +1,35 @@
+#![cfg(feature = "disable")]
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    struct CountingEP {
+        logged: AtomicU32,
+    }
+
+    impl embedded_profiling::EmbeddedProfiler for CountingEP {
+        fn read_clock(&self) -> embedded_profiling::EPInstant {
+            embedded_profiling::EPInstant::from_ticks(0)
+        }
+
+        fn log_snapshot(&self, _snapshot: &embedded_profiling::EPSnapshot) {
+            self.logged.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    static TEST_PROFILER: CountingEP = CountingEP {
+        logged: AtomicU32::new(0),
+    };
+
+    #[embedded_profiling_proc_macros::profile_function(profiler = TEST_PROFILER, max_us = 0)]
+    fn compiled_out(value: u8) -> u8 {
+        value + 1
+    }
+
+    #[test]
+    fn nothing_profiled() {
+        assert_eq!(compiled_out(1), 2);
+        assert_eq!(TEST_PROFILER.logged.load(Ordering::SeqCst), 0);
+    }
+}
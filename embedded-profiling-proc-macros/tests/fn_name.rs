@@ -1,3 +1,6 @@
+// the instrumentation is compiled out with `disable`
+#![cfg(not(feature = "disable"))]
+
 #[cfg(test)]
 mod test {
     struct TestEP {
@@ -25,6 +28,7 @@ mod test {
             if let Some(expected_name) = &self.expected_fn_name {
                 eprintln!("{:?} == {:?} ?", expected_name, snapshot.name);
                 assert_eq!(expected_name, snapshot.name);
+                assert_eq!(snapshot.target, Some(module_path!()));
             } else {
                 panic!("log_snapshot called without an expected fn name");
             }
@@ -36,6 +40,7 @@ mod test {
     static INIT_PROFILER: Once = Once::new();
     static mut TEST_PROFILER: Option<TestEP> = None;
 
+    #[allow(static_mut_refs)]
     fn set_profiler() {
         INIT_PROFILER.call_once(|| unsafe {
             if TEST_PROFILER.is_none() {
@@ -46,6 +51,7 @@ mod test {
     }
 
     /// super unsafe unless our tests are run serially, which we should do anyways
+    #[allow(static_mut_refs)]
     fn set_expected_fn_name(expected_name: &str) {
         unsafe {
             TEST_PROFILER
@@ -66,4 +72,16 @@ mod test {
 
         function_to_profile();
     }
+
+    #[test]
+    #[serial_test::serial]
+    fn name_argument_overrides() {
+        #[embedded_profiling_proc_macros::profile_function(name = "custom_label")]
+        fn function_to_profile() {}
+
+        set_profiler();
+        set_expected_fn_name("custom_label");
+
+        function_to_profile();
+    }
 }
@@ -0,0 +1,67 @@
+// the instrumentation is compiled out with `disable`
+#![cfg(not(feature = "disable"))]
+
+#[cfg(test)]
+mod test {
+    use std::sync::Mutex;
+
+    struct TestEP {
+        logged: Mutex<Vec<&'static str>>,
+    }
+
+    impl embedded_profiling::EmbeddedProfiler for TestEP {
+        fn read_clock(&self) -> embedded_profiling::EPInstant {
+            embedded_profiling::EPInstant::from_ticks(0)
+        }
+
+        fn log_snapshot(&self, snapshot: &embedded_profiling::EPSnapshot) {
+            self.logged.lock().unwrap().push(snapshot.name);
+        }
+    }
+
+    static TEST_PROFILER: TestEP = TestEP {
+        logged: Mutex::new(Vec::new()),
+    };
+
+    struct Driver<T> {
+        value: T,
+    }
+
+    #[embedded_profiling_proc_macros::profile_impl]
+    impl<T> Driver<T>
+    where
+        T: Copy,
+    {
+        const SCALE: u8 = 2;
+
+        fn read(&self) -> T {
+            self.value
+        }
+
+        fn scale(&self) -> u8 {
+            Self::SCALE
+        }
+
+        #[profile_skip]
+        fn is_ready(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn methods_profiled() {
+        unsafe {
+            embedded_profiling::set_profiler(&TEST_PROFILER).unwrap();
+        }
+
+        let driver = Driver { value: 7_u32 };
+        assert_eq!(driver.read(), 7);
+        assert_eq!(driver.scale(), 2);
+        assert!(driver.is_ready());
+
+        assert_eq!(
+            *TEST_PROFILER.logged.lock().unwrap(),
+            ["Driver::read", "Driver::scale"]
+        );
+    }
+}
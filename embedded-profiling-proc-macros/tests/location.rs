@@ -0,0 +1,42 @@
+// the instrumentation is compiled out with `disable`
+#![cfg(not(feature = "disable"))]
+
+#[cfg(test)]
+mod test {
+    use std::sync::Mutex;
+
+    struct TestEP {
+        location: Mutex<Option<(&'static str, u32)>>,
+    }
+
+    impl embedded_profiling::EmbeddedProfiler for TestEP {
+        fn read_clock(&self) -> embedded_profiling::EPInstant {
+            embedded_profiling::EPInstant::from_ticks(0)
+        }
+
+        fn log_snapshot(&self, snapshot: &embedded_profiling::EPSnapshot) {
+            *self.location.lock().unwrap() = snapshot.location;
+        }
+    }
+
+    static TEST_PROFILER: TestEP = TestEP {
+        location: Mutex::new(None),
+    };
+
+    #[test]
+    fn location_reaches_log_snapshot() {
+        let expected_line = line!() + 2;
+        #[embedded_profiling_proc_macros::profile_function]
+        fn located() {}
+
+        unsafe {
+            embedded_profiling::set_profiler(&TEST_PROFILER).unwrap();
+        }
+        located();
+
+        assert_eq!(
+            *TEST_PROFILER.location.lock().unwrap(),
+            Some((file!(), expected_line))
+        );
+    }
+}
@@ -0,0 +1,62 @@
+// the instrumentation is compiled out with `disable`
+#![cfg(not(feature = "disable"))]
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Advances by 100 us on every read.
+    struct SlowClockEP {
+        now: AtomicU32,
+        logged: AtomicU32,
+    }
+
+    impl embedded_profiling::EmbeddedProfiler for SlowClockEP {
+        // only a conversion with `container-u64`
+        #[allow(clippy::useless_conversion)]
+        fn read_clock(&self) -> embedded_profiling::EPInstant {
+            let now = self.now.load(Ordering::SeqCst) + 100;
+            self.now.store(now, Ordering::SeqCst);
+            embedded_profiling::EPInstant::from_ticks(now.into())
+        }
+
+        fn log_snapshot(&self, _snapshot: &embedded_profiling::EPSnapshot) {
+            self.logged
+                .store(self.logged.load(Ordering::SeqCst) + 1, Ordering::SeqCst);
+        }
+    }
+
+    static OVER_BUDGET_PROFILER: SlowClockEP = SlowClockEP {
+        now: AtomicU32::new(0),
+        logged: AtomicU32::new(0),
+    };
+    static WITHIN_BUDGET_PROFILER: SlowClockEP = SlowClockEP {
+        now: AtomicU32::new(0),
+        logged: AtomicU32::new(0),
+    };
+
+    #[embedded_profiling_proc_macros::profile_function(profiler = OVER_BUDGET_PROFILER, max_us = 50)]
+    fn over_budget() {}
+
+    #[embedded_profiling_proc_macros::profile_function(profiler = WITHIN_BUDGET_PROFILER, max_us = 100)]
+    fn within_budget() -> u8 {
+        7
+    }
+
+    #[test]
+    #[cfg_attr(
+        debug_assertions,
+        should_panic(expected = "`over_budget` exceeded its budget of 50 us")
+    )]
+    fn over_budget_trips() {
+        over_budget();
+        // only checked in debug builds, still logged in release ones
+        assert_eq!(OVER_BUDGET_PROFILER.logged.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn within_budget_passes() {
+        assert_eq!(within_budget(), 7);
+        assert_eq!(WITHIN_BUDGET_PROFILER.logged.load(Ordering::SeqCst), 1);
+    }
+}
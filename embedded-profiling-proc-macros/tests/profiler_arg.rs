@@ -0,0 +1,44 @@
+// the instrumentation is compiled out with `disable`
+#![cfg(not(feature = "disable"))]
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    struct CountingEP {
+        logged: AtomicU32,
+    }
+
+    impl embedded_profiling::EmbeddedProfiler for CountingEP {
+        fn read_clock(&self) -> embedded_profiling::EPInstant {
+            embedded_profiling::EPInstant::from_ticks(0)
+        }
+
+        fn log_snapshot(&self, snapshot: &embedded_profiling::EPSnapshot) {
+            assert_eq!(snapshot.name, "subsystem_function");
+            self.logged.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    static GLOBAL_PROFILER: CountingEP = CountingEP {
+        logged: AtomicU32::new(0),
+    };
+    static SUBSYSTEM_PROFILER: CountingEP = CountingEP {
+        logged: AtomicU32::new(0),
+    };
+
+    #[embedded_profiling_proc_macros::profile_function(profiler = SUBSYSTEM_PROFILER)]
+    fn subsystem_function() {}
+
+    #[test]
+    fn logs_to_given_profiler() {
+        unsafe {
+            embedded_profiling::set_profiler(&GLOBAL_PROFILER).unwrap();
+        }
+
+        subsystem_function();
+
+        assert_eq!(SUBSYSTEM_PROFILER.logged.load(Ordering::SeqCst), 1);
+        assert_eq!(GLOBAL_PROFILER.logged.load(Ordering::SeqCst), 0);
+    }
+}
@@ -0,0 +1,76 @@
+// the instrumentation is compiled out with `disable`
+#![cfg(not(feature = "disable"))]
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    struct CountingEP {
+        logged: AtomicU32,
+    }
+
+    impl embedded_profiling::EmbeddedProfiler for CountingEP {
+        fn read_clock(&self) -> embedded_profiling::EPInstant {
+            embedded_profiling::EPInstant::from_ticks(0)
+        }
+
+        fn log_snapshot(&self, _snapshot: &embedded_profiling::EPSnapshot) {
+            self.logged.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    static VALUE_PROFILER: CountingEP = CountingEP {
+        logged: AtomicU32::new(0),
+    };
+    static EARLY_PROFILER: CountingEP = CountingEP {
+        logged: AtomicU32::new(0),
+    };
+
+    #[embedded_profiling_proc_macros::profile_function(profiler = VALUE_PROFILER)]
+    fn owned(count: usize) -> String {
+        "ab".repeat(count)
+    }
+
+    #[embedded_profiling_proc_macros::profile_function(profiler = VALUE_PROFILER)]
+    fn borrowed(text: &str) -> &str {
+        text.trim()
+    }
+
+    #[embedded_profiling_proc_macros::profile_function(profiler = EARLY_PROFILER)]
+    fn parsed(text: &str) -> Result<u32, core::num::ParseIntError> {
+        let value: u32 = text.parse()?;
+        Ok(value * 2)
+    }
+
+    #[test]
+    fn value_returned_and_logged() {
+        assert_eq!(owned(2), "abab");
+        assert_eq!(borrowed("  trimmed "), "trimmed");
+        assert_eq!(VALUE_PROFILER.logged.load(Ordering::SeqCst), 2);
+    }
+
+    #[embedded_profiling_proc_macros::profile_function]
+    fn checked(value: u32) -> Option<u32> {
+        if value == 0 {
+            return None;
+        }
+        value.checked_mul(2)
+    }
+
+    #[test]
+    fn early_return_value() {
+        assert_eq!(parsed("21"), Ok(42));
+        assert!(parsed("not a number").is_err());
+        // `?` still ends the snapshot, so both calls log
+        assert_eq!(EARLY_PROFILER.logged.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn early_return_restores_depth() {
+        let depth = embedded_profiling::nesting_depth();
+        assert_eq!(checked(0), None);
+        assert_eq!(checked(u32::MAX), None);
+        assert_eq!(checked(21), Some(42));
+        assert_eq!(embedded_profiling::nesting_depth(), depth);
+    }
+}
@@ -0,0 +1,48 @@
+// the instrumentation is compiled out with `disable`
+#![cfg(not(feature = "disable"))]
+#![deny(unused_unsafe)]
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    struct TestEP {
+        logged: AtomicBool,
+    }
+
+    impl embedded_profiling::EmbeddedProfiler for TestEP {
+        fn read_clock(&self) -> embedded_profiling::EPInstant {
+            embedded_profiling::EPInstant::from_ticks(0)
+        }
+
+        fn log_snapshot(&self, snapshot: &embedded_profiling::EPSnapshot) {
+            assert_eq!("write_through", snapshot.name);
+            self.logged.store(true, Ordering::SeqCst);
+        }
+    }
+
+    static TEST_PROFILER: TestEP = TestEP {
+        logged: AtomicBool::new(false),
+    };
+
+    /// dereferencing a raw pointer only compiles if the body is still in an unsafe context
+    #[embedded_profiling_proc_macros::profile_function]
+    unsafe fn write_through(ptr: *mut u32, value: u32) {
+        *ptr = value;
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn unsafe_fn_stays_unsafe() {
+        unsafe {
+            embedded_profiling::set_profiler(&TEST_PROFILER).unwrap();
+        }
+
+        let write: unsafe fn(*mut u32, u32) = write_through;
+        let mut target = 0;
+        unsafe { write(&mut target, 42) };
+
+        assert_eq!(target, 42);
+        assert!(TEST_PROFILER.logged.load(Ordering::SeqCst));
+    }
+}
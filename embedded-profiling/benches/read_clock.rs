@@ -0,0 +1,54 @@
+//! Compares reading the global profiler's clock through `&dyn EmbeddedProfiler` with
+//! reading it through a typed [`ClockHandle`].
+//!
+//! Run with `cargo bench -p embedded-profiling`.
+
+use embedded_profiling::{ClockHandle, EPInstant, EmbeddedProfiler};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+
+/// A clock that moves by a tick on every read, cheap enough for the call to dominate.
+struct CountingClock(AtomicU32);
+
+impl EmbeddedProfiler for CountingClock {
+    // only a conversion with `container-u64`
+    #[allow(clippy::useless_conversion)]
+    fn read_clock(&self) -> EPInstant {
+        let now = self.0.load(Ordering::Relaxed);
+        self.0.store(now.wrapping_add(1), Ordering::Relaxed);
+        EPInstant::from_ticks(now.into())
+    }
+}
+
+static CLOCK: CountingClock = CountingClock(AtomicU32::new(0));
+
+const READS: u32 = 10_000_000;
+
+fn measure(label: &str, mut read: impl FnMut() -> EPInstant) {
+    // fold the reads into a checksum that is printed, so they can't be optimized out
+    let mut checksum = 0;
+    let start = Instant::now();
+    for _ in 0..READS {
+        checksum ^= read().ticks();
+    }
+    let elapsed = start.elapsed();
+    let per_read = elapsed.as_nanos() as f64 / f64::from(READS);
+    println!(
+        "{:>6}: {:?} for {} reads, {:.2} ns/read (checksum {})",
+        label, elapsed, READS, per_read, checksum
+    );
+}
+
+fn main() {
+    unsafe {
+        embedded_profiling::set_profiler(&CLOCK).unwrap();
+    }
+    let clock = ClockHandle::global(&CLOCK).unwrap();
+
+    // warm up
+    measure("warmup", || embedded_profiling::profiler().read_clock());
+    std::thread::sleep(Duration::from_millis(10));
+
+    measure("dyn", || embedded_profiling::profiler().read_clock());
+    measure("typed", || clock.read_clock());
+}
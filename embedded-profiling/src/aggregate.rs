@@ -0,0 +1,283 @@
+//! Wrapper accumulating statistics of every trace since boot.
+
+use crate::stats::{Stats, StatsTable};
+use crate::{EPDuration, EPInstant, EPSnapshot, EmbeddedProfiler};
+use core::cell::{Cell, RefCell};
+
+/// Wraps the [`EmbeddedProfiler`] `P`, accumulating [`Stats`] of every logged snapshot
+/// instead of logging them.
+///
+/// Statistics are kept for up to `N` distinct names. Snapshots of any further names are
+/// logged individually by `P` as usual. Unlike [`CoalescingProfiler`](crate::CoalescingProfiler),
+/// nothing is ever reset, so the statistics cover everything since the profiler was created.
+///
+/// An [implausible threshold](AggregatingProfiler::with_implausible_threshold) can be set to
+/// keep snapshots that are too long to be real measurements out of the statistics.
+///
+/// The accumulated statistics are not safe to access reentrantly, so don't log snapshots
+/// from an interrupt that can preempt reading them. Use
+/// [`StatsProfiler`](crate::StatsProfiler) for that instead.
+///
+/// For a soak test, profile in a loop and dump the table on demand, with
+/// [`clear`](AggregatingProfiler::clear) to start over:
+///
+/// ```
+/// # use embedded_profiling::*;
+/// # struct MyClock;
+/// # impl EmbeddedProfiler for MyClock { fn read_clock(&self) -> EPInstant {EPInstant::from_ticks(0)} }
+/// let profiler = AggregatingProfiler::<_, 8>::new(MyClock);
+/// for _ in 0..1_000 {
+///     profiler.profile("soak", || {});
+/// }
+/// profiler.for_each(|stats| println!("{}", stats));
+/// assert_eq!(profiler.stats("soak").unwrap().count, 1_000);
+///
+/// profiler.clear();
+/// assert!(profiler.stats("soak").is_none());
+/// ```
+pub struct AggregatingProfiler<P, const N: usize> {
+    inner: P,
+    table: RefCell<StatsTable<N>>,
+    implausible_threshold: Option<EPDuration>,
+    last_report: Cell<Option<EPInstant>>,
+}
+
+impl<P, const N: usize> AggregatingProfiler<P, N> {
+    /// Creates a new [`AggregatingProfiler`] around the profiler `inner`.
+    #[must_use]
+    pub const fn new(inner: P) -> Self {
+        Self {
+            inner,
+            table: RefCell::new(StatsTable::new()),
+            implausible_threshold: None,
+            last_report: Cell::new(None),
+        }
+    }
+
+    /// Marks snapshots longer than `threshold` as [`suspect`](EPSnapshot::suspect). Instead of
+    /// being recorded, they are logged by the wrapped profiler right away so measurement
+    /// problems (a wrong clock frequency, a stalled clock, a `wfi` inside the trace) are
+    /// noticed immediately.
+    #[must_use]
+    pub const fn with_implausible_threshold(mut self, threshold: EPDuration) -> Self {
+        self.implausible_threshold = Some(threshold);
+        self
+    }
+
+    /// Consumes [`AggregatingProfiler`], returning the wrapped profiler.
+    pub fn free(self) -> P {
+        self.inner
+    }
+
+    /// The statistics of the trace `name`, if any of its snapshots were logged.
+    #[must_use]
+    pub fn stats(&self, name: &str) -> Option<Stats> {
+        self.table.borrow().get(name).copied()
+    }
+
+    /// Calls `f` with the statistics of every recorded trace.
+    pub fn for_each(&self, f: impl FnMut(&Stats)) {
+        self.table.borrow().iter().for_each(f);
+    }
+
+    /// The name of every trace recorded at least once, in the order they were first seen.
+    ///
+    /// The names are copied out, so the profiler can keep recording while iterating.
+    pub fn names(&self) -> impl Iterator<Item = &'static str> {
+        let mut names = [None; N];
+        for (name, stats) in names.iter_mut().zip(self.table.borrow().iter()) {
+            *name = Some(stats.name);
+        }
+        names.into_iter().flatten()
+    }
+
+    /// Forgets the statistics of every trace.
+    pub fn clear(&self) {
+        self.table.borrow_mut().clear();
+    }
+
+    /// The accumulated duration of all snapshots of the trace `name`, zero if there were none.
+    #[must_use]
+    pub fn total(&self, name: &str) -> EPDuration {
+        self.stats(name)
+            .map_or_else(|| EPDuration::from_ticks(0), |stats| stats.total)
+    }
+}
+
+impl<P, const N: usize> AggregatingProfiler<P, N>
+where
+    P: EmbeddedProfiler,
+{
+    /// The fraction of the time since `since` that was spent in the trace `name`, between
+    /// `0.0` and `1.0` for traces that don't overlap themselves.
+    ///
+    /// `since` should be at or before the first snapshot of `name`, e.g. the instant this
+    /// profiler was installed, or the result is meaningless. Returns `0.0` if no time
+    /// has passed since `since`.
+    #[must_use]
+    pub fn duty_cycle(&self, name: &str, since: EPInstant) -> f32 {
+        let elapsed = match self.inner.read_clock().checked_duration_since(since) {
+            Some(elapsed) if elapsed.ticks() > 0 => elapsed,
+            _ => return 0.0,
+        };
+        crate::ratio(self.total(name), elapsed)
+    }
+
+    /// Reports the [`Stats`] of every recorded trace to `report` if `interval` has elapsed
+    /// since the last report, for periodic reporting from a timer task or the idle loop.
+    ///
+    /// The [`Display`](core::fmt::Display) of [`Stats`] is a compact summary line, so `report`
+    /// can simply log it. The first call only starts the interval, so the first report is
+    /// due one `interval` after it.
+    ///
+    /// The statistics aren't reset by reporting, so every report covers everything since
+    /// the profiler was created. Call [`clear`](AggregatingProfiler::clear) after reporting
+    /// to report each interval on its own instead.
+    ///
+    /// Returns whether a report was made.
+    ///
+    /// ```
+    /// # use embedded_profiling::*;
+    /// # struct MyClock;
+    /// # impl EmbeddedProfiler for MyClock { fn read_clock(&self) -> EPInstant {EPInstant::from_ticks(0)} }
+    /// let profiler = AggregatingProfiler::<_, 8>::new(MyClock);
+    /// // from the idle loop
+    /// profiler.report_if_due(profiler.read_clock(), EPDuration::from_ticks(1_000_000), |stats| {
+    ///     println!("{}", stats);
+    /// });
+    /// ```
+    pub fn report_if_due(
+        &self,
+        now: EPInstant,
+        interval: EPDuration,
+        report: impl FnMut(&Stats),
+    ) -> bool {
+        let last_report = match self.last_report.get() {
+            Some(last_report) => last_report,
+            None => {
+                self.last_report.set(Some(now));
+                return false;
+            }
+        };
+
+        match now.checked_duration_since(last_report) {
+            Some(elapsed) if elapsed >= interval => {}
+            _ => return false,
+        }
+
+        self.for_each(report);
+        self.last_report.set(Some(now));
+        true
+    }
+}
+
+impl<P, const N: usize> EmbeddedProfiler for AggregatingProfiler<P, N>
+where
+    P: EmbeddedProfiler,
+{
+    fn log_snapshot(&self, snapshot: &EPSnapshot) {
+        if let Some(threshold) = self.implausible_threshold {
+            if snapshot.duration > threshold {
+                self.inner.log_snapshot(&snapshot.with_suspect(true));
+                return;
+            }
+        }
+
+        if !self.table.borrow_mut().record(snapshot) {
+            self.inner.log_snapshot(snapshot);
+        }
+    }
+
+    forward_profiler!(self.inner => clock, hooks, snapshots);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::{FakeClock, StdMockProfiler};
+
+    #[test]
+    fn total_and_duty_cycle() {
+        let profiler = AggregatingProfiler::<_, 4>::new(FakeClock::default());
+        let boot = profiler.read_clock();
+
+        for _ in 0..4 {
+            let start = profiler.start_snapshot("isr");
+            profiler.inner.advance(10);
+            let snapshot = profiler.end_snapshot(start, "isr").unwrap();
+            profiler.log_snapshot(&snapshot);
+            // time outside of the region
+            profiler.inner.advance(15);
+        }
+
+        assert_eq!(profiler.total("isr").ticks(), 40);
+        assert_eq!(profiler.stats("isr").unwrap().count, 4);
+        assert!((profiler.duty_cycle("isr", boot) - 0.4).abs() < f32::EPSILON);
+
+        assert_eq!(profiler.total("idle").ticks(), 0);
+        assert!(profiler.duty_cycle("idle", boot).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn names_of_recorded_traces() {
+        let profiler = AggregatingProfiler::<_, 4>::new(FakeClock::default());
+        assert_eq!(profiler.names().count(), 0);
+
+        for name in ["poll", "isr", "poll", "render"] {
+            profiler.log_snapshot(&EPSnapshot::new(name, EPDuration::from_ticks(1)));
+        }
+
+        let names: Vec<_> = profiler.names().collect();
+        assert_eq!(names, ["poll", "isr", "render"]);
+    }
+
+    #[test]
+    fn report_fires_once_per_interval() {
+        let profiler = AggregatingProfiler::<_, 4>::new(FakeClock::default());
+        let clock = FakeClock::default();
+        let interval = EPDuration::from_ticks(1_000);
+        let mut lines = Vec::new();
+        let mut report = |stats: &Stats| lines.push(stats.to_string());
+
+        profiler.log_snapshot(&EPSnapshot::new("report_poll", EPDuration::from_ticks(4)));
+        profiler.log_snapshot(&EPSnapshot::new("report_poll", EPDuration::from_ticks(6)));
+        assert!(!profiler.report_if_due(clock.read_clock(), interval, &mut report));
+
+        clock.advance(999);
+        assert!(!profiler.report_if_due(clock.read_clock(), interval, &mut report));
+
+        clock.advance(1);
+        assert!(profiler.report_if_due(clock.read_clock(), interval, &mut report));
+        assert!(!profiler.report_if_due(clock.read_clock(), interval, &mut report));
+
+        assert_eq!(lines, ["report_poll: count=2 min=4us avg=5us max=6us"]);
+    }
+
+    #[test]
+    fn implausible_snapshot_flagged() {
+        let profiler = AggregatingProfiler::<_, 4>::new(StdMockProfiler::default())
+            .with_implausible_threshold(EPDuration::from_ticks(1_000));
+
+        profiler.log_snapshot(&EPSnapshot::new("poll", EPDuration::from_ticks(1_000)));
+        profiler.log_snapshot(&EPSnapshot::new("poll", EPDuration::from_ticks(2_000_000)));
+
+        // the plausible one is recorded, the other one is logged as suspect
+        assert_eq!(profiler.total("poll").ticks(), 1_000);
+        let logged = profiler.inner.last_logged("poll").unwrap();
+        assert!(logged.suspect);
+        assert_eq!(logged.duration.ticks(), 2_000_000);
+    }
+
+    #[test]
+    fn threshold_from_core_duration() {
+        let threshold = crate::duration_from_core(core::time::Duration::from_millis(5));
+        let profiler = AggregatingProfiler::<_, 4>::new(StdMockProfiler::default())
+            .with_implausible_threshold(threshold);
+
+        profiler.log_snapshot(&EPSnapshot::new("poll", EPDuration::from_ticks(5_000)));
+        profiler.log_snapshot(&EPSnapshot::new("poll", EPDuration::from_ticks(5_001)));
+
+        assert_eq!(profiler.total("poll").ticks(), 5_000);
+        assert!(profiler.inner.last_logged("poll").unwrap().suspect);
+    }
+}
@@ -0,0 +1,209 @@
+//! Composing a clock, statistics and an output sink into one profiler.
+
+use crate::stats::{Stats, StatsTable};
+use crate::{EPSnapshot, EmbeddedProfiler};
+use core::cell::RefCell;
+
+/// Where a [`ComposedProfiler`] emits the snapshots it logs.
+pub trait Sink {
+    /// Emits `snapshot`, logged by a profiler reading `clock`.
+    fn emit<C: EmbeddedProfiler>(&self, clock: &C, snapshot: &EPSnapshot);
+}
+
+/// The default [`Sink`], logging snapshots with the clock's own
+/// [`log_snapshot`](EmbeddedProfiler::log_snapshot).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ClockSink;
+
+impl Sink for ClockSink {
+    fn emit<C: EmbeddedProfiler>(&self, clock: &C, snapshot: &EPSnapshot) {
+        clock.log_snapshot(snapshot);
+    }
+}
+
+/// Any `Fn(&EPSnapshot)` is a [`Sink`], like a closure writing to a serial port.
+impl<F> Sink for F
+where
+    F: Fn(&EPSnapshot),
+{
+    fn emit<C: EmbeddedProfiler>(&self, _clock: &C, snapshot: &EPSnapshot) {
+        self(snapshot);
+    }
+}
+
+/// Builds a [`ComposedProfiler`] from a clock profiler, optional statistics and a [`Sink`].
+///
+/// The layers are always put together in the same order, whatever order the builder's
+/// methods are called in:
+///
+/// 1. the [clock](ProfilerBuilder::clock) profiler reads the time and runs the
+///    [`at_start`](EmbeddedProfiler::at_start)/[`at_end`](EmbeddedProfiler::at_end) hooks,
+/// 2. every logged snapshot first updates the [statistics](ProfilerBuilder::aggregate), if any,
+/// 3. and is then emitted to the [sink](ProfilerBuilder::sink), by default the clock
+///    profiler's own [`log_snapshot`](EmbeddedProfiler::log_snapshot).
+///
+/// ```
+/// # use embedded_profiling::*;
+/// # struct MyClock;
+/// # impl EmbeddedProfiler for MyClock { fn read_clock(&self) -> EPInstant {EPInstant::from_ticks(0)} }
+/// let profiler = ProfilerBuilder::new()
+///     .clock(MyClock)
+///     .aggregate::<8>()
+///     .sink(|snapshot: &EPSnapshot| println!("{}", snapshot))
+///     .build();
+/// profiler.log_snapshot(&EPSnapshot::new("built", EPDuration::from_ticks(3)));
+/// assert_eq!(profiler.stats("built").unwrap().count, 1);
+/// ```
+pub struct ProfilerBuilder<C, S, const N: usize> {
+    clock: C,
+    sink: S,
+}
+
+impl ProfilerBuilder<(), ClockSink, 0> {
+    /// Starts building a profiler without statistics, logging to the clock profiler.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            clock: (),
+            sink: ClockSink,
+        }
+    }
+}
+
+impl Default for ProfilerBuilder<(), ClockSink, 0> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C, S, const N: usize> ProfilerBuilder<C, S, N> {
+    /// Reads the time from the profiler `clock`.
+    #[must_use]
+    pub fn clock<T>(self, clock: T) -> ProfilerBuilder<T, S, N>
+    where
+        T: EmbeddedProfiler,
+    {
+        ProfilerBuilder {
+            clock,
+            sink: self.sink,
+        }
+    }
+
+    /// Accumulates [`Stats`] of every logged snapshot for up to `M` distinct names.
+    #[must_use]
+    pub fn aggregate<const M: usize>(self) -> ProfilerBuilder<C, S, M> {
+        ProfilerBuilder {
+            clock: self.clock,
+            sink: self.sink,
+        }
+    }
+
+    /// Emits every logged snapshot to `sink`.
+    #[must_use]
+    pub fn sink<T>(self, sink: T) -> ProfilerBuilder<C, T, N>
+    where
+        T: Sink,
+    {
+        ProfilerBuilder {
+            clock: self.clock,
+            sink,
+        }
+    }
+}
+
+impl<C, S, const N: usize> ProfilerBuilder<C, S, N>
+where
+    C: EmbeddedProfiler,
+    S: Sink,
+{
+    /// Builds the [`ComposedProfiler`].
+    #[must_use]
+    pub fn build(self) -> ComposedProfiler<C, S, N> {
+        ComposedProfiler {
+            clock: self.clock,
+            sink: self.sink,
+            table: RefCell::new(StatsTable::new()),
+        }
+    }
+}
+
+/// A clock profiler, statistics of up to `N` names and a [`Sink`], built by [`ProfilerBuilder`].
+///
+/// Like [`AggregatingProfiler`](crate::AggregatingProfiler), the statistics are not safe to
+/// access reentrantly, so don't log snapshots from an interrupt that can preempt reading them.
+pub struct ComposedProfiler<C, S, const N: usize> {
+    clock: C,
+    sink: S,
+    table: RefCell<StatsTable<N>>,
+}
+
+impl<C, S, const N: usize> ComposedProfiler<C, S, N> {
+    /// Consumes [`ComposedProfiler`], returning the clock profiler and the sink.
+    pub fn free(self) -> (C, S) {
+        (self.clock, self.sink)
+    }
+
+    /// The statistics of the trace `name`, if any of its snapshots were logged.
+    #[must_use]
+    pub fn stats(&self, name: &str) -> Option<Stats> {
+        self.table.borrow().get(name).copied()
+    }
+
+    /// Calls `f` with the statistics of every recorded trace.
+    pub fn for_each(&self, f: impl FnMut(&Stats)) {
+        self.table.borrow().iter().for_each(f);
+    }
+}
+
+impl<C, S, const N: usize> EmbeddedProfiler for ComposedProfiler<C, S, N>
+where
+    C: EmbeddedProfiler,
+    S: Sink,
+{
+    fn log_snapshot(&self, snapshot: &EPSnapshot) {
+        // a full table only means that this name isn't counted, it's still emitted
+        let _ = self.table.borrow_mut().record(snapshot);
+        self.sink.emit(&self.clock, snapshot);
+    }
+
+    forward_profiler!(self.clock => clock, hooks, snapshots);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::{FakeClock, StdMockProfiler};
+    use crate::EPDuration;
+
+    #[test]
+    fn full_stack() {
+        let emitted = RefCell::new(Vec::new());
+        let profiler = ProfilerBuilder::new()
+            .sink(|snapshot: &EPSnapshot| emitted.borrow_mut().push(snapshot.name))
+            .aggregate::<4>()
+            .clock(FakeClock::default())
+            .build();
+
+        for _ in 0..3 {
+            let start = profiler.start_snapshot("stacked");
+            profiler.clock.advance(10);
+            let snapshot = profiler.end_snapshot(start, "stacked").unwrap();
+            profiler.log_snapshot(&snapshot);
+        }
+
+        let stats = profiler.stats("stacked").unwrap();
+        assert_eq!(stats.count, 3);
+        assert_eq!(stats.total.ticks(), 30);
+        assert_eq!(*emitted.borrow(), ["stacked"; 3]);
+    }
+
+    #[test]
+    fn clock_sink_by_default() {
+        let profiler = ProfilerBuilder::new()
+            .clock(StdMockProfiler::default())
+            .build();
+        profiler.log_snapshot(&EPSnapshot::new("clock_sink", EPDuration::from_ticks(1)));
+        assert!(profiler.clock.has_logged("clock_sink"));
+        assert!(profiler.stats("clock_sink").is_none());
+    }
+}
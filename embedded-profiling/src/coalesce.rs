@@ -0,0 +1,139 @@
+//! Wrapper summarizing high frequency snapshots per time window.
+
+use crate::stats::{Stats, StatsTable};
+use crate::{EPDuration, EPInstant, EPSnapshot, EmbeddedProfiler};
+use core::cell::{Cell, RefCell};
+
+/// Wraps the [`EmbeddedProfiler`] `P`, coalescing snapshots into one summary per trace name
+/// and time window instead of logging every one of them.
+///
+/// Logged snapshots are accumulated into [`Stats`] for up to `N` distinct names. Snapshots of
+/// any further names are logged individually by `P` as usual.
+///
+/// Nothing is emitted on its own: [`CoalescingProfiler::tick`] must be called periodically
+/// (from the idle loop or a timer task, for example). A window starts with the first call to
+/// [`tick`](CoalescingProfiler::tick) and is closed by the first call after `window` has
+/// elapsed, so the effective window is rounded up to the tick period. Snapshots logged before
+/// the first tick count towards the first window.
+///
+/// The accumulated statistics are not safe to access reentrantly, so don't log snapshots
+/// from an interrupt that can preempt [`tick`](CoalescingProfiler::tick).
+pub struct CoalescingProfiler<P, const N: usize> {
+    inner: P,
+    window: EPDuration,
+    window_start: Cell<Option<EPInstant>>,
+    table: RefCell<StatsTable<N>>,
+}
+
+impl<P, const N: usize> CoalescingProfiler<P, N> {
+    /// Creates a new [`CoalescingProfiler`] around the profiler `inner`, summarizing every `window`.
+    #[must_use]
+    pub const fn new(inner: P, window: EPDuration) -> Self {
+        Self {
+            inner,
+            window,
+            window_start: Cell::new(None),
+            table: RefCell::new(StatsTable::new()),
+        }
+    }
+
+    /// Consumes [`CoalescingProfiler`], returning the wrapped profiler.
+    pub fn free(self) -> P {
+        self.inner
+    }
+}
+
+impl<P, const N: usize> CoalescingProfiler<P, N>
+where
+    P: EmbeddedProfiler,
+{
+    /// Closes the current window if it has elapsed, calling `emit` once for every trace
+    /// recorded during it.
+    ///
+    /// Returns whether the window was closed.
+    pub fn tick(&self, mut emit: impl FnMut(&Stats)) -> bool {
+        let now = self.inner.read_clock();
+        let window_start = match self.window_start.get() {
+            Some(window_start) => window_start,
+            None => {
+                self.window_start.set(Some(now));
+                return false;
+            }
+        };
+
+        match now.checked_duration_since(window_start) {
+            Some(elapsed) if elapsed >= self.window => {}
+            _ => return false,
+        }
+
+        let mut table = self.table.borrow_mut();
+        table.iter().for_each(&mut emit);
+        table.clear();
+        self.window_start.set(Some(now));
+        true
+    }
+}
+
+impl<P, const N: usize> EmbeddedProfiler for CoalescingProfiler<P, N>
+where
+    P: EmbeddedProfiler,
+{
+    fn log_snapshot(&self, snapshot: &EPSnapshot) {
+        if !self.table.borrow_mut().record(snapshot) {
+            self.inner.log_snapshot(snapshot);
+        }
+    }
+
+    forward_profiler!(self.inner => clock, hooks, snapshots);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::FakeClock;
+
+    #[test]
+    fn single_summary_per_window() {
+        let profiler =
+            CoalescingProfiler::<_, 4>::new(FakeClock::default(), EPDuration::from_ticks(1_000));
+        assert!(!profiler.tick(|_| panic!("nothing recorded yet")));
+
+        for duration in 1..=100 {
+            let start = profiler.start_snapshot("hot");
+            profiler.inner.advance(duration);
+            let snapshot = profiler.end_snapshot(start, "hot").unwrap();
+            profiler.log_snapshot(&snapshot);
+        }
+
+        // way more than the window has passed now
+        let mut emitted = Vec::new();
+        assert!(profiler.tick(|stats| emitted.push(*stats)));
+        assert_eq!(emitted.len(), 1);
+
+        let stats = emitted[0];
+        assert_eq!(stats.name, "hot");
+        assert_eq!(stats.count, 100);
+        assert_eq!(stats.min.ticks(), 1);
+        assert_eq!(stats.max.ticks(), 100);
+        assert_eq!(stats.total.ticks(), 5_050);
+
+        // the window was reset along with the stats
+        assert!(!profiler.tick(|_| panic!("window not elapsed")));
+    }
+
+    #[test]
+    fn window_not_elapsed() {
+        let profiler =
+            CoalescingProfiler::<_, 4>::new(FakeClock::default(), EPDuration::from_ticks(1_000));
+        assert!(!profiler.tick(|_| {}));
+
+        profiler.log_snapshot(&EPSnapshot::new("hot", EPDuration::from_ticks(5)));
+        profiler.inner.advance(999);
+        assert!(!profiler.tick(|_| panic!("window not elapsed")));
+
+        profiler.inner.advance(1);
+        let mut emitted = 0;
+        assert!(profiler.tick(|_| emitted += 1));
+        assert_eq!(emitted, 1);
+    }
+}
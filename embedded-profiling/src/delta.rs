@@ -0,0 +1,319 @@
+//! Compact encoding of snapshot durations for bulk transfer over slow links.
+//!
+//! Consecutive snapshots of the same trace usually have similar durations, so
+//! [`DeltaEncoder`] only sends the first duration of each name in full, and every following
+//! one as the difference to the previous duration of that name. Differences are zig-zag
+//! encoded varints, so stable workloads take two or three bytes per snapshot.
+//!
+//! Each encoded record is one of
+//! - a definition: the varint `id << 1 | 1`, the varint length of the name, the name's bytes
+//!   and the varint duration in microseconds,
+//! - a delta: the varint `id << 1` and the zig-zag varint difference to the last duration
+//!   of `id`.
+//!
+//! The matching [`DeltaDecoder`] is available with the `std` feature, for the host side.
+//!
+//! ```
+//! # use embedded_profiling::*;
+//! # #[cfg(feature = "std")]
+//! # {
+//! let mut encoder = delta::DeltaEncoder::<4>::new();
+//! let mut buffer = [0; 64];
+//! let mut len = 0;
+//! for ticks in [1_000, 1_002, 998] {
+//!     let snapshot = EPSnapshot::new("poll", EPDuration::from_ticks(ticks));
+//!     len += encoder.encode(&snapshot, &mut buffer[len..]).unwrap();
+//! }
+//!
+//! let decoded = delta::DeltaDecoder::default().decode(&buffer[..len]).unwrap();
+//! assert_eq!(decoded[2], ("poll".to_string(), EPDuration::from_ticks(998)));
+//! # }
+//! ```
+
+use crate::EPSnapshot;
+
+/// Encodes snapshot durations relative to the previous duration of the same name.
+///
+/// Up to `N` names are tracked. Snapshots of any further names are always encoded as a
+/// full definition, so they still decode correctly, just without the savings.
+pub struct DeltaEncoder<const N: usize> {
+    names: [Option<&'static str>; N],
+    last: [u64; N],
+}
+
+impl<const N: usize> DeltaEncoder<N> {
+    /// Creates a new [`DeltaEncoder`] that hasn't seen any names yet.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            names: [None; N],
+            last: [0; N],
+        }
+    }
+
+    /// Encodes the duration of `snapshot` into `out`, returning how many bytes were written.
+    ///
+    /// Returns `None` without changing any state if `out` is too small for the record, so
+    /// the snapshot can be encoded again into the next buffer.
+    pub fn encode(&mut self, snapshot: &EPSnapshot, out: &mut [u8]) -> Option<usize> {
+        // only a conversion without `container-u64`
+        #[allow(clippy::useless_conversion)]
+        let duration = u64::from(snapshot.duration.ticks());
+        let mut cursor = Cursor { out, len: 0 };
+
+        let slot = self
+            .names
+            .iter()
+            .position(|name| name.map_or(true, |name| name == snapshot.name));
+        match slot {
+            Some(id) if self.names[id].is_some() => {
+                #[allow(clippy::cast_possible_wrap)]
+                let delta = (duration as i64).wrapping_sub(self.last[id] as i64);
+                cursor.varint((id as u64) << 1)?;
+                cursor.varint(zigzag(delta))?;
+            }
+            // a new name, or the table is full and the untracked id `N` is used
+            _ => {
+                let id = slot.unwrap_or(N);
+                cursor.varint((id as u64) << 1 | 1)?;
+                cursor.varint(snapshot.name.len() as u64)?;
+                cursor.bytes(snapshot.name.as_bytes())?;
+                cursor.varint(duration)?;
+                if let Some(name) = self.names.get_mut(id) {
+                    *name = Some(snapshot.name);
+                }
+            }
+        }
+
+        if let Some(last) = self.last.get_mut(slot.unwrap_or(N)) {
+            *last = duration;
+        }
+        Some(cursor.len)
+    }
+}
+
+impl<const N: usize> Default for DeltaEncoder<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Maps signed values to unsigned ones so small magnitudes stay small varints.
+#[allow(clippy::cast_sign_loss)]
+const fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+/// Writes into a byte slice, failing once it is full.
+struct Cursor<'a> {
+    out: &'a mut [u8],
+    len: usize,
+}
+
+impl Cursor<'_> {
+    fn byte(&mut self, byte: u8) -> Option<()> {
+        *self.out.get_mut(self.len)? = byte;
+        self.len += 1;
+        Some(())
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) -> Option<()> {
+        self.out
+            .get_mut(self.len..self.len + bytes.len())?
+            .copy_from_slice(bytes);
+        self.len += bytes.len();
+        Some(())
+    }
+
+    fn varint(&mut self, mut value: u64) -> Option<()> {
+        while value >= 0x80 {
+            // the low 7 bits, with the continuation bit set
+            #[allow(clippy::cast_possible_truncation)]
+            self.byte(value as u8 | 0x80)?;
+            value >>= 7;
+        }
+        #[allow(clippy::cast_possible_truncation)]
+        self.byte(value as u8)
+    }
+}
+
+#[cfg(feature = "std")]
+pub use decode::{DecodeError, DeltaDecoder};
+
+#[cfg(feature = "std")]
+mod decode {
+    use crate::{EPContainer, EPDuration};
+    use std::collections::HashMap;
+    use std::string::String;
+    use std::vec::Vec;
+
+    /// Indicates that the input of [`DeltaDecoder::decode`] isn't a valid encoding.
+    #[derive(Debug, PartialEq, Eq)]
+    pub enum DecodeError {
+        /// The input ended in the middle of a record.
+        Truncated,
+        /// A delta refers to an id that was never defined.
+        UnknownId(u64),
+        /// A name isn't valid UTF-8.
+        InvalidName,
+        /// A duration doesn't fit into [`EPContainer`].
+        Overflow,
+    }
+
+    /// Decodes the output of [`DeltaEncoder`](super::DeltaEncoder) on the host.
+    ///
+    /// The decoder remembers the names and durations seen so far, so a capture can be
+    /// decoded in chunks as long as each chunk holds whole records.
+    #[derive(Default)]
+    pub struct DeltaDecoder {
+        names: HashMap<u64, (String, u64)>,
+    }
+
+    impl DeltaDecoder {
+        /// Decodes every record of `input` into the name and duration of its snapshot.
+        ///
+        /// # Errors
+        /// returns a [`DecodeError`] if `input` isn't a valid encoding
+        pub fn decode(
+            &mut self,
+            mut input: &[u8],
+        ) -> Result<Vec<(String, EPDuration)>, DecodeError> {
+            let mut snapshots = Vec::new();
+            while !input.is_empty() {
+                let tag = varint(&mut input)?;
+                let id = tag >> 1;
+                let duration = if tag & 1 == 1 {
+                    let len =
+                        usize::try_from(varint(&mut input)?).map_err(|_| DecodeError::Truncated)?;
+                    if input.len() < len {
+                        return Err(DecodeError::Truncated);
+                    }
+                    let (name, rest) = input.split_at(len);
+                    input = rest;
+                    let name =
+                        String::from_utf8(name.to_vec()).map_err(|_| DecodeError::InvalidName)?;
+                    let duration = varint(&mut input)?;
+                    self.names.insert(id, (name, duration));
+                    duration
+                } else {
+                    let delta = unzigzag(varint(&mut input)?);
+                    let (_, last) = self.names.get_mut(&id).ok_or(DecodeError::UnknownId(id))?;
+                    #[allow(clippy::cast_possible_wrap, clippy::cast_sign_loss)]
+                    let duration = (*last as i64).wrapping_add(delta) as u64;
+                    *last = duration;
+                    duration
+                };
+
+                let duration =
+                    EPContainer::try_from(duration).map_err(|_| DecodeError::Overflow)?;
+                snapshots.push((self.names[&id].0.clone(), EPDuration::from_ticks(duration)));
+            }
+            Ok(snapshots)
+        }
+    }
+
+    /// Reads a varint from the front of `input`.
+    fn varint(input: &mut &[u8]) -> Result<u64, DecodeError> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let (&byte, rest) = input.split_first().ok_or(DecodeError::Truncated)?;
+            *input = rest;
+            value |= u64::from(byte & 0x7F) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(DecodeError::Overflow)
+    }
+
+    /// The inverse of [`zigzag`](super::zigzag).
+    #[allow(clippy::cast_possible_wrap)]
+    const fn unzigzag(value: u64) -> i64 {
+        (value >> 1) as i64 ^ -((value & 1) as i64)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+    use crate::EPDuration;
+
+    fn encode_all<const N: usize>(
+        encoder: &mut DeltaEncoder<N>,
+        snapshots: &[(&'static str, crate::EPContainer)],
+    ) -> Vec<u8> {
+        let mut out = Vec::new();
+        for &(name, ticks) in snapshots {
+            let mut buffer = [0; 32];
+            let len = encoder
+                .encode(
+                    &EPSnapshot::new(name, EPDuration::from_ticks(ticks)),
+                    &mut buffer,
+                )
+                .unwrap();
+            out.extend_from_slice(&buffer[..len]);
+        }
+        out
+    }
+
+    #[test]
+    fn round_trip_small_deltas() {
+        let snapshots = [
+            ("poll", 120_000),
+            ("isr", 35),
+            ("poll", 120_004),
+            ("isr", 36),
+            ("poll", 119_990),
+            ("isr", 35),
+            ("poll", 120_001),
+        ];
+        let encoded = encode_all(&mut DeltaEncoder::<2>::new(), &snapshots);
+        // definitions of both names, then 2 bytes per delta
+        assert_eq!(encoded.len(), (1 + 1 + 4 + 3) + (1 + 1 + 3 + 1) + 5 * 2);
+
+        let decoded = DeltaDecoder::default().decode(&encoded).unwrap();
+        let expected: Vec<_> = snapshots
+            .iter()
+            .map(|&(name, ticks)| (name.to_string(), EPDuration::from_ticks(ticks)))
+            .collect();
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn untracked_names_are_redefined() {
+        let snapshots = [("a", 1), ("b", 2), ("c", 3), ("b", 4), ("c", 5)];
+        let encoded = encode_all(&mut DeltaEncoder::<1>::new(), &snapshots);
+
+        let decoded = DeltaDecoder::default().decode(&encoded).unwrap();
+        let names: Vec<_> = decoded.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["a", "b", "c", "b", "c"]);
+        assert_eq!(decoded[4].1.ticks(), 5);
+    }
+
+    #[test]
+    fn full_buffer_keeps_state() {
+        let mut encoder = DeltaEncoder::<1>::new();
+        let snapshot = EPSnapshot::new("poll", EPDuration::from_ticks(1_000));
+        assert_eq!(encoder.encode(&snapshot, &mut [0; 4]), None);
+
+        let mut buffer = [0; 16];
+        let len = encoder.encode(&snapshot, &mut buffer).unwrap();
+        assert_eq!(
+            DeltaDecoder::default().decode(&buffer[..len]).unwrap(),
+            [("poll".to_string(), EPDuration::from_ticks(1_000))]
+        );
+    }
+
+    #[test]
+    fn truncated_input() {
+        let encoded = encode_all(&mut DeltaEncoder::<1>::new(), &[("poll", 1_000)]);
+        assert_eq!(
+            DeltaDecoder::default().decode(&encoded[..encoded.len() - 1]),
+            Err(DecodeError::Truncated)
+        );
+        assert_eq!(
+            DeltaDecoder::default().decode(&[2, 0]),
+            Err(DecodeError::UnknownId(1))
+        );
+    }
+}
@@ -0,0 +1,197 @@
+//! Formatting snapshots in a chosen unit.
+
+use crate::{EPDuration, EPSnapshot};
+use core::fmt;
+
+/// The unit durations are formatted in by [`EPSnapshot::display_as`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Unit {
+    /// Microseconds, like `123 us`. This is what [`EPSnapshot`]'s [`Display`](fmt::Display) uses.
+    Micros,
+    /// Milliseconds, like `4500 ms` or `1.234 ms`.
+    Millis,
+    /// Seconds, like `4.5 s`.
+    Seconds,
+    /// The bare ticks of [`EPDuration`], like `4500000 ticks`.
+    Ticks,
+}
+
+impl Unit {
+    /// Writes `duration` in this unit, with as many decimals as needed but no trailing zeros.
+    fn write(self, f: &mut fmt::Formatter<'_>, duration: EPDuration) -> fmt::Result {
+        let micros = duration.ticks();
+        let (divisor, max_decimals, suffix) = match self {
+            #[cfg(not(feature = "minimal-fmt"))]
+            Unit::Micros => return write!(f, "{}", duration),
+            #[cfg(feature = "minimal-fmt")]
+            Unit::Micros => return write_micros(f, duration),
+            Unit::Ticks => return write!(f, "{} ticks", micros),
+            Unit::Millis => (1_000, 3, "ms"),
+            Unit::Seconds => (1_000_000, 6, "s"),
+        };
+
+        let whole = micros / divisor;
+        let mut fraction = micros % divisor;
+        if fraction == 0 {
+            return write!(f, "{} {}", whole, suffix);
+        }
+
+        let mut decimals = max_decimals;
+        while fraction % 10 == 0 {
+            fraction /= 10;
+            decimals -= 1;
+        }
+        write!(
+            f,
+            "{}.{:0width$} {}",
+            whole,
+            fraction,
+            suffix,
+            width = decimals
+        )
+    }
+}
+
+/// Writes `duration` as its integer microsecond count with a `us` suffix, like `123 us`.
+///
+/// This only needs integer formatting, unlike the [`Display`](fmt::Display) of
+/// [`EPDuration`] from `fugit`. With the `minimal-fmt` feature, [`EPSnapshot`]'s
+/// [`Display`](fmt::Display) uses this to save code size.
+///
+/// # Errors
+/// propagates any error of `writer`
+///
+/// ```
+/// # use embedded_profiling::*;
+/// let mut output = String::new();
+/// write_micros(&mut output, EPDuration::from_ticks(123)).unwrap();
+/// assert_eq!(output, "123 us");
+/// ```
+pub fn write_micros<W>(writer: &mut W, duration: EPDuration) -> fmt::Result
+where
+    W: fmt::Write + ?Sized,
+{
+    write!(writer, "{} us", duration.ticks())
+}
+
+/// Formats an [`EPSnapshot`] with its durations in a chosen [`Unit`], see [`EPSnapshot::display_as`].
+pub struct SnapshotDisplay<'a> {
+    snapshot: &'a EPSnapshot,
+    unit: Unit,
+}
+
+impl EPSnapshot {
+    /// Formats this snapshot like its [`Display`](fmt::Display), but with durations in `unit`.
+    ///
+    /// ```
+    /// # use embedded_profiling::*;
+    /// let snapshot = EPSnapshot::new("long", EPDuration::from_ticks(4_500_000));
+    /// assert_eq!(snapshot.display_as(Unit::Seconds).to_string(), "<EPSS long: 4.5 s>");
+    /// ```
+    #[must_use]
+    pub const fn display_as(&self, unit: Unit) -> SnapshotDisplay<'_> {
+        SnapshotDisplay {
+            snapshot: self,
+            unit,
+        }
+    }
+}
+
+impl fmt::Display for SnapshotDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let snapshot = self.snapshot;
+        write!(f, "<EPSS {}: ", snapshot.name)?;
+        self.unit.write(f, snapshot.duration)?;
+        if let Some(raw) = snapshot.raw {
+            write!(f, " ({} cyc)", raw)?;
+        }
+        if let Some(active) = snapshot.active {
+            write!(f, " (")?;
+            self.unit.write(f, active)?;
+            write!(f, " active)")?;
+        }
+        if let Some(self_time) = snapshot.self_time {
+            write!(f, " (")?;
+            self.unit.write(f, self_time)?;
+            write!(f, " self)")?;
+        }
+        if snapshot.panicked {
+            write!(f, " (panicked)")?;
+        }
+        if snapshot.suspect {
+            write!(f, " (suspect)")?;
+        }
+        write!(f, ">")
+    }
+}
+
+/// Formats as `<EPSS name: 123 us>`, or `<EPSS name: 123 us (14760 cyc)>` if the native tick
+/// count is known.
+impl fmt::Display for EPSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.display_as(Unit::Micros).fmt(f)
+    }
+}
+
+/// Formats like the [`Display`](fmt::Display) of [`EPSnapshot`].
+#[cfg(feature = "defmt")]
+impl defmt::Format for EPSnapshot {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "<EPSS {=str}: {} us", self.name, self.duration.ticks());
+        if let Some(raw) = self.raw {
+            defmt::write!(f, " ({} cyc)", raw);
+        }
+        if let Some(active) = self.active {
+            defmt::write!(f, " ({} us active)", active.ticks());
+        }
+        if let Some(self_time) = self.self_time {
+            defmt::write!(f, " ({} us self)", self_time.ticks());
+        }
+        if self.panicked {
+            defmt::write!(f, " (panicked)");
+        }
+        if self.suspect {
+            defmt::write!(f, " (suspect)");
+        }
+        defmt::write!(f, ">");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn display(micros: crate::EPContainer, unit: Unit) -> String {
+        EPSnapshot::new("unit", EPDuration::from_ticks(micros))
+            .display_as(unit)
+            .to_string()
+    }
+
+    #[test]
+    fn each_unit() {
+        assert_eq!(display(4_500_000, Unit::Micros), "<EPSS unit: 4500000 us>");
+        assert_eq!(display(4_500_000, Unit::Millis), "<EPSS unit: 4500 ms>");
+        assert_eq!(display(4_500_000, Unit::Seconds), "<EPSS unit: 4.5 s>");
+        assert_eq!(
+            display(4_500_000, Unit::Ticks),
+            "<EPSS unit: 4500000 ticks>"
+        );
+    }
+
+    #[test]
+    fn minimal_micros() {
+        let mut output = String::new();
+        write_micros(&mut output, EPDuration::from_ticks(4_500_000)).unwrap();
+        assert_eq!(output, "4500000 us");
+        // the same as the default formatting
+        assert_eq!(output, EPDuration::from_ticks(4_500_000).to_string());
+    }
+
+    #[test]
+    fn decimals() {
+        assert_eq!(display(1_234, Unit::Millis), "<EPSS unit: 1.234 ms>");
+        assert_eq!(display(1_050, Unit::Millis), "<EPSS unit: 1.05 ms>");
+        assert_eq!(display(123, Unit::Seconds), "<EPSS unit: 0.000123 s>");
+        assert_eq!(display(0, Unit::Seconds), "<EPSS unit: 0 s>");
+    }
+}
@@ -0,0 +1,139 @@
+//! Profiling futures until they complete.
+
+use crate::{log_snapshot, profiler, EPInstant};
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+/// Profiles the wrapped future `F` with the globally configured profiler, from when it's
+/// first polled until it completes. Returned by [`profile_async`](crate::profile_async).
+///
+/// The snapshot spans the wall-clock time across every `.await` of the future, including
+/// the time spent waiting for it to be woken. A future dropped before completing ends its
+/// snapshot without logging it, so the profiler's [`at_end`](crate::EmbeddedProfiler::at_end)
+/// hook still balances its [`at_start`](crate::EmbeddedProfiler::at_start).
+///
+/// Other tasks run while the future is pending, and futures complete in any order, so the
+/// snapshot is taken with [`profiler`] directly rather than the global
+/// [`start_snapshot`](crate::start_snapshot). It isn't counted in the
+/// [`nesting_depth`](crate::nesting_depth), and emits no begin and end events.
+#[must_use = "futures do nothing unless polled"]
+pub struct ProfiledFuture<F> {
+    name: &'static str,
+    start: Option<EPInstant>,
+    inner: F,
+}
+
+impl<F> ProfiledFuture<F> {
+    pub(crate) const fn new(name: &'static str, inner: F) -> Self {
+        Self {
+            name,
+            start: None,
+            inner,
+        }
+    }
+}
+
+impl<F> Future for ProfiledFuture<F>
+where
+    F: Future,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: `inner` is never moved out of `self`, so it stays pinned, and the other
+        // fields aren't structurally pinned
+        let this = unsafe { self.get_unchecked_mut() };
+        let name = this.name;
+        let start = *this
+            .start
+            .get_or_insert_with(|| profiler().start_snapshot(name));
+        let inner = unsafe { Pin::new_unchecked(&mut this.inner) };
+
+        let output = match inner.poll(cx) {
+            Poll::Ready(output) => output,
+            Poll::Pending => return Poll::Pending,
+        };
+        this.start = None;
+        if let Some(snapshot) = profiler().end_snapshot(start, name) {
+            log_snapshot(&snapshot);
+        }
+        Poll::Ready(output)
+    }
+}
+
+impl<F> Drop for ProfiledFuture<F> {
+    fn drop(&mut self) {
+        // only set between the first poll and completion
+        if let Some(start) = self.start {
+            let _ = profiler().end_snapshot(start, self.name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::{mock_profiler, noop_waker, set_profiler, YieldOnce};
+    use crate::{nesting_depth, profile_async, snapshots_emitted};
+    use core::sync::atomic::Ordering::SeqCst;
+
+    #[test]
+    #[serial_test::serial]
+    fn logged_on_completion() {
+        set_profiler();
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let before = snapshots_emitted();
+        let mut future = Box::pin(profile_async("profile_async", YieldOnce(false)));
+        assert_eq!(future.as_mut().poll(&mut cx), Poll::Pending);
+        assert_eq!(snapshots_emitted(), before);
+
+        assert_eq!(future.as_mut().poll(&mut cx), Poll::Ready(42));
+        assert_eq!(snapshots_emitted().wrapping_sub(before), 1);
+        assert!(mock_profiler().has_logged("profile_async"));
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn interleaved_futures() {
+        set_profiler();
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let depth = nesting_depth();
+        let mut first = Box::pin(profile_async("interleaved_first", YieldOnce(false)));
+        let mut second = Box::pin(profile_async("interleaved_second", YieldOnce(false)));
+        assert_eq!(first.as_mut().poll(&mut cx), Poll::Pending);
+        assert_eq!(second.as_mut().poll(&mut cx), Poll::Pending);
+        // other tasks don't see the pending futures as nesting
+        assert_eq!(nesting_depth(), depth);
+
+        // completed in the order they were started, not the reverse
+        assert_eq!(first.as_mut().poll(&mut cx), Poll::Ready(42));
+        assert_eq!(second.as_mut().poll(&mut cx), Poll::Ready(42));
+        assert_eq!(nesting_depth(), depth);
+        assert!(mock_profiler().has_logged("interleaved_first"));
+        assert!(mock_profiler().has_logged("interleaved_second"));
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn dropped_mid_await() {
+        set_profiler();
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let ended = mock_profiler().ended.load(SeqCst);
+        let before = snapshots_emitted();
+        let mut future = Box::pin(profile_async("profile_async_dropped", YieldOnce(false)));
+        assert_eq!(future.as_mut().poll(&mut cx), Poll::Pending);
+        drop(future);
+
+        // ended, but not logged
+        assert_eq!(mock_profiler().ended.load(SeqCst), ended + 1);
+        assert_eq!(snapshots_emitted(), before);
+        assert!(!mock_profiler().has_logged("profile_async_dropped"));
+    }
+}
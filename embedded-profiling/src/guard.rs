@@ -0,0 +1,210 @@
+//! RAII profiling of a scope.
+
+use crate::{begin_snapshot, end_snapshot, log_snapshot, EPDuration, EPInstant};
+use core::cell::Cell;
+
+/// Profiles from its creation until it is dropped, using the globally configured profiler.
+///
+/// The snapshot is ended and logged in [`Drop`], so every way out of the scope (early
+/// `return`, `?`, `break`, unwinding from a panic) is covered. With the `std` feature,
+/// snapshots ended by a panic are marked as [`panicked`](crate::EPSnapshot::panicked).
+///
+/// ```
+/// fn early_return(bail: bool) -> u32 {
+///     let _guard = embedded_profiling::ProfileGuard::new("early_return");
+///     if bail {
+///         return 0;
+///     }
+///     42
+/// }
+/// ```
+///
+/// Nested scopes can be profiled with [`child`](ProfileGuard::child) guards, so the
+/// parent's snapshot also reports its [`self_time`](crate::EPSnapshot::self_time).
+#[must_use = "the snapshot ends as soon as the guard is dropped"]
+pub struct ProfileGuard<'a> {
+    name: &'static str,
+    start: EPInstant,
+    /// The total duration of the ended children, `None` if there weren't any.
+    children: Cell<Option<EPDuration>>,
+    parent: Option<&'a Cell<Option<EPDuration>>>,
+}
+
+impl ProfileGuard<'static> {
+    /// Takes the starting snapshot of the trace `name`.
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            start: begin_snapshot(name),
+            children: Cell::new(None),
+            parent: None,
+        }
+    }
+}
+
+impl ProfileGuard<'_> {
+    /// Takes the starting snapshot of the trace `name`, nested in this one.
+    ///
+    /// When the child is dropped, its duration is added to this guard's children, and this
+    /// guard's snapshot reports the time spent outside of them as its
+    /// [`self_time`](crate::EPSnapshot::self_time). The child borrows its parent, so it
+    /// can't outlive it. The self time is zero if the children somehow took longer than
+    /// the parent.
+    ///
+    /// ```
+    /// let parent = embedded_profiling::ProfileGuard::new("frame");
+    /// {
+    ///     let _update = parent.child("update");
+    ///     // (...)
+    /// }
+    /// {
+    ///     let _render = parent.child("render");
+    ///     // (...)
+    /// }
+    /// // logs `frame` with the time spent outside of `update` and `render` as its self time
+    /// drop(parent);
+    /// ```
+    pub fn child(&self, name: &'static str) -> ProfileGuard<'_> {
+        ProfileGuard {
+            name,
+            start: begin_snapshot(name),
+            children: Cell::new(None),
+            parent: Some(&self.children),
+        }
+    }
+}
+
+impl Drop for ProfileGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(snapshot) = end_snapshot(self.start, self.name) {
+            #[cfg(feature = "std")]
+            let snapshot = snapshot.with_panicked(std::thread::panicking());
+            let snapshot = match self.children.get() {
+                Some(children) => snapshot.with_self_time(
+                    snapshot
+                        .duration
+                        .checked_sub(children)
+                        .unwrap_or_else(|| EPDuration::from_ticks(0)),
+                ),
+                None => snapshot,
+            };
+            if let Some(parent) = self.parent {
+                let total = match parent.get() {
+                    Some(total) => total.checked_add(snapshot.duration).unwrap_or(total),
+                    None => snapshot.duration,
+                };
+                parent.set(Some(total));
+            }
+            log_snapshot(&snapshot);
+        }
+    }
+}
+
+/// Calls its closure if dropped before being [`disarm`](CancelGuard::disarm)ed.
+///
+/// Used by `profile_function` to end the snapshot on every way out of a function's body, and
+/// on an `async fn` to end it, without logging it, when the future is dropped before
+/// completing.
+#[doc(hidden)]
+pub struct CancelGuard<F: FnOnce()> {
+    on_cancel: Option<F>,
+}
+
+impl<F: FnOnce()> CancelGuard<F> {
+    #[doc(hidden)]
+    pub fn new(on_cancel: F) -> Self {
+        Self {
+            on_cancel: Some(on_cancel),
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn disarm(mut self) {
+        self.on_cancel = None;
+    }
+}
+
+impl<F: FnOnce()> Drop for CancelGuard<F> {
+    fn drop(&mut self) {
+        if let Some(on_cancel) = self.on_cancel.take() {
+            on_cancel();
+        }
+    }
+}
+
+/// Whether the current thread is unwinding from a panic, always `false` without `std`.
+///
+/// Used by `profile_function` to skip its budget check rather than panic again, and abort,
+/// while unwinding.
+#[doc(hidden)]
+pub fn unwinding() -> bool {
+    #[cfg(feature = "std")]
+    return std::thread::panicking();
+    #[cfg(not(feature = "std"))]
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::{mock_profiler, set_profiler};
+
+    #[test]
+    #[serial_test::serial]
+    fn logs_on_drop() {
+        set_profiler();
+
+        {
+            let _guard = ProfileGuard::new("guard_logs_on_drop");
+            assert!(!mock_profiler().has_logged("guard_logs_on_drop"));
+        }
+        assert!(mock_profiler().has_logged("guard_logs_on_drop"));
+        assert!(!mock_profiler().has_logged_panicked("guard_logs_on_drop"));
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn logs_on_panic() {
+        set_profiler();
+
+        let result = std::panic::catch_unwind(|| {
+            crate::profile_guarded("guard_logs_on_panic", || panic!("profiled panic"))
+        });
+        assert!(result.is_err());
+        assert!(mock_profiler().has_logged("guard_logs_on_panic"));
+        #[cfg(feature = "std")]
+        assert!(mock_profiler().has_logged_panicked("guard_logs_on_panic"));
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn self_time_excludes_children() {
+        set_profiler();
+        let sleep = |ms| std::thread::sleep(std::time::Duration::from_millis(ms));
+
+        let parent = ProfileGuard::new("guard_parent");
+        sleep(2);
+        {
+            let _child = parent.child("guard_first_child");
+            sleep(3);
+        }
+        {
+            let _child = parent.child("guard_second_child");
+            sleep(3);
+        }
+        drop(parent);
+
+        let logged = |name| mock_profiler().last_logged(name).unwrap();
+        let (parent, first, second) = (
+            logged("guard_parent"),
+            logged("guard_first_child"),
+            logged("guard_second_child"),
+        );
+        assert_eq!(first.self_time, None);
+        assert_eq!(
+            parent.self_time,
+            Some(parent.duration - first.duration - second.duration)
+        );
+        assert!(parent.self_time.unwrap().ticks() >= 2_000);
+    }
+}
@@ -0,0 +1,104 @@
+//! Statically dispatched access to the global profiler's clock.
+
+use crate::{EPInstant, EmbeddedProfiler};
+
+/// A typed handle to a profiler, reading its clock without going through the
+/// `&dyn EmbeddedProfiler` returned by [`profiler`](crate::profiler).
+///
+/// Every call through the global profiler is a virtual call, which adds up in hot loops
+/// sampling the clock. The handle knows the concrete profiler type `P`, so
+/// [`read_clock`](ClockHandle::read_clock) is a direct call that can be inlined, while still
+/// using the same profiler (and peripheral) as the global functions.
+///
+/// This requires knowing the concrete type of the profiler, so it is obtained from the
+/// `&'static P` that was passed to [`set_profiler`](crate::set_profiler).
+///
+/// ```
+/// # use embedded_profiling::*;
+/// # struct MyProfiler;
+/// # impl EmbeddedProfiler for MyProfiler { fn read_clock(&self) -> EPInstant { EPInstant::from_ticks(0) } }
+/// static MY_PROFILER: MyProfiler = MyProfiler;
+/// unsafe {
+///     set_profiler(&MY_PROFILER).unwrap();
+/// }
+///
+/// let clock = ClockHandle::global(&MY_PROFILER).unwrap();
+/// for _ in 0..1_000 {
+///     let _sample = clock.read_clock();
+/// }
+/// ```
+pub struct ClockHandle<P: 'static> {
+    profiler: &'static P,
+}
+
+impl<P> ClockHandle<P>
+where
+    P: EmbeddedProfiler,
+{
+    /// Creates a new [`ClockHandle`] to `profiler`, whether it's the global one or not.
+    #[must_use]
+    pub const fn new(profiler: &'static P) -> Self {
+        Self { profiler }
+    }
+
+    /// Creates a new [`ClockHandle`] to `profiler` if it is the profiler installed with
+    /// [`set_profiler`](crate::set_profiler), `None` otherwise.
+    #[must_use]
+    pub fn global(profiler: &'static P) -> Option<Self> {
+        let installed = crate::profiler() as *const dyn EmbeddedProfiler as *const ();
+        let same = core::ptr::eq(profiler as *const P as *const (), installed);
+        (same && crate::profiler_kind() == crate::ProfilerKind::User).then(|| Self::new(profiler))
+    }
+
+    /// Reads the clock of the profiler, see [`EmbeddedProfiler::read_clock`].
+    #[inline]
+    #[must_use]
+    pub fn read_clock(&self) -> EPInstant {
+        self.profiler.read_clock()
+    }
+
+    /// The profiler this handle reads.
+    #[must_use]
+    pub const fn profiler(&self) -> &'static P {
+        self.profiler
+    }
+}
+
+impl<P> Clone for ClockHandle<P> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<P> Copy for ClockHandle<P> {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::{mock_profiler, set_profiler};
+    use core::sync::atomic::{AtomicU32, Ordering};
+
+    struct OtherProfiler(AtomicU32);
+
+    impl EmbeddedProfiler for OtherProfiler {
+        // only a conversion with `container-u64`
+        #[allow(clippy::useless_conversion)]
+        fn read_clock(&self) -> EPInstant {
+            EPInstant::from_ticks(self.0.load(Ordering::Relaxed).into())
+        }
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn only_the_installed_profiler_is_global() {
+        set_profiler();
+        assert!(ClockHandle::global(mock_profiler()).is_some());
+
+        static OTHER: OtherProfiler = OtherProfiler(AtomicU32::new(42));
+        assert!(ClockHandle::global(&OTHER).is_none());
+        assert_eq!(
+            ClockHandle::new(&OTHER).read_clock(),
+            EPInstant::from_ticks(42)
+        );
+    }
+}
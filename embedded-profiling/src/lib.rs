@@ -46,23 +46,339 @@
 //! ### `container-u64`
 //!
 //! Use a [`u64`] as the time storage type instead of [`u32`] for longer running profiling.
+//! That's enough for any capture: even raw cycles of a 250 MHz clock only wrap after more
+//! than 2000 years.
+//!
+//! ### `compile-filter`
+//!
+//! enables `proc-macros` and compiles out every
+//! [`profile_function`](self::profile_function) site marked `compile_filter`, so small
+//! spans cost nothing in builds that don't need them.
+//!
+//! ### `disable`
+//!
+//! enables `proc-macros` and compiles out every
+//! [`profile_function`](self::profile_function) and [`profile_impl`](self::profile_impl)
+//! site, leaving the functions exactly as written for zero overhead in production images.
+//! Profiling through the functions of this crate is unaffected.
+//!
+//! ### `std`
+//!
+//! Lets [`ProfileGuard`] detect that it was dropped while unwinding from a panic, see
+//! [`profile_guarded`].
+//!
+//! Also enables the [`perfetto`] module for exporting snapshots as a Perfetto trace,
+//! [`ThreadSafe`] for sharing a profiler between threads, [`SteppingProfiler`], a
+//! deterministic clock for examples and host tests, and the host side
+//! [`DeltaDecoder`](delta::DeltaDecoder) of [`delta`] encoded snapshots.
+//!
+//! ### `minimal-fmt`
+//!
+//! formats the durations of [`EPSnapshot`]'s [`Display`](core::fmt::Display) with
+//! [`write_micros`], which only needs integer formatting, instead of `fugit`'s formatting.
+//! The output is the same, but it saves code size on flash-starved parts.
 //!
 //! ### `proc-macros`
 //!
 //! enables the `proc-macros` feature in [`embedded-profiling`](self). Enables
-//! the [`embedded_profiling::profile_function`](self::profile_function) procedural macro.
+//! the [`embedded_profiling::profile_function`](self::profile_function) procedural macro, and
+//! [`embedded_profiling::profile_impl`](self::profile_impl) for every method of an `impl` block.
+//!
+//! ### `cortex-m`
+//!
+//! Enables [`set_profiler_checked`], which checks that interrupts are disabled using the
+//! Cortex-M `PRIMASK` register.
+//!
+//! ### `critical-section`
+//!
+//! Enables [`set_profiler_cs`], a safe way to set the global profiler inside a
+//! [`critical-section`](https://docs.rs/critical-section) critical section,
+//! [`RingProfiler`], which buffers snapshots in RAM from interrupts,
+//! [`StatsProfiler`], which accumulates statistics from interrupts, and
+//! [`OverlapCheckProfiler`], which checks spans from interrupts end in order.
+//!
+//! ### `defmt`
+//!
+//! Implements [`defmt::Format`] for [`EPSnapshot`], and logs snapshots with
+//! [`defmt_log_snapshot`] in the default [`EmbeddedProfiler::log_snapshot`], for projects
+//! that use [`defmt`](https://defmt.ferrous-systems.com) instead of `log`.
+//!
+//! ### `serde`
+//!
+//! Implements `Serialize` for [`EPSnapshot`], and `Deserialize` too with the `std` feature.
+//! See [`serde_ticks`] for the details.
+//!
+//! ### `rtic`
+//!
+//! Enables the [`rtic`] module for profiling [RTIC](https://rtic.rs) tasks.
 //!
 #![warn(missing_docs)]
-#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicU32, AtomicU8, AtomicUsize, Ordering};
 
-use core::sync::atomic::{AtomicU8, Ordering};
+/// Implements the listed [`EmbeddedProfiler`] methods by forwarding them to the wrapped
+/// profiler, either a field (`self.inner => ...`, `self.0 => ...`), a method returning it
+/// (`self.lock() => ...`) or the target of a reference (`**self => ...`), so wrappers only
+/// write out the methods they change.
+///
+/// Besides single methods, `clock` forwards reading the clock and its properties, `hooks`
+/// the `at_*` and `log_begin`/`log_end` hooks, `snapshots` reading and ending snapshots,
+/// `provided` the remaining provided methods (like `start_snapshot` and `self_test`), and
+/// `all` every overridable method, for wrappers that only rename or guard what they wrap.
+///
+/// Exported for the wrappers of the other `ep-*` crates, not part of the public API.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! forward_profiler {
+    (self.$getter:ident() => $($method:ident),+ $(,)?) => {
+        $($crate::forward_profiler!(@method [] [.$getter()] $method);)+
+    };
+    (self.$field:tt => $($method:ident),+ $(,)?) => {
+        $($crate::forward_profiler!(@method [] [.$field] $method);)+
+    };
+    (**self => $($method:ident),+ $(,)?) => {
+        $($crate::forward_profiler!(@method [* *] [] $method);)+
+    };
+    (@method [$($pre:tt)*] [$($post:tt)*] all) => {
+        $crate::forward_profiler!(@method [$($pre)*] [$($post)*] clock);
+        $crate::forward_profiler!(@method [$($pre)*] [$($post)*] log_snapshot);
+        $crate::forward_profiler!(@method [$($pre)*] [$($post)*] hooks);
+        $crate::forward_profiler!(@method [$($pre)*] [$($post)*] snapshots);
+        $crate::forward_profiler!(@method [$($pre)*] [$($post)*] provided);
+    };
+    (@method [$($pre:tt)*] [$($post:tt)*] clock) => {
+        $crate::forward_profiler!(@method [$($pre)*] [$($post)*] read_clock);
+        $crate::forward_profiler!(@method [$($pre)*] [$($post)*] clock_is_running);
+        $crate::forward_profiler!(@method [$($pre)*] [$($post)*] overflow_events);
+        $crate::forward_profiler!(@method [$($pre)*] [$($post)*] name);
+        $crate::forward_profiler!(@method [$($pre)*] [$($post)*] backward_jitter);
+        $crate::forward_profiler!(@method [$($pre)*] [$($post)*] synchronize);
+        $crate::forward_profiler!(@method [$($pre)*] [$($post)*] has_real_clock);
+        $crate::forward_profiler!(@method [$($pre)*] [$($post)*] read_clock_raw);
+        $crate::forward_profiler!(@method [$($pre)*] [$($post)*] duration_from_raw);
+        $crate::forward_profiler!(@method [$($pre)*] [$($post)*] read_clock_ns);
+    };
+    (@method [$($pre:tt)*] [$($post:tt)*] hooks) => {
+        $crate::forward_profiler!(@method [$($pre)*] [$($post)*] at_start);
+        $crate::forward_profiler!(@method [$($pre)*] [$($post)*] at_end);
+        $crate::forward_profiler!(@method [$($pre)*] [$($post)*] log_begin);
+        $crate::forward_profiler!(@method [$($pre)*] [$($post)*] log_end);
+    };
+    (@method [$($pre:tt)*] [$($post:tt)*] snapshots) => {
+        $crate::forward_profiler!(@method [$($pre)*] [$($post)*] read_snapshot);
+        $crate::forward_profiler!(@method [$($pre)*] [$($post)*] end_snapshot_silent);
+        $crate::forward_profiler!(@method [$($pre)*] [$($post)*] end_snapshot_clamped);
+    };
+    (@method [$($pre:tt)*] [$($post:tt)*] provided) => {
+        $crate::forward_profiler!(@method [$($pre)*] [$($post)*] self_test);
+        $crate::forward_profiler!(@method [$($pre)*] [$($post)*] start_snapshot);
+        $crate::forward_profiler!(@method [$($pre)*] [$($post)*] end_snapshot);
+        $crate::forward_profiler!(@method [$($pre)*] [$($post)*] start_snapshot_raw);
+        $crate::forward_profiler!(@method [$($pre)*] [$($post)*] end_snapshot_raw);
+    };
+    (@method [$($pre:tt)*] [$($post:tt)*] read_clock) => {
+        fn read_clock(&self) -> $crate::EPInstant {
+            ($($pre)* self $($post)*).read_clock()
+        }
+    };
+    (@method [$($pre:tt)*] [$($post:tt)*] log_snapshot) => {
+        fn log_snapshot(&self, snapshot: &$crate::EPSnapshot) {
+            ($($pre)* self $($post)*).log_snapshot(snapshot);
+        }
+    };
+    (@method [$($pre:tt)*] [$($post:tt)*] log_begin) => {
+        fn log_begin(&self, name: &'static str, at: $crate::EPInstant) {
+            ($($pre)* self $($post)*).log_begin(name, at);
+        }
+    };
+    (@method [$($pre:tt)*] [$($post:tt)*] log_end) => {
+        fn log_end(&self, name: &'static str, at: $crate::EPInstant) {
+            ($($pre)* self $($post)*).log_end(name, at);
+        }
+    };
+    (@method [$($pre:tt)*] [$($post:tt)*] at_start) => {
+        fn at_start(&self, name: &'static str) {
+            ($($pre)* self $($post)*).at_start(name);
+        }
+    };
+    (@method [$($pre:tt)*] [$($post:tt)*] at_end) => {
+        fn at_end(&self, name: &'static str) {
+            ($($pre)* self $($post)*).at_end(name);
+        }
+    };
+    (@method [$($pre:tt)*] [$($post:tt)*] clock_is_running) => {
+        fn clock_is_running(&self) -> bool {
+            ($($pre)* self $($post)*).clock_is_running()
+        }
+    };
+    (@method [$($pre:tt)*] [$($post:tt)*] overflow_events) => {
+        fn overflow_events(&self) -> Option<u32> {
+            ($($pre)* self $($post)*).overflow_events()
+        }
+    };
+    (@method [$($pre:tt)*] [$($post:tt)*] name) => {
+        fn name(&self) -> &'static str {
+            ($($pre)* self $($post)*).name()
+        }
+    };
+    (@method [$($pre:tt)*] [$($post:tt)*] backward_jitter) => {
+        fn backward_jitter(&self) -> $crate::EPDuration {
+            ($($pre)* self $($post)*).backward_jitter()
+        }
+    };
+    (@method [$($pre:tt)*] [$($post:tt)*] synchronize) => {
+        fn synchronize(&self, rtc_now: $crate::EPInstant) {
+            ($($pre)* self $($post)*).synchronize(rtc_now);
+        }
+    };
+    (@method [$($pre:tt)*] [$($post:tt)*] has_real_clock) => {
+        fn has_real_clock(&self) -> bool {
+            ($($pre)* self $($post)*).has_real_clock()
+        }
+    };
+    (@method [$($pre:tt)*] [$($post:tt)*] read_clock_raw) => {
+        fn read_clock_raw(&self) -> $crate::EPContainer {
+            ($($pre)* self $($post)*).read_clock_raw()
+        }
+    };
+    (@method [$($pre:tt)*] [$($post:tt)*] duration_from_raw) => {
+        fn duration_from_raw(&self, raw: $crate::EPContainer) -> $crate::EPDuration {
+            ($($pre)* self $($post)*).duration_from_raw(raw)
+        }
+    };
+    (@method [$($pre:tt)*] [$($post:tt)*] read_clock_ns) => {
+        fn read_clock_ns(&self) -> $crate::EPInstantNs {
+            ($($pre)* self $($post)*).read_clock_ns()
+        }
+    };
+    (@method [$($pre:tt)*] [$($post:tt)*] read_snapshot) => {
+        fn read_snapshot(&self) -> $crate::EPInstant {
+            ($($pre)* self $($post)*).read_snapshot()
+        }
+    };
+    (@method [$($pre:tt)*] [$($post:tt)*] end_snapshot_silent) => {
+        fn end_snapshot_silent(
+            &self,
+            start: $crate::EPInstant,
+            name: &'static str,
+        ) -> Option<$crate::EPSnapshot> {
+            ($($pre)* self $($post)*).end_snapshot_silent(start, name)
+        }
+    };
+    (@method [$($pre:tt)*] [$($post:tt)*] end_snapshot_clamped) => {
+        fn end_snapshot_clamped(
+            &self,
+            start: $crate::EPInstant,
+            name: &'static str,
+        ) -> Option<$crate::EPSnapshot> {
+            ($($pre)* self $($post)*).end_snapshot_clamped(start, name)
+        }
+    };
+    (@method [$($pre:tt)*] [$($post:tt)*] self_test) => {
+        fn self_test(&self) -> $crate::SelfTestReport {
+            ($($pre)* self $($post)*).self_test()
+        }
+    };
+    (@method [$($pre:tt)*] [$($post:tt)*] start_snapshot) => {
+        fn start_snapshot(&self, name: &'static str) -> $crate::EPInstant {
+            ($($pre)* self $($post)*).start_snapshot(name)
+        }
+    };
+    (@method [$($pre:tt)*] [$($post:tt)*] end_snapshot) => {
+        fn end_snapshot(
+            &self,
+            start: $crate::EPInstant,
+            name: &'static str,
+        ) -> Option<$crate::EPSnapshot> {
+            ($($pre)* self $($post)*).end_snapshot(start, name)
+        }
+    };
+    (@method [$($pre:tt)*] [$($post:tt)*] start_snapshot_raw) => {
+        fn start_snapshot_raw(&self, name: &'static str) -> $crate::EPContainer {
+            ($($pre)* self $($post)*).start_snapshot_raw(name)
+        }
+    };
+    (@method [$($pre:tt)*] [$($post:tt)*] end_snapshot_raw) => {
+        fn end_snapshot_raw(
+            &self,
+            start: $crate::EPContainer,
+            name: &'static str,
+        ) -> Option<$crate::EPSnapshot> {
+            ($($pre)* self $($post)*).end_snapshot_raw(start, name)
+        }
+    };
+}
 
+mod aggregate;
+mod builder;
+mod coalesce;
+pub mod delta;
+mod display;
+mod future;
+mod guard;
+mod handle;
+mod marker;
 #[cfg(test)]
 mod mock;
+mod monotonic;
+#[cfg(feature = "critical-section")]
+mod overlap;
+#[cfg(feature = "std")]
+pub mod perfetto;
+pub mod prelude;
+mod ratelimit;
+#[cfg(feature = "critical-section")]
+mod ring;
+#[cfg(feature = "rtic")]
+pub mod rtic;
+mod scaled;
+#[cfg(feature = "serde")]
+pub mod serde_ticks;
+mod sleep;
+mod stats;
+#[cfg(feature = "critical-section")]
+mod stats_profiler;
+#[cfg(feature = "std")]
+mod stepping;
+mod synced;
+mod tee;
+#[cfg(feature = "std")]
+mod thread_safe;
+mod tuple;
+mod writer;
 #[cfg(feature = "proc-macros")]
-pub use embedded_profiling_proc_macros::profile_function;
+pub use embedded_profiling_proc_macros::{profile_function, profile_impl};
 
+pub use aggregate::AggregatingProfiler;
+pub use builder::{ClockSink, ComposedProfiler, ProfilerBuilder, Sink};
+pub use coalesce::CoalescingProfiler;
+pub use display::{write_micros, SnapshotDisplay, Unit};
 pub use fugit;
+pub use future::ProfiledFuture;
+pub use guard::ProfileGuard;
+#[doc(hidden)]
+pub use guard::{unwinding, CancelGuard};
+pub use handle::ClockHandle;
+pub use marker::{mark_begin, mark_end, MARKER_SLOTS};
+pub use monotonic::MonotonicProfiler;
+#[cfg(feature = "critical-section")]
+pub use overlap::OverlapCheckProfiler;
+pub use ratelimit::RateLimitedSink;
+#[cfg(feature = "critical-section")]
+pub use ring::RingProfiler;
+pub use scaled::ScaledProfiler;
+pub use sleep::SleepAwareProfiler;
+pub use stats::{Stats, StatsCsv};
+#[cfg(feature = "critical-section")]
+pub use stats_profiler::StatsProfiler;
+#[cfg(feature = "std")]
+pub use stepping::SteppingProfiler;
+pub use synced::RtcSyncedProfiler;
+pub use tee::TeeProfiler;
+#[cfg(feature = "std")]
+pub use thread_safe::ThreadSafe;
+pub use writer::{BorrowWriter, WriterProfiler};
 
 // do the feature gating on a private type so our public documentation is only in one place
 #[cfg(not(feature = "container-u64"))]
@@ -72,8 +388,18 @@ type PrivContainer = u64;
 
 /// The underlying container of our [`Duration`](fugit::Duration)/[`Instant`](fugit::Instant) types.
 /// Can be either `u32` or `u64`, depending on features (default: `u32`).
+///
+/// There is no `u16` or `u128` option, as `fugit` only implements its types for `u32` and
+/// `u64`. For very short spans on the smallest parts, instants can be kept in a `u16` with
+/// [`truncate_instant_u16`] and [`wrapping_duration_u16`], wrapping after about 65 ms.
 pub type EPContainer = PrivContainer;
 
+// wide enough to hold any `EPContainer` times a `u64` factor, for `convert_instant_dyn`
+#[cfg(not(feature = "container-u64"))]
+type WideContainer = u64;
+#[cfg(feature = "container-u64")]
+type WideContainer = u128;
+
 /// Our [`Duration`](fugit::Duration) type, representing time elapsed in microseconds.
 pub type EPDuration = fugit::MicrosDuration<EPContainer>;
 
@@ -81,25 +407,232 @@ pub type EPDuration = fugit::MicrosDuration<EPContainer>;
 /// a clock with 1 µs precision (or at least, converted to this representation).
 pub type EPInstant = fugit::Instant<EPContainer, 1, 1_000_000>;
 
+/// A [`Duration`](fugit::Duration) type representing time elapsed in nanoseconds, for clocks
+/// finer than a microsecond (see [`EmbeddedProfiler::read_clock_ns`]).
+///
+/// With a `u32` container, nanoseconds wrap after about 4.3 seconds, so enable
+/// `container-u64` for anything longer.
+pub type EPDurationNs = fugit::NanosDuration<EPContainer>;
+
+/// An [`Instant`](fugit::Instant) type from a clock with 1 ns precision, see [`EPDurationNs`].
+pub type EPInstantNs = fugit::Instant<EPContainer, 1, 1_000_000_000>;
+
 /// An [`Instant`](fugit::Instant) type but with a generic fraction. This needs to be
 /// converted into [`EPInstant`] for use in the [`EmbeddedProfiler::read_clock`] function.
 pub type EPInstantGeneric<const NOM: u32, const DENOM: u32> =
     fugit::Instant<EPContainer, NOM, DENOM>;
 
 /// A recorded snapshot.
+#[derive(Clone, Copy, Debug)]
 pub struct EPSnapshot {
     /// The name of this trace.
     pub name: &'static str,
     /// The duration of this trace.
     pub duration: EPDuration,
+    /// The duration of this trace in the native ticks of the clock (e.g. CPU cycles), for
+    /// clock sources that have one. `None` otherwise.
+    pub raw: Option<EPContainer>,
+    /// Where this trace comes from, like the `module_path!()` of a function annotated with
+    /// [`profile_function`](embedded_profiling_proc_macros::profile_function). Backends built on
+    /// `log` use this as the target of their records when it's set.
+    pub target: Option<&'static str>,
+    /// Whether this trace was ended by unwinding from a panic. Only detected with the
+    /// `std` feature, see [`profile_guarded`].
+    pub panicked: bool,
+    /// The source file and line of this trace, set by
+    /// [`profile_function`](embedded_profiling_proc_macros::profile_function) to the location
+    /// of the annotated function. `None` otherwise.
+    pub location: Option<(&'static str, u32)>,
+    /// The category of this trace, like `"io"`, so traces can be grouped separately from
+    /// their name. Set by [`profile_categorized`], `None` otherwise.
+    pub category: Option<&'static str>,
+    /// The time spent awake during this trace, for clock sources that can tell it apart
+    /// from sleep (see [`SleepAwareProfiler`]). `None` otherwise.
+    pub active: Option<EPDuration>,
+    /// When this trace started. Set by the default
+    /// [`end_snapshot_silent`](EmbeddedProfiler::end_snapshot_silent), `None` if unknown.
+    pub start: Option<EPInstant>,
+    /// Whether this trace took implausibly long, hinting at a measurement problem like a
+    /// wrong clock frequency or a stalled clock. Set by an [`AggregatingProfiler`] with an
+    /// [`implausible threshold`](AggregatingProfiler::with_implausible_threshold).
+    pub suspect: bool,
+    /// The time spent in this trace outside of its children, for a [`ProfileGuard`] that
+    /// had [child guards](ProfileGuard::child). `None` otherwise.
+    pub self_time: Option<EPDuration>,
+}
+
+impl EPSnapshot {
+    /// Creates a new snapshot without a native tick count.
+    #[must_use]
+    pub const fn new(name: &'static str, duration: EPDuration) -> Self {
+        Self {
+            name,
+            duration,
+            raw: None,
+            target: None,
+            panicked: false,
+            location: None,
+            category: None,
+            active: None,
+            start: None,
+            suspect: false,
+            self_time: None,
+        }
+    }
+
+    /// Attaches the native tick count `raw` of the clock source to this snapshot.
+    #[must_use]
+    pub const fn with_raw(mut self, raw: EPContainer) -> Self {
+        self.raw = Some(raw);
+        self
+    }
+
+    /// Sets where this trace comes from to `target`, typically a module path.
+    #[must_use]
+    pub const fn with_target(mut self, target: &'static str) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    /// Sets the source `file` and `line` this trace comes from.
+    #[must_use]
+    pub const fn with_location(mut self, file: &'static str, line: u32) -> Self {
+        self.location = Some((file, line));
+        self
+    }
+
+    /// Sets the category of this trace to `category`.
+    #[must_use]
+    pub const fn with_category(mut self, category: &'static str) -> Self {
+        self.category = Some(category);
+        self
+    }
+
+    /// Sets when this trace started to `start`.
+    #[must_use]
+    pub const fn with_start(mut self, start: EPInstant) -> Self {
+        self.start = Some(start);
+        self
+    }
+
+    /// Sets the time spent awake during this trace to `active`.
+    #[must_use]
+    pub const fn with_active(mut self, active: EPDuration) -> Self {
+        self.active = Some(active);
+        self
+    }
+
+    /// Marks whether this trace was ended by unwinding from a panic.
+    #[must_use]
+    pub const fn with_panicked(mut self, panicked: bool) -> Self {
+        self.panicked = panicked;
+        self
+    }
+
+    /// Sets the time spent in this trace outside of its children to `self_time`.
+    #[must_use]
+    pub const fn with_self_time(mut self, self_time: EPDuration) -> Self {
+        self.self_time = Some(self_time);
+        self
+    }
+
+    /// Marks whether this trace took implausibly long.
+    #[must_use]
+    pub const fn with_suspect(mut self, suspect: bool) -> Self {
+        self.suspect = suspect;
+        self
+    }
+}
+
+/// The measurement floor of a profiler, as measured by [`EmbeddedProfiler::self_test`].
+#[derive(Clone, Copy, Debug)]
+pub struct SelfTestReport {
+    /// The smallest non-zero duration the clock resolves, zero if it never moved.
+    pub resolution: EPDuration,
+    /// The shortest measured duration of profiling an empty closure.
+    pub empty_overhead: EPDuration,
+}
+
+/// Logs `snapshot` with [`defmt::info!`], formatted like its [`Display`](core::fmt::Display).
+///
+/// This is what [`EmbeddedProfiler::log_snapshot`] does by default with the `defmt` feature,
+/// for backends to use in their own implementation.
+#[cfg(feature = "defmt")]
+pub fn defmt_log_snapshot(snapshot: &EPSnapshot) {
+    defmt::info!("{}", snapshot);
+}
+
+/// Writes `snapshot` to `writer` on its own line, the way snapshots are logged by backends
+/// that output to a [`Write`](core::fmt::Write) sink (like a serial port) instead of `log`.
+///
+/// # Errors
+/// propagates any error of `writer`
+///
+/// ```
+/// # use embedded_profiling::*;
+/// let mut output = String::new();
+/// let snapshot = EPSnapshot::new("written", EPDuration::from_ticks(12));
+/// write_snapshot(&mut output, &snapshot).unwrap();
+/// assert_eq!(output, "<EPSS written: 12 us>\n");
+/// ```
+pub fn write_snapshot<W>(writer: &mut W, snapshot: &EPSnapshot) -> core::fmt::Result
+where
+    W: core::fmt::Write + ?Sized,
+{
+    writeln!(writer, "{}", snapshot)
 }
 
-impl core::fmt::Display for EPSnapshot {
-    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        write!(f, "<EPSS {}: {}>", self.name, self.duration)
+/// Formats `snapshot` like its [`Display`](core::fmt::Display) into `buf`, returning how
+/// many bytes were written.
+///
+/// For backends staging output in a fixed buffer (a USB packet, a flash page). Neither
+/// allocates nor panics: if the formatted snapshot doesn't fit, [`core::fmt::Error`] is
+/// returned and the contents of `buf` are unspecified.
+///
+/// # Errors
+/// returns [`core::fmt::Error`] if the formatted snapshot is longer than `buf`
+///
+/// ```
+/// # use embedded_profiling::*;
+/// let mut buf = [0; 32];
+/// let snapshot = EPSnapshot::new("packet", EPDuration::from_ticks(12));
+/// let len = format_snapshot(&snapshot, &mut buf).unwrap();
+/// assert_eq!(&buf[..len], b"<EPSS packet: 12 us>");
+/// ```
+pub fn format_snapshot(snapshot: &EPSnapshot, buf: &mut [u8]) -> Result<usize, core::fmt::Error> {
+    /// Writes into a byte slice, failing once it is full.
+    struct SliceWriter<'a> {
+        buf: &'a mut [u8],
+        len: usize,
+    }
+
+    impl core::fmt::Write for SliceWriter<'_> {
+        fn write_str(&mut self, s: &str) -> core::fmt::Result {
+            let end = self.len + s.len();
+            self.buf
+                .get_mut(self.len..end)
+                .ok_or(core::fmt::Error)?
+                .copy_from_slice(s.as_bytes());
+            self.len = end;
+            Ok(())
+        }
     }
+
+    let mut writer = SliceWriter { buf, len: 0 };
+    core::fmt::write(&mut writer, format_args!("{}", snapshot))?;
+    Ok(writer.len)
 }
 
+/// How many times [`EmbeddedProfiler::clock_is_running`] reads the clock by default before
+/// deciding that it's stalled. Plenty to see a 1µs tick even on fast cores.
+const CLOCK_RUNNING_READS: u32 = 10_000;
+
+/// The default [`EmbeddedProfiler::backward_jitter`], a single tick.
+pub const DEFAULT_BACKWARD_JITTER: EPDuration = EPDuration::from_ticks(1);
+
+/// How many empty snapshots [`EmbeddedProfiler::self_test`] takes, keeping the shortest.
+const SELF_TEST_RUNS: u32 = 8;
+
 /// The main trait to implement. All that is required is a way to read time and a way
 /// to output our results, if desired. You can also implement functions that
 /// get called when a snapshot starts and ends.
@@ -112,16 +645,151 @@ pub trait EmbeddedProfiler {
     fn read_clock(&self) -> EPInstant;
 
     /// Optionally log the snapshot to some output, like a serial port.
-    fn log_snapshot(&self, _snapshot: &EPSnapshot) {}
+    ///
+    /// Does nothing by default, or logs with [`defmt_log_snapshot`] with the `defmt` feature.
+    fn log_snapshot(&self, _snapshot: &EPSnapshot) {
+        #[cfg(feature = "defmt")]
+        defmt_log_snapshot(_snapshot);
+    }
 
-    /// Optional function that gets called at the start of the snapshot recording.
+    /// Optional function that gets called at the start of the snapshot recording of the
+    /// trace `name`.
     ///
     /// If one would want to very simple profiling, they could use `at_start` and `at_end`
-    /// to simply toggle a GPIO.
-    fn at_start(&self) {}
+    /// to simply toggle a GPIO. The name is empty if it isn't known yet, like when
+    /// starting through the global [`start_snapshot`].
+    fn at_start(&self, _name: &'static str) {}
+
+    /// Optional function that gets called at the end of the snapshot recording of the
+    /// trace `name`.
+    fn at_end(&self, _name: &'static str) {}
+
+    /// Whether the clock is running. A stalled clock (e.g. a counter that was disabled or
+    /// gated by a sleep mode) makes every duration come out as zero.
+    ///
+    /// By default, reads the clock a bounded number of times and checks if it moved. Backends
+    /// that can check their counter's enable bit override this to do that instead.
+    #[must_use]
+    fn clock_is_running(&self) -> bool {
+        let first = self.read_clock();
+        (0..CLOCK_RUNNING_READS).any(|_| self.read_clock() != first)
+    }
+
+    /// Logs that the trace `name` began at `at`, for consumers that expect separate begin and
+    /// end events rather than complete spans. Only called with [`emit_begin_end`] enabled,
+    /// for traces started with the global [`begin_snapshot`]. Does nothing by default.
+    fn log_begin(&self, name: &'static str, at: EPInstant) {
+        let _ = (name, at);
+    }
+
+    /// Logs that the trace `name` ended at `at`, the counterpart of
+    /// [`log_begin`](EmbeddedProfiler::log_begin). Only called by the global [`end_snapshot`]
+    /// for traces that emitted a begin event. Does nothing by default.
+    fn log_end(&self, name: &'static str, at: EPInstant) {
+        let _ = (name, at);
+    }
+
+    /// Records the offset between this profiler's clock and a real time clock reading
+    /// `rtc_now` at this moment, so snapshot starts can be reported in the RTC's timebase.
+    /// Does nothing by default, see [`RtcSyncedProfiler`].
+    fn synchronize(&self, rtc_now: EPInstant) {
+        let _ = rtc_now;
+    }
+
+    /// Whether [`duration`](EPSnapshot::duration)s measured by this profiler are real time.
+    ///
+    /// Marker-only profilers (like a pin toggle, whose output is measured externally) report
+    /// `false`, so exporters can leave out their durations instead of showing them as zero.
+    fn has_real_clock(&self) -> bool {
+        true
+    }
+
+    /// Reads the clock in its native ticks, like CPU cycles, without converting them to
+    /// microseconds, for durations shorter than a microsecond. Convert them with
+    /// [`duration_from_raw`](EmbeddedProfiler::duration_from_raw).
+    ///
+    /// By default, the microseconds of [`read_clock`](EmbeddedProfiler::read_clock). Backends
+    /// with a finer clock, like the DWT cycle counter, override this with their counter.
+    #[must_use]
+    fn read_clock_raw(&self) -> EPContainer {
+        self.read_clock().ticks()
+    }
+
+    /// Converts `raw` native ticks, as read by [`read_clock_raw`](EmbeddedProfiler::read_clock_raw),
+    /// to a duration. Backends overriding one must override the other.
+    #[must_use]
+    fn duration_from_raw(&self, raw: EPContainer) -> EPDuration {
+        EPDuration::from_ticks(raw)
+    }
+
+    /// Reads the clock with nanosecond precision, for clocks finer than a microsecond. Wraps
+    /// if the nanoseconds overflow [`EPContainer`], see [`convert_instant_ns`].
+    ///
+    /// By default, the microseconds of [`read_clock`](EmbeddedProfiler::read_clock) in
+    /// nanoseconds. Backends with a finer clock, like the DWT cycle counter, override this to
+    /// skip the lossy conversion to microseconds.
+    #[must_use]
+    fn read_clock_ns(&self) -> EPInstantNs {
+        convert_instant_ns(self.read_clock())
+    }
+
+    /// How many times the underlying counter has overflowed, for profilers that extend
+    /// their counter by tracking that (like the `extended` DWT and SysTick profilers).
+    /// `None` otherwise.
+    ///
+    /// A count climbing faster than expected, e.g. during a supposedly short operation,
+    /// means the measured code blocked far longer than expected.
+    #[must_use]
+    fn overflow_events(&self) -> Option<u32> {
+        None
+    }
+
+    /// A short name of this kind of profiler, like `"dwt"`, to tell which profiler is in use
+    /// when several are configured or they're swapped at runtime. Wrappers report the name
+    /// of the profiler they wrap.
+    #[must_use]
+    fn name(&self) -> &'static str {
+        "unknown"
+    }
+
+    /// Measures the resolution of the clock and the overhead of profiling an empty closure,
+    /// to give context to the durations this profiler reports (e.g. by printing it at boot).
+    ///
+    /// The resolution is the step between two consecutive changes of the clock, read back
+    /// to back. The overhead is the shortest of a few empty snapshots, which call
+    /// [`EmbeddedProfiler::at_start`] and [`EmbeddedProfiler::at_end`] like any other.
+    #[must_use]
+    fn self_test(&self) -> SelfTestReport {
+        let mut resolution = EPDuration::from_ticks(0);
+        let mut last = self.read_clock();
+        let mut ticked = false;
+        for _ in 0..CLOCK_RUNNING_READS {
+            let now = self.read_clock();
+            if now != last {
+                // the first change only lines us up with the start of a tick
+                if ticked {
+                    resolution = now.checked_duration_since(last).unwrap_or(resolution);
+                    break;
+                }
+                ticked = true;
+                last = now;
+            }
+        }
+
+        let empty_overhead = (0..SELF_TEST_RUNS)
+            .filter_map(|_| {
+                let start = self.start_snapshot("self_test");
+                self.end_snapshot(start, "self_test")
+            })
+            .map(|snapshot| snapshot.duration)
+            .min()
+            .unwrap_or_else(|| EPDuration::from_ticks(0));
 
-    /// Optional function that gets called at the end of the snapshot recording.
-    fn at_end(&self) {}
+        SelfTestReport {
+            resolution,
+            empty_overhead,
+        }
+    }
 
     /// takes the starting snapshot of a specific trace.
     ///
@@ -131,25 +799,221 @@ pub trait EmbeddedProfiler {
     /// # impl EmbeddedProfiler for MyProfiler { fn read_clock(&self) -> EPInstant {EPInstant::from_ticks(0)} }
     /// # let my_profiler = MyProfiler;
     /// # fn function_to_profile() {}
-    /// let start_time = my_profiler.start_snapshot();
+    /// let start_time = my_profiler.start_snapshot("function_to_profile");
     /// function_to_profile();
     /// if let Some(snapshot) = my_profiler.end_snapshot(start_time, "function_to_profile") {
     ///     my_profiler.log_snapshot(&snapshot);
     /// }
     /// ```
     #[allow(clippy::must_use_candidate)]
-    fn start_snapshot(&self) -> EPInstant {
-        self.at_start();
-        self.read_clock()
+    fn start_snapshot(&self, name: &'static str) -> EPInstant {
+        self.at_start(name);
+        self.read_snapshot()
     }
 
     /// computes the duration of the snapshot given the start time, if there hasn't been overflow.
     #[allow(clippy::must_use_candidate)]
     fn end_snapshot(&self, start: EPInstant, name: &'static str) -> Option<EPSnapshot> {
-        self.at_end();
+        self.at_end(name);
+        self.end_snapshot_silent(start, name)
+    }
+
+    /// Like [`EmbeddedProfiler::end_snapshot`], but tells why no snapshot could be made.
+    ///
+    /// # Errors
+    /// returns `Err(SnapshotError::Overflow)` if the clock overflowed since `start`
+    ///
+    /// ```
+    /// # use embedded_profiling::*;
+    /// # struct MyProfiler;
+    /// # impl EmbeddedProfiler for MyProfiler { fn read_clock(&self) -> EPInstant {EPInstant::from_ticks(0)} }
+    /// # let my_profiler = MyProfiler;
+    /// let start = EPInstant::from_ticks(100);
+    /// match my_profiler.end_snapshot_checked(start, "wrapped") {
+    ///     Ok(snapshot) => my_profiler.log_snapshot(&snapshot),
+    ///     Err(SnapshotError::Overflow) => println!("`wrapped` overflowed, dropped"),
+    /// }
+    /// ```
+    fn end_snapshot_checked(
+        &self,
+        start: EPInstant,
+        name: &'static str,
+    ) -> Result<EPSnapshot, SnapshotError> {
+        self.end_snapshot(start, name)
+            .ok_or(SnapshotError::Overflow)
+    }
+
+    /// takes the starting snapshot of a specific trace without calling [`EmbeddedProfiler::at_start`].
+    ///
+    /// Use this together with [`EmbeddedProfiler::end_snapshot_silent`] for internal
+    /// measurements that shouldn't fire any markers, like a pin toggle. Use
+    /// [`EmbeddedProfiler::start_snapshot`] for everything else.
+    ///
+    /// [`EmbeddedProfiler::start_snapshot`] calls this after
+    /// [`EmbeddedProfiler::at_start`], so profilers tracking the start of their spans override
+    /// this, which wrappers forward, instead of `start_snapshot`.
+    ///
+    /// ```
+    /// # use embedded_profiling::*;
+    /// # struct MyProfiler;
+    /// # impl EmbeddedProfiler for MyProfiler { fn read_clock(&self) -> EPInstant {EPInstant::from_ticks(0)} }
+    /// # let my_profiler = MyProfiler;
+    /// let start_time = my_profiler.read_snapshot();
+    /// // (...) internal work
+    /// let snapshot = my_profiler.end_snapshot_silent(start_time, "internal");
+    /// ```
+    #[must_use]
+    fn read_snapshot(&self) -> EPInstant {
+        self.read_clock()
+    }
+
+    /// computes the duration of the snapshot given the start time, if there hasn't been overflow,
+    /// without calling [`EmbeddedProfiler::at_end`].
+    #[allow(clippy::must_use_candidate)]
+    fn end_snapshot_silent(&self, start: EPInstant, name: &'static str) -> Option<EPSnapshot> {
         let now = self.read_clock();
         now.checked_duration_since(start)
-            .map(|duration| EPSnapshot { name, duration })
+            .map(|duration| EPSnapshot::new(name, duration).with_start(start))
+    }
+
+    /// How far the clock may jump backwards between two reads and still be considered
+    /// benign jitter by [`EmbeddedProfiler::end_snapshot_clamped`]. Defaults to
+    /// [`DEFAULT_BACKWARD_JITTER`], a single tick.
+    #[must_use]
+    fn backward_jitter(&self) -> EPDuration {
+        DEFAULT_BACKWARD_JITTER
+    }
+
+    /// takes the starting snapshot of a specific trace in the clock's native ticks, to be
+    /// ended by [`EmbeddedProfiler::end_snapshot_raw`].
+    ///
+    /// ```
+    /// # use embedded_profiling::*;
+    /// # struct MyProfiler;
+    /// # impl EmbeddedProfiler for MyProfiler { fn read_clock(&self) -> EPInstant {EPInstant::from_ticks(0)} }
+    /// # let my_profiler = MyProfiler;
+    /// let start = my_profiler.start_snapshot_raw("critical_section");
+    /// // (...) a few cycles of work
+    /// if let Some(snapshot) = my_profiler.end_snapshot_raw(start, "critical_section") {
+    ///     // the duration in native ticks is in `snapshot.raw`
+    ///     my_profiler.log_snapshot(&snapshot);
+    /// }
+    /// ```
+    #[allow(clippy::must_use_candidate)]
+    fn start_snapshot_raw(&self, name: &'static str) -> EPContainer {
+        self.at_start(name);
+        self.read_clock_raw()
+    }
+
+    /// computes the snapshot given the start time in native ticks, if there hasn't been overflow.
+    ///
+    /// The duration is computed in native ticks, kept as the snapshot's [`raw`](EPSnapshot::raw)
+    /// duration at full resolution, and only converted for its [`duration`](EPSnapshot::duration).
+    #[allow(clippy::must_use_candidate)]
+    fn end_snapshot_raw(&self, start: EPContainer, name: &'static str) -> Option<EPSnapshot> {
+        self.at_end(name);
+        let raw = self.read_clock_raw().checked_sub(start)?;
+        Some(EPSnapshot::new(name, self.duration_from_raw(raw)).with_raw(raw))
+    }
+
+    /// Like [`EmbeddedProfiler::end_snapshot`], but reports a zero duration snapshot instead
+    /// of `None` if the clock went backwards by at most
+    /// [`backward_jitter`](EmbeddedProfiler::backward_jitter) since `start`.
+    ///
+    /// Larger backward jumps are treated as overflow and still return `None`. The snapshot is
+    /// ended with [`end_snapshot_silent`](EmbeddedProfiler::end_snapshot_silent), so backends
+    /// overriding it keep their behaviour, and the clock is only read again to measure the
+    /// jump when that fails.
+    #[allow(clippy::must_use_candidate)]
+    fn end_snapshot_clamped(&self, start: EPInstant, name: &'static str) -> Option<EPSnapshot> {
+        self.at_end(name);
+        self.end_snapshot_silent(start, name).or_else(|| {
+            match start.checked_duration_since(self.read_clock()) {
+                Some(jump) if jump <= self.backward_jitter() => {
+                    Some(EPSnapshot::new(name, EPDuration::from_ticks(0)).with_start(start))
+                }
+                _ => None,
+            }
+        })
+    }
+
+    /// Profiles the given closure `target` with name `name` using this profiler, without
+    /// going through the global profiler.
+    ///
+    /// This allows profiling without any global state: [`set_profiler`] never has to be
+    /// called, and the snapshot callback isn't invoked.
+    ///
+    /// ```
+    /// # use embedded_profiling::{EPInstant, EmbeddedProfiler};
+    /// # struct MyProfiler;
+    /// # impl EmbeddedProfiler for MyProfiler { fn read_clock(&self) -> EPInstant { EPInstant::from_ticks(0) } }
+    /// let my_profiler = MyProfiler;
+    /// let sum = my_profiler.profile("sum", || (0..10).sum::<u32>());
+    /// assert_eq!(sum, 45);
+    /// ```
+    fn profile<T, R>(&self, name: &'static str, target: T) -> R
+    where
+        Self: Sized,
+        T: FnOnce() -> R,
+    {
+        let start = self.start_snapshot(name);
+        let ret = target();
+        if let Some(snapshot) = self.end_snapshot(start, name) {
+            self.log_snapshot(&snapshot);
+        }
+        ret
+    }
+}
+
+/// Forwards every method to the referenced profiler, so `&my_profiler` can be used
+/// wherever an [`EmbeddedProfiler`] is expected.
+impl<T> EmbeddedProfiler for &T
+where
+    T: EmbeddedProfiler + ?Sized,
+{
+    forward_profiler!(**self => all);
+}
+
+/// Makes profiling optional: `Some` forwards every method to the profiler, and `None`
+/// behaves like no profiler was configured, never logging anything and reading a clock
+/// stuck at zero.
+///
+/// ```
+/// # use embedded_profiling::*;
+/// fn driver_step(profiler: &Option<impl EmbeddedProfiler>) {
+///     let start = profiler.start_snapshot("driver_step");
+///     // (...)
+///     if let Some(snapshot) = profiler.end_snapshot(start, "driver_step") {
+///         profiler.log_snapshot(&snapshot);
+///     }
+/// }
+/// # struct MyProfiler;
+/// # impl EmbeddedProfiler for MyProfiler { fn read_clock(&self) -> EPInstant { EPInstant::from_ticks(0) } }
+/// driver_step(&Some(MyProfiler));
+/// driver_step(&None::<MyProfiler>);
+/// ```
+impl<P> EmbeddedProfiler for Option<P>
+where
+    P: EmbeddedProfiler,
+{
+    forward_profiler!(self.or_noop() => all);
+}
+
+/// The profiler an `Option<P>` forwards to.
+trait OrNoop {
+    /// The contained profiler, or [`NoopProfiler`] if there isn't one.
+    fn or_noop(&self) -> &dyn EmbeddedProfiler;
+}
+
+impl<P> OrNoop for Option<P>
+where
+    P: EmbeddedProfiler,
+{
+    fn or_noop(&self) -> &dyn EmbeddedProfiler {
+        match self {
+            Some(profiler) => profiler,
+            None => &NoopProfiler,
+        }
     }
 }
 
@@ -172,27 +1036,388 @@ pub const fn convert_instant<const NOM: u32, const DENOM: u32>(
     EPInstant::from_ticks(us.ticks())
 }
 
-struct NoopProfiler;
+/// Converts an instant of an unknown fraction `NOM`/`DENOM` to nanoseconds.
+///
+/// This function is useful when implementing [`EmbeddedProfiler::read_clock_ns`]. Unlike
+/// [`convert_instant`], the result is truncated if it overflows [`EPContainer`], as
+/// nanoseconds quickly outgrow a `u32`. Durations between two truncated instants are still
+/// right as long as they're shorter than the wrap, but when the counter being converted
+/// wraps around itself, the nanoseconds jump by whatever its wrap doesn't cover of the
+/// container's. E.g. a `u32` cycle counter at 120 MHz wraps after 35.8 s of nanoseconds,
+/// which isn't a multiple of the 4.29 s nanosecond wrap, so durations spanning the cycle
+/// counter's wrap are wrong.
+/// ```
+/// # use embedded_profiling::*;
+/// let my_clock_instant = EPInstantGeneric::<1, 120_000_000>::from_ticks(3);
+/// let converted_instant = convert_instant_ns::<1, 120_000_000>(my_clock_instant);
+/// assert_eq!(25, converted_instant.ticks());
+/// ```
+#[inline]
+#[must_use]
+pub const fn convert_instant_ns<const NOM: u32, const DENOM: u32>(
+    now: EPInstantGeneric<NOM, DENOM>,
+) -> EPInstantNs {
+    let (nom, denom) = NanosPerTick::<NOM, DENOM>::FRACTION;
+    // `From` isn't const, and this is only a conversion without `container-u64`
+    #[allow(clippy::unnecessary_cast)]
+    let ticks = now.ticks() as u64;
+    let ns = if core::mem::size_of::<EPContainer>() == 4 && nom <= u32::MAX as u64 {
+        // a `u32` count times a `u32` can't overflow, sparing a 128 bit division on every read
+        ticks * nom / denom
+    } else {
+        // can't overflow, the result is truncated below anyways
+        #[allow(clippy::cast_possible_truncation)]
+        let ns = (ticks as u128 * nom as u128 / denom as u128) as u64;
+        ns
+    };
+    #[allow(clippy::cast_possible_truncation)]
+    EPInstantNs::from_ticks(ns as EPContainer)
+}
 
-impl EmbeddedProfiler for NoopProfiler {
-    fn read_clock(&self) -> EPInstant {
-        EPInstant::from_ticks(0)
-    }
+/// Nanoseconds per tick of a clock with the fraction `NOM`/`DENOM`.
+struct NanosPerTick<const NOM: u32, const DENOM: u32>;
 
-    fn log_snapshot(&self, _snapshot: &EPSnapshot) {}
+impl<const NOM: u32, const DENOM: u32> NanosPerTick<NOM, DENOM> {
+    /// The reduced fraction `NOM * 1_000_000_000 / DENOM`.
+    const FRACTION: (u64, u64) = {
+        let nom = NOM as u64 * 1_000_000_000;
+        let divisor = gcd(nom, DENOM as u64);
+        (nom / divisor, DENOM as u64 / divisor)
+    };
 }
 
-static mut PROFILER: &dyn EmbeddedProfiler = &NoopProfiler;
+/// Converts `ticks` of a clock with a runtime fraction `nom`/`denom` to our microsecond
+/// representation.
+///
+/// This is [`convert_instant`] for clocks whose frequency isn't known at compile time, like
+/// the core clock of a system scaling its frequency. It's slightly slower than
+/// [`convert_instant`], as the fraction can't be reduced at compile time and a non-const
+/// division is needed for every conversion.
+/// ```
+/// # use embedded_profiling::*;
+/// let converted_instant = convert_instant_dyn(100, 1, 1_000);
+/// assert_eq!(100_000, converted_instant.ticks());
+/// ```
+///
+/// # Panics
+/// if `denom` is zero or the result overflows [`EPContainer`], like [`convert_instant`]
+#[must_use]
+pub fn convert_instant_dyn(ticks: EPContainer, nom: u32, denom: u32) -> EPInstant {
+    assert!(denom != 0, "Convert failed!");
+
+    let nom = u64::from(nom) * 1_000_000;
+    let divisor = gcd(nom, u64::from(denom));
+    let (nom, denom) = (nom / divisor, u64::from(denom) / divisor);
+
+    let us = WideContainer::from(ticks)
+        .checked_mul(WideContainer::from(nom))
+        .expect("Convert failed!")
+        / WideContainer::from(denom);
+    EPInstant::from_ticks(EPContainer::try_from(us).expect("Convert failed!"))
+}
+
+/// Truncates `instant` to 16 bits of microseconds, for the smallest parts that keep
+/// instants in a `u16`, see [`wrapping_duration_u16`].
+/// ```
+/// # use embedded_profiling::*;
+/// assert_eq!(truncate_instant_u16(EPInstant::from_ticks(65_536 + 25)), 25);
+/// ```
+#[must_use]
+pub const fn truncate_instant_u16(instant: EPInstant) -> u16 {
+    #[allow(clippy::cast_possible_truncation)]
+    let micros = instant.ticks() as u16;
+    micros
+}
+
+/// The duration from `start` to `end`, two instants truncated to 16 bits of microseconds
+/// by [`truncate_instant_u16`].
+///
+/// The instants wrap every 65.536 ms, so this is only right for spans shorter than that.
+/// Longer spans wrap too, and come out as their remainder of 65.536 ms.
+/// ```
+/// # use embedded_profiling::*;
+/// // `end` wrapped past `u16::MAX`
+/// assert_eq!(wrapping_duration_u16(65_500, 100).ticks(), 136);
+/// ```
+#[must_use]
+pub const fn wrapping_duration_u16(start: u16, end: u16) -> EPDuration {
+    // `From` isn't const
+    EPDuration::from_ticks(end.wrapping_sub(start) as EPContainer)
+}
+
+/// The greatest common divisor of `a` and `b`, for reducing the fraction of a clock at
+/// compile time when implementing conversions like [`convert_instant`] by hand.
+/// ```
+/// # use embedded_profiling::*;
+/// let divisor = gcd(120_000_000, 1_000_000);
+/// assert_eq!((120_000_000 / divisor, 1_000_000 / divisor), (120, 1));
+/// ```
+#[must_use]
+pub const fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        let rem = a % b;
+        a = b;
+        b = rem;
+    }
+    a
+}
+
+/// Converts `duration` to a [`Duration`](fugit::Duration) of any other base `NOM`/`DENOM`,
+/// returning `None` if the result would overflow [`EPContainer`].
+///
+/// ```
+/// # use embedded_profiling::*;
+/// let tenths = try_rebase::<1, 10>(EPDuration::from_ticks(250_000)).unwrap();
+/// assert_eq!(2, tenths.ticks());
+/// ```
+#[inline]
+#[must_use]
+pub const fn try_rebase<const NOM: u32, const DENOM: u32>(
+    duration: EPDuration,
+) -> Option<fugit::Duration<EPContainer, NOM, DENOM>> {
+    duration.const_try_into()
+}
+
+/// Converts `duration` to a [`Duration`](fugit::Duration) of any other base `NOM`/`DENOM`.
+///
+/// # Panics
+/// if the result would overflow [`EPContainer`]. See [`try_rebase`] for a non-panicking version.
+#[inline]
+#[must_use]
+pub const fn rebase<const NOM: u32, const DENOM: u32>(
+    duration: EPDuration,
+) -> fugit::Duration<EPContainer, NOM, DENOM> {
+    duration.convert()
+}
+
+/// Converts `duration` to milliseconds, truncating any remaining microseconds.
+///
+/// ```
+/// # use embedded_profiling::*;
+/// let millis = to_millis_duration(EPDuration::from_ticks(4_500));
+/// assert_eq!(4, millis.ticks());
+/// ```
+#[inline]
+#[must_use]
+pub const fn to_millis_duration(duration: EPDuration) -> fugit::MillisDuration<EPContainer> {
+    rebase(duration)
+}
+
+/// Converts `duration` to nanoseconds.
+///
+/// # Panics
+/// if the result would overflow [`EPContainer`], i.e. above ~4.29 s with the default `u32`
+/// container.
+#[inline]
+#[must_use]
+pub const fn to_nanos_duration(duration: EPDuration) -> fugit::NanosDuration<EPContainer> {
+    rebase(duration)
+}
+
+/// The microseconds since the clock's epoch of `instant`, e.g. to hand it to C code or a
+/// binary protocol. The ticks of an [`EPInstant`] already are microseconds, so this is
+/// the same as [`ticks`](fugit::Instant::ticks).
+///
+/// ```
+/// # use embedded_profiling::*;
+/// let instant = instant_from_micros(1_500);
+/// assert_eq!(1_500, instant_to_micros(instant));
+/// ```
+#[inline]
+#[must_use]
+pub const fn instant_to_micros(instant: EPInstant) -> EPContainer {
+    instant.ticks()
+}
+
+/// The [`EPInstant`] `micros` microseconds after the clock's epoch, the inverse of
+/// [`instant_to_micros`].
+#[inline]
+#[must_use]
+pub const fn instant_from_micros(micros: EPContainer) -> EPInstant {
+    EPInstant::from_ticks(micros)
+}
+
+/// The length of `duration` in microseconds. The ticks of an [`EPDuration`] already are
+/// microseconds, so this is the same as [`ticks`](fugit::Duration::ticks).
+///
+/// ```
+/// # use embedded_profiling::*;
+/// let duration = duration_from_micros(250);
+/// assert_eq!(250, duration_to_micros(duration));
+/// ```
+#[inline]
+#[must_use]
+pub const fn duration_to_micros(duration: EPDuration) -> EPContainer {
+    duration.ticks()
+}
+
+/// The [`EPDuration`] of `micros` microseconds, the inverse of [`duration_to_micros`].
+#[inline]
+#[must_use]
+pub const fn duration_from_micros(micros: EPContainer) -> EPDuration {
+    EPDuration::from_ticks(micros)
+}
+
+/// Converts the [`core::time::Duration`] `duration` to an [`EPDuration`], e.g. to pass a
+/// threshold written as `Duration::from_millis(5)` in host code.
+///
+/// Any remaining nanoseconds are truncated, and durations that don't fit the container
+/// saturate at its maximum. `EPDuration` is a type of `fugit`, so this can't be a
+/// [`From`] implementation.
+///
+/// ```
+/// # use embedded_profiling::*;
+/// use core::time::Duration;
+///
+/// assert_eq!(duration_from_core(Duration::from_millis(5)).ticks(), 5_000);
+/// assert_eq!(duration_to_core(EPDuration::from_ticks(1_500)), Duration::from_micros(1_500));
+/// ```
+#[must_use]
+pub fn duration_from_core(duration: core::time::Duration) -> EPDuration {
+    EPDuration::from_ticks(EPContainer::try_from(duration.as_micros()).unwrap_or(EPContainer::MAX))
+}
+
+/// Converts `duration` to a [`core::time::Duration`], the inverse of [`duration_from_core`].
+#[inline]
+#[must_use]
+pub fn duration_to_core(duration: EPDuration) -> core::time::Duration {
+    // only a conversion with `container-u64`
+    #[allow(clippy::useless_conversion)]
+    core::time::Duration::from_micros(duration.ticks().into())
+}
+
+/// The proportion of `numer` to `denom`, like `0.35` if `numer` is 35% of `denom`, or `0.0`
+/// if `denom` is zero.
+///
+/// ```
+/// # use embedded_profiling::*;
+/// let frame = EPDuration::from_ticks(16_000);
+/// assert_eq!(ratio(EPDuration::from_ticks(4_000), frame), 0.25);
+/// ```
+#[must_use]
+pub fn ratio(numer: EPDuration, denom: EPDuration) -> f32 {
+    if denom.ticks() == 0 {
+        return 0.0;
+    }
+    // only the precision of very long durations is lost, nothing can overflow
+    #[allow(clippy::cast_precision_loss)]
+    let ratio = numer.ticks() as f32 / denom.ticks() as f32;
+    ratio
+}
+
+/// The proportion of `numer` to `denom` in thousandths, rounded down, like `350` if `numer` is
+/// 35% of `denom`. For targets without floating point, see [`ratio`].
+///
+/// The multiplication is widened so it can't overflow, and the result saturates at
+/// [`EPContainer::MAX`] if `numer` is vastly longer than `denom`. Returns `None` if `denom`
+/// is zero.
+///
+/// ```
+/// # use embedded_profiling::*;
+/// let frame = EPDuration::from_ticks(16_000);
+/// assert_eq!(permille(EPDuration::from_ticks(5_600), frame), Some(350));
+/// ```
+#[must_use]
+pub fn permille(numer: EPDuration, denom: EPDuration) -> Option<EPContainer> {
+    if denom.ticks() == 0 {
+        return None;
+    }
+    let permille = WideContainer::from(numer.ticks()) * 1_000 / WideContainer::from(denom.ticks());
+    Some(EPContainer::try_from(permille).unwrap_or(EPContainer::MAX))
+}
+
+struct NoopProfiler;
+
+impl EmbeddedProfiler for NoopProfiler {
+    fn read_clock(&self) -> EPInstant {
+        EPInstant::from_ticks(0)
+    }
+
+    fn log_snapshot(&self, _snapshot: &EPSnapshot) {}
+
+    fn name(&self) -> &'static str {
+        "noop"
+    }
+
+    fn has_real_clock(&self) -> bool {
+        false
+    }
+}
+
+static mut PROFILER: &dyn EmbeddedProfiler = &NoopProfiler;
 
 const UNINITIALIZED: u8 = 0;
 const INITIALIZED: u8 = 2;
 
 static STATE: AtomicU8 = AtomicU8::new(UNINITIALIZED);
 
-/// Indicates that setting the profiler has gone awry, probably because the
-/// profiler has already been set.
+/// How many snapshots taken with the global [`start_snapshot`] haven't ended yet.
+///
+/// Only loads and stores are used, as read-modify-write atomics aren't available on every
+/// target (e.g. cortex-M0). That is still correct when interrupts preempt us between the
+/// load and the store, as long as they end every snapshot they start before returning.
+static DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+/// The deepest [`DEPTH`] reached, see [`max_observed_depth`].
+static MAX_DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether [`begin_snapshot`] and [`end_snapshot`] emit begin and end events.
+static EMIT_BEGIN_END: AtomicBool = AtomicBool::new(false);
+
+/// The nesting depths, one bit each, whose open trace emitted a begin event, so
+/// [`end_snapshot`] only emits the end events matching them. Only loads and stores are
+/// used, like [`DEPTH`].
+static BEGUN: AtomicU32 = AtomicU32::new(0);
+
+/// The bit of `depth` in [`BEGUN`], `0` for depths too deep to be tracked.
+fn begun_bit(depth: usize) -> u32 {
+    u32::try_from(depth)
+        .ok()
+        .and_then(|depth| 1_u32.checked_shl(depth))
+        .unwrap_or(0)
+}
+
+/// Whether [`start_snapshot`] asserts that a profiler was set, see [`require_profiler`].
+static REQUIRE_PROFILER: AtomicBool = AtomicBool::new(false);
+
+/// Whether [`end_snapshot`] excludes the time nested snapshots spent being logged.
+static EXCLUDE_NESTED_LOGGING: AtomicBool = AtomicBool::new(false);
+
+/// How many levels of nesting [`exclude_nested_logging`] keeps track of.
+const MAX_TRACKED_DEPTH: usize = 8;
+
+/// The total time spent in [`log_snapshot`] while excluding nested logging, in wrapping µs.
+static LOGGING_TIME: AtomicU32 = AtomicU32::new(0);
+
+/// [`LOGGING_TIME`] when the snapshot at each nesting depth was started.
+#[allow(clippy::declare_interior_mutable_const)]
+static LOGGING_TIME_AT_START: [AtomicU32; MAX_TRACKED_DEPTH] = {
+    const ZERO: AtomicU32 = AtomicU32::new(0);
+    [ZERO; MAX_TRACKED_DEPTH]
+};
+
+/// The `fn(&EPSnapshot)` set with [`set_snapshot_callback`], null if there is none.
+static SNAPSHOT_CALLBACK: AtomicPtr<()> = AtomicPtr::new(core::ptr::null_mut());
+
+/// How many snapshots [`log_snapshot`] has handed to the global profiler, wrapping.
+static SNAPSHOTS_EMITTED: AtomicU32 = AtomicU32::new(0);
+
+/// Indicates that setting the profiler has gone awry.
 #[derive(Debug)]
-pub struct SetProfilerError;
+pub enum SetProfilerError {
+    /// A global profiler has already been configured.
+    AlreadySet,
+    /// Interrupts were enabled, see [`set_profiler_checked`].
+    InterruptsEnabled,
+}
+
+/// Indicates why a snapshot couldn't be ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SnapshotError {
+    /// The clock read before the start of the snapshot, as the counter overflowed (or the
+    /// clock jumped backwards) in between. The duration can't be known.
+    Overflow,
+}
 
 /// Sets the global profiler.
 ///
@@ -201,7 +1426,7 @@ pub struct SetProfilerError;
 /// or, in an embedded single core environment, with interrupts disabled.
 ///
 /// # Errors
-/// returns `Err(SetProfilerError)` when a global profiler has already been configured
+/// returns `Err(SetProfilerError::AlreadySet)` when a global profiler has already been configured
 ///
 /// ```
 /// # struct MyProfiler;
@@ -223,11 +1448,130 @@ pub unsafe fn set_profiler(
             STATE.store(INITIALIZED, Ordering::Release);
             Ok(())
         }
-        INITIALIZED => Err(SetProfilerError),
+        INITIALIZED => Err(SetProfilerError::AlreadySet),
         _ => unreachable!(),
     }
 }
 
+/// Sets the global profiler to `profiler`, whether or not one has already been configured.
+///
+/// Returns the previously configured profiler, or `None` if it was the no-op profiler.
+///
+/// # Safety
+/// Must be completed with no other threads running
+/// or, in an embedded single core environment, with interrupts disabled. No snapshot of
+/// the previous profiler may be ended by the new one.
+///
+/// ```
+/// # struct MyProfiler;
+/// # impl embedded_profiling::EmbeddedProfiler for MyProfiler { fn read_clock(&self) -> embedded_profiling::EPInstant { embedded_profiling::EPInstant::from_ticks(0) } }
+/// # static FIRST: MyProfiler = MyProfiler;
+/// # static SECOND: MyProfiler = MyProfiler;
+/// unsafe {
+///     assert!(embedded_profiling::replace_profiler(&FIRST).is_none());
+///     assert!(embedded_profiling::replace_profiler(&SECOND).is_some());
+/// }
+/// ```
+pub unsafe fn replace_profiler(
+    profiler: &'static dyn EmbeddedProfiler,
+) -> Option<&'static dyn EmbeddedProfiler> {
+    let previous = match STATE.load(Ordering::Acquire) {
+        UNINITIALIZED => None,
+        INITIALIZED => Some(PROFILER),
+        _ => unreachable!(),
+    };
+    PROFILER = profiler;
+    STATE.store(INITIALIZED, Ordering::Release);
+    previous
+}
+
+/// Uninstalls the global profiler, going back to the no-op profiler until
+/// [`set_profiler`] is called again.
+///
+/// # Safety
+/// Must be completed with no other threads running
+/// or, in an embedded single core environment, with interrupts disabled. No snapshot of
+/// the cleared profiler may be ended afterwards.
+///
+/// ```
+/// # struct MyProfiler;
+/// # impl embedded_profiling::EmbeddedProfiler for MyProfiler { fn read_clock(&self) -> embedded_profiling::EPInstant { embedded_profiling::EPInstant::from_ticks(0) } }
+/// # static MY_PROFILER: MyProfiler = MyProfiler;
+/// unsafe {
+///     embedded_profiling::set_profiler(&MY_PROFILER).unwrap();
+///     embedded_profiling::clear_profiler();
+///     // it can be set again
+///     embedded_profiling::set_profiler(&MY_PROFILER).unwrap();
+/// }
+/// ```
+pub unsafe fn clear_profiler() {
+    // stop handing out the profiler before it's replaced
+    STATE.store(UNINITIALIZED, Ordering::Release);
+    PROFILER = &NoopProfiler;
+}
+
+/// Sets the global profiler, checking that interrupts are disabled first.
+///
+/// Cortex-M specific, requires the `cortex-m` feature. Reads `PRIMASK` to turn calling
+/// [`set_profiler`] with interrupts enabled into an error instead of a silent race.
+///
+/// # Safety
+/// On multi-core systems, must be completed with no other cores running.
+///
+/// # Errors
+/// returns `Err(SetProfilerError::InterruptsEnabled)` if interrupts are enabled, and
+/// `Err(SetProfilerError::AlreadySet)` when a global profiler has already been configured
+///
+/// ```no_run
+/// # struct MyProfiler;
+/// # impl embedded_profiling::EmbeddedProfiler for MyProfiler { fn read_clock(&self) -> embedded_profiling::EPInstant { embedded_profiling::EPInstant::from_ticks(0) } }
+/// # static MY_PROFILER: MyProfiler = MyProfiler;
+/// cortex_m::interrupt::free(|_| unsafe {
+///     embedded_profiling::set_profiler_checked(&MY_PROFILER).unwrap();
+/// });
+/// ```
+#[cfg(feature = "cortex-m")]
+pub unsafe fn set_profiler_checked(
+    profiler: &'static dyn EmbeddedProfiler,
+) -> Result<(), SetProfilerError> {
+    set_profiler_if_masked(profiler, cortex_m::register::primask::read().is_active())
+}
+
+/// Sets the global profiler inside a critical section of the
+/// [`critical-section`](https://docs.rs/critical-section) crate.
+///
+/// Requires the `critical-section` feature. Unlike [`set_profiler`], this is safe to call
+/// with interrupts enabled or other cores running, as long as an implementation of
+/// `critical-section` suited to the target is linked in.
+///
+/// # Errors
+/// returns `Err(SetProfilerError::AlreadySet)` when a global profiler has already been configured
+///
+/// ```no_run
+/// # struct MyProfiler;
+/// # impl embedded_profiling::EmbeddedProfiler for MyProfiler { fn read_clock(&self) -> embedded_profiling::EPInstant { embedded_profiling::EPInstant::from_ticks(0) } }
+/// # static MY_PROFILER: MyProfiler = MyProfiler;
+/// embedded_profiling::set_profiler_cs(&MY_PROFILER).unwrap();
+/// ```
+#[cfg(feature = "critical-section")]
+pub fn set_profiler_cs(profiler: &'static dyn EmbeddedProfiler) -> Result<(), SetProfilerError> {
+    // nothing else can run while in the critical section, which is all `set_profiler` needs
+    critical_section::with(|_| unsafe { set_profiler(profiler) })
+}
+
+/// Sets the global profiler unless `interrupts_enabled`.
+#[cfg(any(test, feature = "cortex-m"))]
+unsafe fn set_profiler_if_masked(
+    profiler: &'static dyn EmbeddedProfiler,
+    interrupts_enabled: bool,
+) -> Result<(), SetProfilerError> {
+    if interrupts_enabled {
+        Err(SetProfilerError::InterruptsEnabled)
+    } else {
+        set_profiler(profiler)
+    }
+}
+
 /// Returns a reference to the configured profiler.
 ///
 /// If a profiler hasn't yet been set by [`set_profiler`], the no-op profiler
@@ -235,7 +1579,7 @@ pub unsafe fn set_profiler(
 /// functions rather than getting a reference through this function.
 ///
 /// ```
-/// let start = embedded_profiling::profiler().start_snapshot();
+/// let start = embedded_profiling::profiler().start_snapshot("doc-example");
 /// // (...)
 /// let snapshot = embedded_profiling::profiler().end_snapshot(start, "doc-example");
 /// ```
@@ -250,8 +1594,42 @@ pub fn profiler() -> &'static dyn EmbeddedProfiler {
     }
 }
 
+/// Which kind of profiler [`profiler`] returns, see [`profiler_kind`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProfilerKind {
+    /// No profiler has been set with [`set_profiler`], so nothing is measured or logged.
+    Noop,
+    /// The profiler set with [`set_profiler`].
+    User,
+}
+
+/// Returns whether the global profiler has been set, or the no-op profiler is used instead.
+///
+/// Forgetting to call [`set_profiler`] silently produces no output, so this can be checked
+/// at boot or from a debug command to tell that apart from a misbehaving profiler.
+///
+/// ```
+/// use embedded_profiling::ProfilerKind;
+///
+/// if embedded_profiling::profiler_kind() == ProfilerKind::Noop {
+///     println!("WARNING: profiling is using the no-op profiler, did you call set_profiler()?");
+/// }
+/// ```
+#[inline]
+#[must_use]
+pub fn profiler_kind() -> ProfilerKind {
+    if STATE.load(Ordering::Acquire) == INITIALIZED {
+        ProfilerKind::User
+    } else {
+        ProfilerKind::Noop
+    }
+}
+
 /// takes the starting snapshot of a specific trace.
 ///
+/// The name of the trace isn't known yet, so [`EmbeddedProfiler::at_start`] is called with
+/// an empty name. Use [`begin_snapshot`] to pass it on.
+///
 /// ```
 /// let start = embedded_profiling::start_snapshot();
 /// // (...)
@@ -260,7 +1638,50 @@ pub fn profiler() -> &'static dyn EmbeddedProfiler {
 #[inline]
 #[allow(clippy::must_use_candidate)]
 pub fn start_snapshot() -> EPInstant {
-    profiler().start_snapshot()
+    start_named_snapshot("")
+}
+
+#[inline]
+fn start_named_snapshot(name: &'static str) -> EPInstant {
+    debug_assert!(
+        !REQUIRE_PROFILER.load(Ordering::Relaxed) || profiler_kind() == ProfilerKind::User,
+        "profiling with the no-op profiler, was `set_profiler` called yet?"
+    );
+    let depth = DEPTH.load(Ordering::Relaxed);
+    DEPTH.store(depth + 1, Ordering::Relaxed);
+    if depth + 1 > MAX_DEPTH.load(Ordering::Relaxed) {
+        MAX_DEPTH.store(depth + 1, Ordering::Relaxed);
+    }
+    BEGUN.store(
+        BEGUN.load(Ordering::Relaxed) & !begun_bit(depth),
+        Ordering::Relaxed,
+    );
+    if EXCLUDE_NESTED_LOGGING.load(Ordering::Relaxed) {
+        if let Some(at_start) = LOGGING_TIME_AT_START.get(depth) {
+            at_start.store(LOGGING_TIME.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+    }
+    profiler().start_snapshot(name)
+}
+
+/// takes the starting snapshot of the trace `name`, like [`start_snapshot`], also emitting
+/// its begin event if [`emit_begin_end`] is enabled.
+///
+/// ```
+/// let start = embedded_profiling::begin_snapshot("doc-example");
+/// // (...)
+/// let snapshot = embedded_profiling::end_snapshot(start, "doc-example");
+/// ```
+#[inline]
+#[allow(clippy::must_use_candidate)]
+pub fn begin_snapshot(name: &'static str) -> EPInstant {
+    let start = start_named_snapshot(name);
+    let bit = begun_bit(DEPTH.load(Ordering::Relaxed).saturating_sub(1));
+    if EMIT_BEGIN_END.load(Ordering::Relaxed) && bit != 0 {
+        BEGUN.store(BEGUN.load(Ordering::Relaxed) | bit, Ordering::Relaxed);
+        profiler().log_begin(name, start);
+    }
+    start
 }
 
 /// computes the duration of the snapshot given the start time using the
@@ -268,7 +1689,160 @@ pub fn start_snapshot() -> EPInstant {
 #[inline]
 #[allow(clippy::must_use_candidate)]
 pub fn end_snapshot(start: EPInstant, name: &'static str) -> Option<EPSnapshot> {
-    profiler().end_snapshot(start, name)
+    let mut snapshot = profiler().end_snapshot(start, name);
+    let depth = DEPTH.load(Ordering::Relaxed).saturating_sub(1);
+    let (begun, bit) = (BEGUN.load(Ordering::Relaxed), begun_bit(depth));
+    if begun & bit != 0 {
+        BEGUN.store(begun & !bit, Ordering::Relaxed);
+        let end = snapshot.map_or_else(
+            || profiler().read_clock(),
+            |snapshot| start + snapshot.duration,
+        );
+        profiler().log_end(name, end);
+    }
+    DEPTH.store(depth, Ordering::Relaxed);
+
+    if EXCLUDE_NESTED_LOGGING.load(Ordering::Relaxed) {
+        if let (Some(snapshot), Some(at_start)) = (&mut snapshot, LOGGING_TIME_AT_START.get(depth))
+        {
+            let nested_logging = LOGGING_TIME
+                .load(Ordering::Relaxed)
+                .wrapping_sub(at_start.load(Ordering::Relaxed));
+            // only a conversion with `container-u64`
+            #[allow(clippy::useless_conversion)]
+            let nested_logging = EPDuration::from_ticks(nested_logging.into());
+            snapshot.duration = snapshot
+                .duration
+                .checked_sub(nested_logging)
+                .unwrap_or_else(|| EPDuration::from_ticks(0));
+        }
+    }
+    snapshot
+}
+
+/// Like [`end_snapshot`], but tells why no snapshot could be made.
+///
+/// # Errors
+/// returns `Err(SnapshotError::Overflow)` if the clock overflowed since `start`
+///
+/// ```
+/// let start = embedded_profiling::start_snapshot();
+/// // (...)
+/// match embedded_profiling::end_snapshot_checked(start, "doc-example") {
+///     Ok(snapshot) => embedded_profiling::log_snapshot(&snapshot),
+///     Err(error) => println!("`doc-example` dropped: {:?}", error),
+/// }
+/// ```
+#[inline]
+pub fn end_snapshot_checked(
+    start: EPInstant,
+    name: &'static str,
+) -> Result<EPSnapshot, SnapshotError> {
+    end_snapshot(start, name).ok_or(SnapshotError::Overflow)
+}
+
+/// Enables or disables emitting separate begin and end events through
+/// [`EmbeddedProfiler::log_begin`] and [`EmbeddedProfiler::log_end`], in addition to the
+/// complete snapshots, for streaming consumers that reconstruct durations themselves.
+///
+/// Begin events are emitted for traces started with [`begin_snapshot`], which [`profile`],
+/// [`profile_categorized`], [`ProfileGuard`] and
+/// [`profile_function`](embedded_profiling_proc_macros::profile_function) use. End events
+/// are emitted by [`end_snapshot`] for exactly those traces, even if the clock overflowed,
+/// so every begin event is matched and traces started with [`start_snapshot`] emit neither.
+/// Traces nested more than 32 deep emit no events.
+///
+/// ```
+/// embedded_profiling::emit_begin_end(true);
+/// ```
+pub fn emit_begin_end(enabled: bool) {
+    EMIT_BEGIN_END.store(enabled, Ordering::Relaxed);
+}
+
+/// Enables or disables asserting that a profiler was set with [`set_profiler`] before
+/// profiling with the global profiler, to catch traces taken too early in init that would
+/// silently be lost to the no-op profiler.
+///
+/// Only checked in debug builds, where [`start_snapshot`] (and everything calling it, like
+/// [`profile`]) then panics if no profiler was set. In release builds this does nothing.
+///
+/// ```
+/// embedded_profiling::require_profiler(true);
+/// // (...) set the profiler before profiling anything
+/// ```
+pub fn require_profiler(enabled: bool) {
+    REQUIRE_PROFILER.store(enabled, Ordering::Relaxed);
+}
+
+/// Enables or disables excluding the time spent logging nested snapshots from the duration
+/// of the snapshots around them, to get closer to their "self time".
+///
+/// With it enabled, [`log_snapshot`] measures how long the profiler takes to log each snapshot,
+/// and [`end_snapshot`] subtracts the logging time of every snapshot nested inside from the
+/// duration. This is an approximation: the clock reads measuring the logging, and the
+/// overhead of starting and ending the nested snapshots, are still counted. Only the
+/// outermost 8 levels of nesting are corrected. Enable it before any snapshot is started.
+///
+/// ```
+/// embedded_profiling::exclude_nested_logging(true);
+/// ```
+pub fn exclude_nested_logging(enabled: bool) {
+    EXCLUDE_NESTED_LOGGING.store(enabled, Ordering::Relaxed);
+}
+
+/// The number of snapshots started with [`start_snapshot`] that haven't been ended with
+/// [`end_snapshot`] yet, i.e. how deeply nested the current trace is.
+///
+/// The depth already includes a snapshot while its [`EmbeddedProfiler::at_start`] and
+/// [`EmbeddedProfiler::at_end`] run, so it's `1` for the outermost trace. Snapshots taken
+/// by calling the [`EmbeddedProfiler`] methods directly aren't counted, and neither are
+/// async spans like [`profile_async`], which stay open while other tasks run.
+///
+/// ```
+/// assert_eq!(embedded_profiling::nesting_depth(), 0);
+/// let start = embedded_profiling::start_snapshot();
+/// assert_eq!(embedded_profiling::nesting_depth(), 1);
+/// let _ = embedded_profiling::end_snapshot(start, "doc-example");
+/// assert_eq!(embedded_profiling::nesting_depth(), 0);
+/// ```
+#[inline]
+#[must_use]
+pub fn nesting_depth() -> usize {
+    DEPTH.load(Ordering::Relaxed)
+}
+
+/// The deepest [`nesting_depth`] reached so far, to size buffers that hold an entry per
+/// nesting level (like the `N` of [`SleepAwareProfiler`]) from what the instrumentation
+/// actually reaches.
+///
+/// ```
+/// let start = embedded_profiling::start_snapshot();
+/// let _ = embedded_profiling::end_snapshot(start, "doc-example");
+/// assert!(embedded_profiling::max_observed_depth() >= 1);
+/// ```
+#[inline]
+#[must_use]
+pub fn max_observed_depth() -> usize {
+    MAX_DEPTH.load(Ordering::Relaxed)
+}
+
+/// Resets [`max_observed_depth`] to the current [`nesting_depth`], e.g. to measure each
+/// phase of a program on its own.
+pub fn reset_max_observed_depth() {
+    MAX_DEPTH.store(DEPTH.load(Ordering::Relaxed), Ordering::Relaxed);
+}
+
+/// How many times the counter of the globally configured profiler has overflowed, see
+/// [`EmbeddedProfiler::overflow_events`].
+///
+/// ```
+/// // the no-op profiler doesn't track overflows
+/// assert_eq!(embedded_profiling::overflow_events(), None);
+/// ```
+#[inline]
+#[must_use]
+pub fn overflow_events() -> Option<u32> {
+    profiler().overflow_events()
 }
 
 /// Logs the given snapshot with the globally configured profiler.
@@ -281,7 +1855,75 @@ pub fn end_snapshot(start: EPInstant, name: &'static str) -> Option<EPSnapshot>
 /// }
 #[inline]
 pub fn log_snapshot(snapshot: &EPSnapshot) {
-    profiler().log_snapshot(snapshot);
+    if EXCLUDE_NESTED_LOGGING.load(Ordering::Relaxed) {
+        let profiler = profiler();
+        let start = profiler.read_clock();
+        profiler.log_snapshot(snapshot);
+        if let Some(logging) = profiler.read_clock().checked_duration_since(start) {
+            // only the wrapping difference between two readings of this matters, and it's
+            // only a cast with `container-u64`
+            #[allow(clippy::cast_possible_truncation, clippy::unnecessary_cast)]
+            let logging = logging.ticks() as u32;
+            LOGGING_TIME.store(
+                LOGGING_TIME.load(Ordering::Relaxed).wrapping_add(logging),
+                Ordering::Relaxed,
+            );
+        }
+    } else {
+        profiler().log_snapshot(snapshot);
+    }
+    let callback = SNAPSHOT_CALLBACK.load(Ordering::Acquire);
+    if !callback.is_null() {
+        // only ever set from a `fn(&EPSnapshot)` in `set_snapshot_callback`
+        let callback: fn(&EPSnapshot) = unsafe { core::mem::transmute(callback) };
+        callback(snapshot);
+    }
+    SNAPSHOTS_EMITTED.store(
+        SNAPSHOTS_EMITTED.load(Ordering::Relaxed).wrapping_add(1),
+        Ordering::Relaxed,
+    );
+}
+
+/// Sets `callback` to be called with every snapshot logged by [`log_snapshot`], to capture
+/// snapshots without implementing a whole [`EmbeddedProfiler`].
+///
+/// The callback is called right after the global profiler's own
+/// [`log_snapshot`](EmbeddedProfiler::log_snapshot), in addition to it, so it also sees the
+/// snapshots if no profiler was set. Replaces any previously set callback.
+///
+/// ```
+/// fn capture(snapshot: &embedded_profiling::EPSnapshot) {
+///     println!("captured {}", snapshot);
+/// }
+///
+/// embedded_profiling::set_snapshot_callback(capture);
+/// embedded_profiling::profile("captured", || {});
+/// ```
+pub fn set_snapshot_callback(callback: fn(&EPSnapshot)) {
+    SNAPSHOT_CALLBACK.store(callback as *mut (), Ordering::Release);
+}
+
+/// Removes the callback set with [`set_snapshot_callback`].
+pub fn clear_snapshot_callback() {
+    SNAPSHOT_CALLBACK.store(core::ptr::null_mut(), Ordering::Release);
+}
+
+/// How many snapshots [`log_snapshot`] has handed to the globally configured profiler, as
+/// a watchdog for the profiling pipeline: a supervisory task can check that it keeps
+/// advancing while the instrumented code runs.
+///
+/// The count wraps around, as a 64 bit atomic isn't available on every target, so compare
+/// it to a previous reading with [`wrapping_sub`](u32::wrapping_sub) rather than `<`.
+///
+/// ```
+/// let before = embedded_profiling::snapshots_emitted();
+/// embedded_profiling::profile("doc-example", || {});
+/// assert_eq!(embedded_profiling::snapshots_emitted().wrapping_sub(before), 1);
+/// ```
+#[inline]
+#[must_use]
+pub fn snapshots_emitted() -> u32 {
+    SNAPSHOTS_EMITTED.load(Ordering::Relaxed)
 }
 
 /// Profiles the given closure `target` with name `name`.
@@ -295,47 +1937,335 @@ pub fn profile<T, R>(name: &'static str, target: T) -> R
 where
     T: Fn() -> R,
 {
-    let start = start_snapshot();
+    let start = begin_snapshot(name);
+    let ret = target();
+    if let Some(snapshot) = end_snapshot(start, name) {
+        log_snapshot(&snapshot);
+    }
+    ret
+}
+
+/// Profiles the given closure `target` with name `name`, returning its value along with
+/// the snapshot instead of logging it, or `None` if the clock overflowed.
+///
+/// ```
+/// # fn control_step() {}
+/// let ((), snapshot) = embedded_profiling::profile_measured("control_step", control_step);
+/// if let Some(snapshot) = snapshot {
+///     // adapt the next iteration to `snapshot.duration`
+/// }
+/// ```
+pub fn profile_measured<T, R>(name: &'static str, target: T) -> (R, Option<EPSnapshot>)
+where
+    T: FnOnce() -> R,
+{
+    let start = begin_snapshot(name);
+    let ret = target();
+    (ret, end_snapshot(start, name))
+}
+
+/// Profiles the given block with the name `$name`, evaluating to the block's value.
+///
+/// The snapshot is ended by a [`ProfileGuard`], so an early `return`, `?` or `break` out of
+/// the block ends it too, timing everything up to that point.
+///
+/// ```
+/// # fn parse(buf: &[u8]) -> usize { buf.len() }
+/// # let buf = [0u8; 4];
+/// let x = embedded_profiling::profiled!("parse", { parse(&buf) });
+/// assert_eq!(x, 4);
+/// ```
+#[macro_export]
+macro_rules! profiled {
+    ($name:expr, $body:block) => {{
+        let _guard = $crate::ProfileGuard::new($name);
+        $body
+    }};
+}
+
+/// Profiles the given closure `target` with name `name` using `profiler` instead of the
+/// global profiler.
+///
+/// ```
+/// # use embedded_profiling::{EPInstant, EmbeddedProfiler};
+/// # struct MyProfiler;
+/// # impl EmbeddedProfiler for MyProfiler { fn read_clock(&self) -> EPInstant { EPInstant::from_ticks(0) } }
+/// let my_profiler = MyProfiler;
+/// embedded_profiling::profile_with_profiler(&my_profiler, "profile println", || {
+///     println!("profiling this closure");
+/// });
+/// ```
+pub fn profile_with_profiler<P, T, R>(profiler: &P, name: &'static str, target: T) -> R
+where
+    P: EmbeddedProfiler + ?Sized,
+    T: Fn() -> R,
+{
+    let start = profiler.start_snapshot(name);
+    let ret = target();
+    if let Some(snapshot) = profiler.end_snapshot(start, name) {
+        profiler.log_snapshot(&snapshot);
+    }
+    ret
+}
+
+/// Profiles the given closure `target` with name `name` in the category `category`.
+///
+/// ```
+/// embedded_profiling::profile_categorized("io", "flush", || {
+///     println!("profiling this closure");
+/// });
+/// ```
+pub fn profile_categorized<T, R>(category: &'static str, name: &'static str, target: T) -> R
+where
+    T: Fn() -> R,
+{
+    let start = begin_snapshot(name);
     let ret = target();
     if let Some(snapshot) = end_snapshot(start, name) {
+        log_snapshot(&snapshot.with_category(category));
+    }
+    ret
+}
+
+/// Profiles the given closure `target` with name `name`, even if it panics.
+///
+/// Unlike [`profile`], the snapshot is ended by a [`ProfileGuard`], so it is still logged
+/// while unwinding from a panic in `target`. With the `std` feature, such snapshots are
+/// marked as [`panicked`](EPSnapshot::panicked). On `no_std` targets panics usually abort,
+/// in which case this is the same as [`profile`].
+///
+/// ```
+/// let result = std::panic::catch_unwind(|| {
+///     embedded_profiling::profile_guarded("may panic", || panic!("oops"))
+/// });
+/// assert!(result.is_err());
+/// ```
+pub fn profile_guarded<T, R>(name: &'static str, target: T) -> R
+where
+    T: FnOnce() -> R,
+{
+    let _guard = ProfileGuard::new(name);
+    target()
+}
+
+/// Profiles from here until the end of the scope with name `name`, through the returned
+/// [`ProfileGuard`].
+///
+/// The snapshot is ended and logged when the guard is dropped, whichever way the scope is
+/// left. With the no-op profiler installed, nothing is logged.
+///
+/// ```
+/// fn early_return(bail: bool) -> u32 {
+///     let _guard = embedded_profiling::profile_scope("early_return");
+///     if bail {
+///         return 0;
+///     }
+///     42
+/// }
+/// # early_return(true);
+/// ```
+#[inline]
+#[must_use = "the snapshot ends as soon as the guard is dropped"]
+pub fn profile_scope(name: &'static str) -> ProfileGuard<'static> {
+    ProfileGuard::new(name)
+}
+
+/// Profiles the future `target` with name `name`, through the returned [`ProfiledFuture`].
+///
+/// The snapshot starts when the future is first polled and is ended and logged when it
+/// completes, so it spans the whole `.await`, without depending on any executor. It isn't
+/// counted in the [`nesting_depth`], see [`ProfiledFuture`].
+///
+/// ```
+/// # async fn receive() -> u8 { 0 }
+/// async fn task() {
+///     let byte = embedded_profiling::profile_async("receive", receive()).await;
+///     # let _ = byte;
+/// }
+/// ```
+#[inline]
+pub fn profile_async<F>(name: &'static str, target: F) -> ProfiledFuture<F>
+where
+    F: core::future::Future,
+{
+    ProfiledFuture::new(name, target)
+}
+
+/// Profiles the given fallible closure `target` with name `name`, logging the snapshot
+/// whether it returns `Ok` or `Err`.
+///
+/// The timing covers the failing path too, so an early return through `?` inside `target`
+/// is measured up to that point. Use [`profile_err`] to only log the failures.
+///
+/// ```
+/// # fn i2c_write() -> Result<(), ()> { Err(()) }
+/// let result = embedded_profiling::profile_result("i2c_write", || {
+///     i2c_write()?;
+///     i2c_write()
+/// });
+/// assert!(result.is_err());
+/// ```
+pub fn profile_result<T, E, F>(name: &'static str, target: F) -> Result<T, E>
+where
+    F: FnOnce() -> Result<T, E>,
+{
+    let start = begin_snapshot(name);
+    let ret = target();
+    if let Some(snapshot) = end_snapshot(start, name) {
+        log_snapshot(&snapshot);
+    }
+    ret
+}
+
+/// Profiles the given closure `target` with name `name`, only logging the snapshot if it
+/// returns an `Err`.
+///
+/// `Ok` results are still timed, so [`EmbeddedProfiler::at_start`] and
+/// [`EmbeddedProfiler::at_end`] are called either way, but they aren't logged. Note that this
+/// also keeps them from wrappers like [`AggregatingProfiler`], which only see what's logged.
+///
+/// ```
+/// # fn i2c_write() -> Result<(), ()> { Err(()) }
+/// let result = embedded_profiling::profile_err("i2c_write", || i2c_write());
+/// assert!(result.is_err());
+/// ```
+pub fn profile_err<T, E, F>(name: &'static str, target: F) -> Result<T, E>
+where
+    F: FnOnce() -> Result<T, E>,
+{
+    let start = begin_snapshot(name);
+    let ret = target();
+    let snapshot = end_snapshot(start, name);
+    if let (Some(snapshot), Err(_)) = (snapshot, &ret) {
         log_snapshot(&snapshot);
     }
     ret
 }
 
+/// Profiles the given closure `target` with name `name` only for its first `n` calls,
+/// counted in `counter`. Afterwards, `target` is just called.
+///
+/// `counter` has to be unique to the call site, see [`profile_n_times!`] for a macro
+/// providing one. It stops counting at `n`, so it never wraps around.
+///
+/// With the `critical-section` feature, `counter` is checked and counted up inside a
+/// critical section, so callers racing on the same call site profile exactly `n` calls.
+/// Without it, only loads and stores are used like for [`nesting_depth`], and an interrupt
+/// re-entering the call site between them can be profiled on top of the first `n` calls.
+///
+/// ```no_run
+/// use core::sync::atomic::AtomicU32;
+///
+/// static CALLS: AtomicU32 = AtomicU32::new(0);
+/// for _ in 0..100 {
+///     // only the first 3 iterations are profiled
+///     embedded_profiling::profile_n_times(&CALLS, "hot loop", 3, || {});
+/// }
+/// ```
+pub fn profile_n_times<T, R>(counter: &AtomicU32, name: &'static str, n: u32, target: T) -> R
+where
+    T: FnOnce() -> R,
+{
+    if !count_call(counter, n) {
+        return target();
+    }
+
+    let start = begin_snapshot(name);
+    let ret = target();
+    if let Some(snapshot) = end_snapshot(start, name) {
+        log_snapshot(&snapshot);
+    }
+    ret
+}
+
+/// Counts a call in `counter` unless it already reached `n`, returning whether it counted.
+fn count_call(counter: &AtomicU32, n: u32) -> bool {
+    let count_up = || {
+        let count = counter.load(Ordering::Relaxed);
+        if count < n {
+            counter.store(count + 1, Ordering::Relaxed);
+        }
+        count < n
+    };
+    #[cfg(feature = "critical-section")]
+    return critical_section::with(|_| count_up());
+    #[cfg(not(feature = "critical-section"))]
+    count_up()
+}
+
+/// Profiles the given closure `$target` with the name `$name` only for the first `$n`
+/// times this call site is reached, through [`profile_n_times`](fn@profile_n_times).
+///
+/// ```no_run
+/// fn hot_function() -> u32 {
+///     embedded_profiling::profile_n_times!("hot_function", 10, || 42)
+/// }
+/// # hot_function();
+/// ```
+#[macro_export]
+macro_rules! profile_n_times {
+    ($name:expr, $n:expr, $target:expr) => {{
+        static CALLS: core::sync::atomic::AtomicU32 = core::sync::atomic::AtomicU32::new(0);
+        $crate::profile_n_times(&CALLS, $name, $n, $target)
+    }};
+}
+
 #[cfg(test)]
 mod test {
-    use super::mock::StdMockProfiler;
+    use super::mock::{mock_profiler, set_profiler, FakeClock, StdMockProfiler, TickingClock};
     use super::*;
+    use core::cell::Cell;
 
-    #[cfg(feature = "proc-macros")]
+    // the instrumentation referring to the crate by name is compiled out with `disable`
+    #[cfg(all(feature = "proc-macros", not(feature = "disable")))]
     use crate as embedded_profiling;
 
-    use std::sync::Once;
-
-    static INIT_PROFILER: Once = Once::new();
-    static mut MOCK_PROFILER: Option<StdMockProfiler> = None;
-
-    fn set_profiler() {
-        INIT_PROFILER.call_once(|| unsafe {
-            if MOCK_PROFILER.is_none() {
-                MOCK_PROFILER = Some(StdMockProfiler::default());
-            }
-            super::set_profiler(MOCK_PROFILER.as_ref().unwrap()).unwrap();
-        });
-    }
-
     #[test]
     #[serial_test::serial]
     fn basic_duration() {
         let profiler = StdMockProfiler::default();
 
-        let start = profiler.start_snapshot();
+        let start = profiler.start_snapshot("basic_dur");
         std::thread::sleep(std::time::Duration::from_millis(25));
         let end = profiler.end_snapshot(start, "basic_dur").unwrap();
         profiler.log_snapshot(&end);
     }
 
+    #[test]
+    fn profiler_by_reference() {
+        use Ordering::SeqCst;
+
+        fn profile_with<P: EmbeddedProfiler>(profiler: P) {
+            let start = profiler.start_snapshot("by_ref");
+            if let Some(snapshot) = profiler.end_snapshot(start, "by_ref") {
+                profiler.log_snapshot(&snapshot);
+            }
+        }
+
+        let profiler = StdMockProfiler::default();
+        profile_with(&profiler);
+
+        let stats = &profiler.funcs_called;
+        assert!(stats.at_start.called.load(SeqCst));
+        assert!(stats.read_clock.called.load(SeqCst));
+        assert!(stats.at_end.called.load(SeqCst));
+        assert!(stats.log_snapshot.called.load(SeqCst));
+    }
+
+    #[test]
+    fn silent_snapshot_skips_hooks() {
+        use Ordering::SeqCst;
+
+        let profiler = StdMockProfiler::default();
+        let start = profiler.read_snapshot();
+        assert!(profiler.end_snapshot_silent(start, "silent").is_some());
+
+        let stats = &profiler.funcs_called;
+        assert!(stats.read_clock.called.load(SeqCst));
+        assert!(!stats.at_start.called.load(SeqCst));
+        assert!(!stats.at_end.called.load(SeqCst));
+    }
+
     #[test]
     #[serial_test::serial]
     fn basic_duration_and_set_profiler() {
@@ -359,7 +2289,176 @@ mod test {
         });
     }
 
-    #[cfg(feature = "proc-macros")]
+    #[test]
+    #[serial_test::serial]
+    fn set_profiler_checks_interrupts() {
+        assert!(matches!(
+            unsafe { set_profiler_if_masked(mock_profiler(), true) },
+            Err(SetProfilerError::InterruptsEnabled)
+        ));
+
+        // with interrupts disabled it gets as far as finding the profiler already set
+        set_profiler();
+        assert!(matches!(
+            unsafe { set_profiler_if_masked(mock_profiler(), false) },
+            Err(SetProfilerError::AlreadySet)
+        ));
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn nested_logging_excluded() {
+        let parent = profile_with_slow_logging(true);
+        // the parent's own 2 ms and the child's 1 ms, without the child's 20 ms of logging
+        assert!(parent.duration >= EPDuration::from_ticks(3_000));
+        assert!(parent.duration < EPDuration::from_ticks(3_000 + 5_000));
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn nested_logging_included() {
+        let parent = profile_with_slow_logging(false);
+        assert!(parent.duration >= EPDuration::from_ticks(3_000 + 20_000));
+    }
+
+    /// Profiles a parent sleeping 2 ms around a child sleeping 1 ms, while logging a snapshot
+    /// takes 20 ms, returning the parent's snapshot.
+    fn profile_with_slow_logging(exclude: bool) -> EPSnapshot {
+        set_profiler();
+        let sleep = |ms| std::thread::sleep(std::time::Duration::from_millis(ms));
+        mock_profiler().log_delay.store(20_000, Ordering::SeqCst);
+        exclude_nested_logging(exclude);
+
+        profile("self_time_parent", || {
+            sleep(1);
+            profile("self_time_child", || sleep(1));
+            sleep(1);
+        });
+
+        exclude_nested_logging(false);
+        mock_profiler().log_delay.store(0, Ordering::SeqCst);
+        mock_profiler().last_logged("self_time_parent").unwrap()
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn scope_logged_on_early_return() {
+        set_profiler();
+
+        fn early_return(bail: bool) -> u32 {
+            let _guard = profile_scope("profile_scope_early");
+            if bail {
+                return 0;
+            }
+            unreachable!()
+        }
+
+        let before = snapshots_emitted();
+        assert_eq!(early_return(true), 0);
+        assert_eq!(snapshots_emitted().wrapping_sub(before), 1);
+        assert!(mock_profiler().has_logged("profile_scope_early"));
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn measured_not_logged() {
+        set_profiler();
+
+        let before = snapshots_emitted();
+        let (value, snapshot) = profile_measured("profile_measured", || 42);
+        assert_eq!(value, 42);
+        assert_eq!(snapshot.unwrap().name, "profile_measured");
+        assert_eq!(snapshots_emitted(), before);
+        assert!(!mock_profiler().has_logged("profile_measured"));
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn result_logged_on_err() {
+        set_profiler();
+
+        assert_eq!(profile_err("profile_err_err", || Err::<(), _>(7)), Err(7));
+        assert!(mock_profiler().has_logged("profile_err_err"));
+
+        assert_eq!(profile_err("profile_err_ok", || Ok::<_, ()>(3)), Ok(3));
+        assert!(!mock_profiler().has_logged("profile_err_ok"));
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn result_logged_on_both_paths() {
+        set_profiler();
+
+        let failing = || -> Result<(), u8> {
+            Err(5)?;
+            unreachable!()
+        };
+        assert_eq!(profile_result("profile_result_err", failing), Err(5));
+        assert!(mock_profiler().has_logged("profile_result_err"));
+
+        assert_eq!(
+            profile_result("profile_result_ok", || Ok::<_, ()>(3)),
+            Ok(3)
+        );
+        assert!(mock_profiler().has_logged("profile_result_ok"));
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn profiled_only_n_times() {
+        set_profiler();
+
+        let before = snapshots_emitted();
+        let mut calls = 0;
+        for _ in 0..5 {
+            calls = crate::profile_n_times!("profiled_n_times", 3, || calls + 1);
+        }
+        assert_eq!(calls, 5);
+        assert_eq!(snapshots_emitted().wrapping_sub(before), 3);
+        assert!(mock_profiler().has_logged("profiled_n_times"));
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn profiled_block_value() {
+        set_profiler();
+
+        let value = profiled!("profiled_block_value", { 6 * 7 });
+        assert_eq!(value, 42);
+        assert!(mock_profiler().has_logged("profiled_block_value"));
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn profiled_block_early_return() {
+        fn first_even(values: &[u32]) -> Option<u32> {
+            profiled!("profiled_block_early_return", {
+                for &value in values {
+                    if value % 2 == 0 {
+                        return Some(value);
+                    }
+                }
+            });
+            None
+        }
+
+        set_profiler();
+
+        assert_eq!(first_even(&[1, 4, 5]), Some(4));
+        assert!(mock_profiler().has_logged("profiled_block_early_return"));
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn profile_categorized_closure() {
+        set_profiler();
+
+        profile_categorized("io", "categorized flush", || {});
+        assert!(mock_profiler().has_logged_category("io", "categorized flush"));
+        assert!(!mock_profiler().has_logged_category("io", "25ms closure"));
+    }
+
+    #[cfg(all(feature = "proc-macros", not(feature = "disable")))]
     #[test]
     #[serial_test::serial]
     fn profile_proc_macro() {
@@ -374,7 +2473,7 @@ mod test {
         delay_25ms();
     }
 
-    #[cfg(feature = "proc-macros")]
+    #[cfg(all(feature = "proc-macros", not(feature = "disable")))]
     #[test]
     #[serial_test::serial]
     fn check_call_and_order() {
@@ -391,7 +2490,7 @@ mod test {
         delay_25ms();
 
         // check if our functions were called and if the order is right
-        let stats = unsafe { &MOCK_PROFILER.as_ref().unwrap().funcs_called };
+        let stats = &mock_profiler().funcs_called;
         let at_start_was_called = stats.at_start.called.load(SeqCst);
         let read_clock_was_called = stats.read_clock.called.load(SeqCst);
         let at_end_was_called = stats.at_end.called.load(SeqCst);
@@ -435,6 +2534,481 @@ mod test {
         assert_eq!(log_snapshot_at, 3, "'log_snapshot' called at wrong time");
     }
 
+    #[test]
+    #[serial_test::serial]
+    fn nesting_depth_follows_snapshots() {
+        assert_eq!(nesting_depth(), 0);
+        profile("outer", || {
+            assert_eq!(nesting_depth(), 1);
+            profile("inner", || assert_eq!(nesting_depth(), 2));
+            assert_eq!(nesting_depth(), 1);
+        });
+        assert_eq!(nesting_depth(), 0);
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn begin_and_end_events() {
+        set_profiler();
+        mock_profiler().events.lock().unwrap().clear();
+
+        emit_begin_end(true);
+        profile("begin_end_outer", || profile("begin_end_inner", || {}));
+        emit_begin_end(false);
+        profile("begin_end_disabled", || {});
+
+        let events = mock_profiler().events.lock().unwrap();
+        assert_eq!(
+            *events,
+            [
+                ("begin", "begin_end_outer"),
+                ("begin", "begin_end_inner"),
+                ("end", "begin_end_inner"),
+                ("end", "begin_end_outer"),
+            ]
+        );
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn start_snapshot_emits_no_events() {
+        set_profiler();
+        mock_profiler().events.lock().unwrap().clear();
+
+        emit_begin_end(true);
+        let start = start_snapshot();
+        let _ = end_snapshot(start, "begin_end_unnamed");
+        let outer = start_snapshot();
+        profile("begin_end_nested", || {});
+        let _ = end_snapshot(outer, "begin_end_unnamed_outer");
+        emit_begin_end(false);
+
+        let events = mock_profiler().events.lock().unwrap();
+        assert_eq!(
+            *events,
+            [("begin", "begin_end_nested"), ("end", "begin_end_nested"),]
+        );
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn snapshots_emitted_advances() {
+        set_profiler();
+
+        let before = snapshots_emitted();
+        log_snapshot(&EPSnapshot::new("emitted", EPDuration::from_ticks(1)));
+        assert_eq!(snapshots_emitted().wrapping_sub(before), 1);
+        profile("emitted", || {});
+        assert_eq!(snapshots_emitted().wrapping_sub(before), 2);
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn snapshot_callback_fires() {
+        static CALLED: AtomicUsize = AtomicUsize::new(0);
+
+        fn count(snapshot: &EPSnapshot) {
+            if snapshot.name == "callback" {
+                CALLED.store(CALLED.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
+            }
+        }
+
+        set_profiler();
+        set_snapshot_callback(count);
+        profile("callback", || {});
+        log_snapshot(&EPSnapshot::new("callback", EPDuration::from_ticks(1)));
+        assert_eq!(CALLED.load(Ordering::Relaxed), 2);
+        assert!(mock_profiler().has_logged("callback"));
+
+        clear_snapshot_callback();
+        profile("callback", || {});
+        assert_eq!(CALLED.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn max_depth_tracked() {
+        reset_max_observed_depth();
+        assert_eq!(max_observed_depth(), 0);
+        profile("outer", || profile("middle", || profile("inner", || {})));
+        profile("shallow", || {});
+        assert_eq!(max_observed_depth(), 3);
+    }
+
+    #[test]
+    fn clamped_backward_jitter() {
+        let clock = FakeClock::default();
+        clock.set(1_000);
+        let start = clock.start_snapshot("jitter");
+
+        clock.set(999);
+        assert!(clock.end_snapshot(start, "jitter").is_none());
+        let snapshot = clock.end_snapshot_clamped(start, "jitter").unwrap();
+        assert_eq!(snapshot.duration.ticks(), 0);
+
+        clock.set(500);
+        assert!(clock.end_snapshot_clamped(start, "overflow").is_none());
+
+        clock.set(1_010);
+        let snapshot = clock.end_snapshot_clamped(start, "forward").unwrap();
+        assert_eq!(snapshot.duration.ticks(), 10);
+    }
+
+    #[test]
+    fn clamped_ends_through_silent() {
+        let (active, wall) = (FakeClock::default(), FakeClock::default());
+        let profiler = SleepAwareProfiler::<_, _, 1>::new(&active, &wall);
+
+        let start = profiler.start_snapshot("clamped_sleep");
+        active.advance(2);
+        wall.advance(100);
+        let snapshot = profiler
+            .end_snapshot_clamped(start, "clamped_sleep")
+            .unwrap();
+        assert_eq!(snapshot.duration.ticks(), 100);
+        assert_eq!(snapshot.active, Some(EPDuration::from_ticks(2)));
+    }
+
+    #[test]
+    fn overflow_reported() {
+        let clock = FakeClock::default();
+        clock.set(1_000);
+        let start = clock.start_snapshot("wrapped");
+
+        clock.set(10);
+        assert_eq!(
+            clock.end_snapshot_checked(start, "wrapped").unwrap_err(),
+            SnapshotError::Overflow
+        );
+
+        clock.set(1_005);
+        let snapshot = clock.end_snapshot_checked(start, "wrapped").unwrap();
+        assert_eq!(snapshot.duration.ticks(), 5);
+    }
+
+    #[test]
+    fn optional_profiler() {
+        use Ordering::SeqCst;
+
+        let some = Some(StdMockProfiler::default());
+        let start = some.start_snapshot("optional");
+        let snapshot = some.end_snapshot(start, "optional").unwrap();
+        some.log_snapshot(&snapshot);
+        let mock = some.as_ref().unwrap();
+        assert!(mock.has_logged("optional"));
+        assert!(mock.funcs_called.at_start.called.load(SeqCst));
+
+        let none: Option<StdMockProfiler> = None;
+        assert_eq!(none.read_clock(), EPInstant::from_ticks(0));
+        let start = none.start_snapshot("optional");
+        let snapshot = none.end_snapshot(start, "optional").unwrap();
+        assert_eq!(snapshot.duration.ticks(), 0);
+        none.log_snapshot(&snapshot);
+        assert_eq!(none.name(), "noop");
+        assert_eq!(none.overflow_events(), None);
+    }
+
+    #[test]
+    fn profiler_names() {
+        assert_eq!(NoopProfiler.name(), "noop");
+        assert_eq!(FakeClock::default().name(), "unknown");
+
+        let by_ref: &dyn EmbeddedProfiler = &NoopProfiler;
+        assert_eq!(by_ref.name(), "noop");
+        assert_eq!(MonotonicProfiler::new(NoopProfiler).name(), "noop");
+    }
+
+    #[test]
+    fn real_clock_reported() {
+        assert!(!NoopProfiler.has_real_clock());
+        assert!(!None::<FakeClock>.has_real_clock());
+        assert!(FakeClock::default().has_real_clock());
+        assert!(AggregatingProfiler::<_, 1>::new(FakeClock::default()).has_real_clock());
+        assert!(!MonotonicProfiler::new(NoopProfiler).has_real_clock());
+    }
+
+    #[test]
+    fn name_reaches_markers() {
+        #[derive(Default)]
+        struct NamedMarkers {
+            started: Cell<&'static str>,
+            ended: Cell<&'static str>,
+        }
+
+        impl EmbeddedProfiler for NamedMarkers {
+            fn read_clock(&self) -> EPInstant {
+                EPInstant::from_ticks(0)
+            }
+
+            fn at_start(&self, name: &'static str) {
+                self.started.set(name);
+            }
+
+            fn at_end(&self, name: &'static str) {
+                self.ended.set(name);
+            }
+        }
+
+        let profiler = AggregatingProfiler::<_, 1>::new(NamedMarkers::default());
+        profiler.profile("named", || ());
+        let markers = profiler.free();
+        assert_eq!(markers.started.get(), "named");
+        assert_eq!(markers.ended.get(), "named");
+    }
+
+    #[test]
+    fn overflow_events_forwarded() {
+        struct ExtendedProfiler;
+
+        impl EmbeddedProfiler for ExtendedProfiler {
+            fn read_clock(&self) -> EPInstant {
+                EPInstant::from_ticks(0)
+            }
+
+            fn overflow_events(&self) -> Option<u32> {
+                Some(3)
+            }
+        }
+
+        let profiler = MonotonicProfiler::new(ExtendedProfiler);
+        let by_ref: &dyn EmbeddedProfiler = &profiler;
+        assert_eq!(by_ref.overflow_events(), Some(3));
+        assert_eq!(FakeClock::default().overflow_events(), None);
+    }
+
+    #[test]
+    fn frozen_clock_is_not_running() {
+        assert!(!FakeClock::default().clock_is_running());
+        assert!(StdMockProfiler::default().clock_is_running());
+    }
+
+    #[test]
+    fn self_test_reports_resolution() {
+        let report = TickingClock::new(1).self_test();
+        assert_eq!(report.resolution.ticks(), 1);
+        // one read to start and one to end
+        assert_eq!(report.empty_overhead.ticks(), 1);
+
+        assert_eq!(TickingClock::new(0).self_test().resolution.ticks(), 0);
+    }
+
+    #[test]
+    fn snapshot_display() {
+        let snapshot = EPSnapshot::new("display", EPDuration::from_ticks(123));
+        assert_eq!(snapshot.to_string(), "<EPSS display: 123 us>");
+
+        let snapshot = snapshot.with_raw(14_760);
+        assert_eq!(snapshot.to_string(), "<EPSS display: 123 us (14760 cyc)>");
+
+        let snapshot = snapshot.with_active(EPDuration::from_ticks(23));
+        assert_eq!(
+            snapshot.to_string(),
+            "<EPSS display: 123 us (14760 cyc) (23 us active)>"
+        );
+    }
+
+    #[test]
+    fn write_snapshot_to_writer() {
+        let mut output = String::new();
+        write_snapshot(
+            &mut output,
+            &EPSnapshot::new("first", EPDuration::from_ticks(1)),
+        )
+        .unwrap();
+        write_snapshot(
+            &mut output,
+            &EPSnapshot::new("second", EPDuration::from_ticks(2)).with_raw(240),
+        )
+        .unwrap();
+        assert_eq!(
+            output,
+            "<EPSS first: 1 us>\n<EPSS second: 2 us (240 cyc)>\n"
+        );
+    }
+
+    #[test]
+    fn format_snapshot_into_slice() {
+        let snapshot = EPSnapshot::new("slice", EPDuration::from_ticks(12));
+        let formatted = "<EPSS slice: 12 us>";
+
+        // exact fit
+        let mut exact = [0; 19];
+        assert_eq!(format_snapshot(&snapshot, &mut exact), Ok(formatted.len()));
+        assert_eq!(&exact, formatted.as_bytes());
+
+        // more room than needed
+        let mut roomy = [0xFF; 32];
+        assert_eq!(format_snapshot(&snapshot, &mut roomy), Ok(formatted.len()));
+        assert_eq!(&roomy[..formatted.len()], formatted.as_bytes());
+        assert_eq!(roomy[formatted.len()], 0xFF);
+
+        // one byte short
+        let mut short = [0; 18];
+        assert_eq!(
+            format_snapshot(&snapshot, &mut short),
+            Err(core::fmt::Error)
+        );
+        assert_eq!(format_snapshot(&snapshot, &mut []), Err(core::fmt::Error));
+    }
+
+    #[test]
+    fn rebase_durations() {
+        let duration = EPDuration::from_ticks(1_234_567);
+        assert_eq!(to_millis_duration(duration).ticks(), 1_234);
+        assert_eq!(to_nanos_duration(duration).ticks(), 1_234_567_000);
+        assert_eq!(rebase::<1, 1>(duration).ticks(), 1);
+
+        let too_long = EPDuration::from_ticks(EPContainer::MAX);
+        assert!(try_rebase::<1, 1_000_000_000>(too_long).is_none());
+    }
+
+    #[test]
+    fn micros_round_trip() {
+        for micros in [0, 1, 1_234_567, EPContainer::MAX] {
+            assert_eq!(instant_to_micros(instant_from_micros(micros)), micros);
+            assert_eq!(duration_to_micros(duration_from_micros(micros)), micros);
+        }
+
+        let instant = EPInstant::from_ticks(42);
+        assert_eq!(instant_from_micros(instant_to_micros(instant)), instant);
+        let duration = EPDuration::micros(42);
+        assert_eq!(duration_from_micros(duration_to_micros(duration)), duration);
+    }
+
+    #[test]
+    fn ratios() {
+        let duration = EPDuration::from_ticks;
+        assert!((ratio(duration(35), duration(100)) - 0.35).abs() < f32::EPSILON);
+        assert!((ratio(duration(3), duration(2)) - 1.5).abs() < f32::EPSILON);
+        assert_eq!(permille(duration(35), duration(100)), Some(350));
+        assert_eq!(permille(duration(1), duration(3)), Some(333));
+
+        // zero denominator
+        assert!(ratio(duration(35), duration(0)).abs() < f32::EPSILON);
+        assert_eq!(permille(duration(35), duration(0)), None);
+
+        // `numer * 1_000` overflows a `u32`
+        let large = 4_000_000_000;
+        assert_eq!(permille(duration(large), duration(large)), Some(1_000));
+        assert_eq!(permille(duration(large / 4), duration(large)), Some(250));
+        assert!((ratio(duration(large / 4), duration(large)) - 0.25).abs() < f32::EPSILON);
+        assert_eq!(
+            permille(duration(EPContainer::MAX), duration(1)),
+            Some(EPContainer::MAX)
+        );
+    }
+
+    #[test]
+    fn dynamic_conversion_matches_const() {
+        fn check<const NOM: u32, const DENOM: u32>(ticks: EPContainer) {
+            assert_eq!(
+                convert_instant_dyn(ticks, NOM, DENOM),
+                convert_instant(EPInstantGeneric::<NOM, DENOM>::from_ticks(ticks)),
+            );
+        }
+
+        for ticks in [0, 1, 7, 1_000, 123_456, 4_000_000_000] {
+            check::<1, 1_000_000>(ticks);
+            check::<1, 120_000_000>(ticks);
+            check::<1, 16_000_000>(ticks);
+            check::<4, 4_000_000>(ticks);
+            check::<3, 48_000_000>(ticks);
+        }
+        check::<1, 1_000>(1_000);
+    }
+
+    #[test]
+    fn raw_snapshot_keeps_cycles() {
+        /// Counts 10 cycles per microsecond.
+        #[derive(Default)]
+        struct CycleClock {
+            cycles: Cell<EPContainer>,
+        }
+
+        impl EmbeddedProfiler for CycleClock {
+            fn read_clock(&self) -> EPInstant {
+                EPInstant::from_ticks(self.cycles.get() / 10)
+            }
+
+            fn read_clock_raw(&self) -> EPContainer {
+                self.cycles.get()
+            }
+
+            fn duration_from_raw(&self, raw: EPContainer) -> EPDuration {
+                EPDuration::from_ticks(raw / 10)
+            }
+        }
+
+        let clock = CycleClock::default();
+        let start = clock.start_snapshot_raw("raw");
+        clock.cycles.set(37);
+        let snapshot = clock.end_snapshot_raw(start, "raw").unwrap();
+        assert_eq!(snapshot.raw, Some(37));
+        assert_eq!(snapshot.duration.ticks(), 3);
+        assert!(clock.end_snapshot_raw(40, "raw").is_none());
+
+        // by default, the native ticks are microseconds
+        let clock = FakeClock::default();
+        let start = clock.start_snapshot_raw("raw");
+        clock.advance(12);
+        let snapshot = clock.end_snapshot_raw(start, "raw").unwrap();
+        assert_eq!(snapshot.raw, Some(12));
+        assert_eq!(snapshot.duration.ticks(), 12);
+    }
+
+    #[test]
+    fn u16_spans_around_the_wrap() {
+        let start = EPInstant::from_ticks(1_000_000);
+        let measure = |span: EPContainer| {
+            let end = start + EPDuration::from_ticks(span);
+            wrapping_duration_u16(truncate_instant_u16(start), truncate_instant_u16(end))
+        };
+
+        // just under the wrap, the span is exact
+        assert_eq!(measure(65_535).ticks(), 65_535);
+        // at and just over the wrap, only the remainder of 65.536 ms is left
+        assert_eq!(measure(65_536).ticks(), 0);
+        assert_eq!(measure(65_537).ticks(), 1);
+    }
+
+    #[test]
+    fn nanosecond_conversion() {
+        let ns = convert_instant_ns(EPInstantGeneric::<1, 120_000_000>::from_ticks(120));
+        assert_eq!(ns.ticks(), 1_000);
+        let ns = convert_instant_ns(EPInstantGeneric::<1, 120_000_000>::from_ticks(1));
+        assert_eq!(ns.ticks(), 8);
+        assert_eq!(NanosPerTick::<1, 120_000_000>::FRACTION, (25, 3));
+
+        // truncated to the container, even when the tick count times the fraction isn't
+        let ticks = EPContainer::from(u32::MAX);
+        let ns = convert_instant_ns(EPInstantGeneric::<1, 120_000_000>::from_ticks(ticks));
+        #[allow(clippy::cast_possible_truncation)]
+        let expected = (u128::from(ticks) * 25 / 3) as EPContainer;
+        assert_eq!(ns.ticks(), expected);
+        let ns = convert_instant_ns(EPInstantGeneric::<7, 3>::from_ticks(ticks));
+        #[allow(clippy::cast_possible_truncation)]
+        let expected = (u128::from(ticks) * 7_000_000_000 / 3) as EPContainer;
+        assert_eq!(ns.ticks(), expected);
+
+        // by default, the microseconds of the clock
+        let clock = FakeClock::default();
+        clock.set(5);
+        assert_eq!(clock.read_clock_ns().ticks(), 5_000);
+
+        // wraps instead of panicking
+        clock.set(EPContainer::MAX);
+        assert_eq!(
+            clock.read_clock_ns().ticks(),
+            EPContainer::MAX.wrapping_mul(1_000)
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn dynamic_conversion_overflow() {
+        let _ = convert_instant_dyn(EPContainer::MAX, 1, 1_000);
+    }
+
     #[test]
     const fn check_conversion() {
         // check to see if the conversion is naive and saturates or not
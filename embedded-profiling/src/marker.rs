@@ -0,0 +1,169 @@
+//! Keyed markers, timing regions that begin and end in different functions.
+
+use crate::{EPContainer, EPInstant, EPSnapshot};
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+/// How many markers can be pending at once, see [`mark_begin`].
+pub const MARKER_SLOTS: usize = 8;
+
+/// An [`EPInstant`] stored with load/store atomics only.
+///
+/// With `container-u64` it's stored as two halves, which isn't atomic as a whole. That is
+/// fine as every slot is published only after its instant is stored.
+struct AtomicInstant {
+    low: AtomicU32,
+    #[cfg(feature = "container-u64")]
+    high: AtomicU32,
+}
+
+impl AtomicInstant {
+    const fn new() -> Self {
+        Self {
+            low: AtomicU32::new(0),
+            #[cfg(feature = "container-u64")]
+            high: AtomicU32::new(0),
+        }
+    }
+
+    // only a cast with `container-u64`, where it splits the instant into its halves
+    #[allow(clippy::cast_possible_truncation, clippy::unnecessary_cast)]
+    fn store(&self, instant: EPInstant) {
+        let ticks = instant.ticks();
+        self.low.store(ticks as u32, Ordering::Relaxed);
+        #[cfg(feature = "container-u64")]
+        self.high.store((ticks >> 32) as u32, Ordering::Relaxed);
+    }
+
+    fn load(&self) -> EPInstant {
+        // only a conversion with `container-u64`
+        #[allow(clippy::useless_conversion)]
+        let ticks = EPContainer::from(self.low.load(Ordering::Relaxed));
+        #[cfg(feature = "container-u64")]
+        let ticks = ticks | EPContainer::from(self.high.load(Ordering::Relaxed)) << 32;
+        EPInstant::from_ticks(ticks)
+    }
+}
+
+/// A pending marker.
+struct Slot {
+    pending: AtomicBool,
+    key: AtomicU32,
+    start: AtomicInstant,
+}
+
+impl Slot {
+    const fn new() -> Self {
+        Self {
+            pending: AtomicBool::new(false),
+            key: AtomicU32::new(0),
+            start: AtomicInstant::new(),
+        }
+    }
+}
+
+/// The pending markers, the marker `key` lives in slot `key % MARKER_SLOTS`.
+#[allow(clippy::declare_interior_mutable_const)]
+static SLOTS: [Slot; MARKER_SLOTS] = {
+    const FREE: Slot = Slot::new();
+    [FREE; MARKER_SLOTS]
+};
+
+fn slot(key: u32) -> &'static Slot {
+    &SLOTS[key as usize % MARKER_SLOTS]
+}
+
+/// Begins the marker `key` with the globally configured profiler, to be ended by
+/// [`mark_end`] with the same key, possibly in a different function.
+///
+/// Unlike [`start_snapshot`](crate::start_snapshot), this times regions that don't fit in a
+/// single scope, like the latency from a request arriving to its response being sent.
+///
+/// Markers live in a table of [`MARKER_SLOTS`] slots, `key` going in slot
+/// `key % MARKER_SLOTS`. Beginning a marker whose slot is taken, by the same or another
+/// key, replaces the pending one. Keys that are pending at the same time should therefore
+/// differ modulo [`MARKER_SLOTS`], e.g. by using a small request counter as key.
+///
+/// ```
+/// fn on_request() {
+///     embedded_profiling::mark_begin(7);
+/// }
+///
+/// fn on_response() {
+///     embedded_profiling::mark_end(7, "request");
+/// }
+/// # on_request();
+/// # on_response();
+/// ```
+pub fn mark_begin(key: u32) {
+    let slot = slot(key);
+    // retract the pending marker while the new one is written
+    slot.pending.store(false, Ordering::Relaxed);
+    // the name is only known once the marker ends
+    slot.start.store(crate::profiler().start_snapshot(""));
+    slot.key.store(key, Ordering::Relaxed);
+    slot.pending.store(true, Ordering::Release);
+}
+
+/// Ends the marker `key` begun by [`mark_begin`], logging its snapshot named `name` with the
+/// globally configured profiler.
+///
+/// Returns the logged snapshot, or `None` if nothing was logged: when `key` isn't pending
+/// (it was never begun, already ended or replaced by another marker), or when the clock
+/// overflowed like with [`end_snapshot`](crate::end_snapshot).
+#[allow(clippy::must_use_candidate)]
+pub fn mark_end(key: u32, name: &'static str) -> Option<EPSnapshot> {
+    let slot = slot(key);
+    if !slot.pending.load(Ordering::Acquire) || slot.key.load(Ordering::Relaxed) != key {
+        return None;
+    }
+    let start = slot.start.load();
+    slot.pending.store(false, Ordering::Relaxed);
+
+    let snapshot = crate::profiler().end_snapshot(start, name)?;
+    crate::log_snapshot(&snapshot);
+    Some(snapshot)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::{mock_profiler, set_profiler};
+
+    fn request_arrived() {
+        mark_begin(3);
+    }
+
+    fn response_sent() -> Option<EPSnapshot> {
+        mark_end(3, "marker_request")
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn begin_and_end_in_different_functions() {
+        set_profiler();
+
+        request_arrived();
+        assert!(response_sent().is_some());
+        assert!(mock_profiler().has_logged("marker_request"));
+
+        // the slot was freed
+        assert!(response_sent().is_none());
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn missing_and_replaced_keys() {
+        set_profiler();
+
+        assert!(mark_end(1, "marker_never_begun").is_none());
+
+        mark_begin(2);
+        // same slot, different key
+        mark_begin(2 + MARKER_SLOTS as u32);
+        assert!(mark_end(2, "marker_replaced").is_none());
+        assert!(mark_end(2 + MARKER_SLOTS as u32, "marker_replacing").is_some());
+
+        assert!(!mock_profiler().has_logged("marker_never_begun"));
+        assert!(!mock_profiler().has_logged("marker_replaced"));
+    }
+}
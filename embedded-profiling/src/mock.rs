@@ -40,6 +40,13 @@ impl core::default::Default for CalledFuncs {
 pub struct StdMockProfiler {
     start: std::time::Instant,
     pub funcs_called: CalledFuncs,
+    pub logged: std::sync::Mutex<Vec<crate::EPSnapshot>>,
+    /// how long every `log_snapshot` takes, in microseconds
+    pub log_delay: atomic::AtomicU64,
+    /// every `log_begin` and `log_end`, as `("begin" | "end", name)`
+    pub events: std::sync::Mutex<Vec<(&'static str, &'static str)>>,
+    /// how many times `at_end` was called
+    pub ended: atomic::AtomicUsize,
 }
 
 impl core::default::Default for StdMockProfiler {
@@ -47,10 +54,68 @@ impl core::default::Default for StdMockProfiler {
         Self {
             start: std::time::Instant::now(),
             funcs_called: Default::default(),
+            logged: Default::default(),
+            log_delay: Default::default(),
+            events: Default::default(),
+            ended: Default::default(),
         }
     }
 }
 
+impl StdMockProfiler {
+    /// the last logged snapshot named `name`
+    pub fn last_logged(&self, name: &str) -> Option<crate::EPSnapshot> {
+        self.logged
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|snapshot| snapshot.name == name)
+            .copied()
+    }
+
+    /// whether a snapshot named `name` has been logged
+    pub fn has_logged(&self, name: &str) -> bool {
+        self.last_logged(name).is_some()
+    }
+
+    /// whether a snapshot named `name` has been logged as [`panicked`](crate::EPSnapshot::panicked)
+    pub fn has_logged_panicked(&self, name: &str) -> bool {
+        self.last_logged(name)
+            .map_or(false, |snapshot| snapshot.panicked)
+    }
+
+    /// whether a snapshot named `name` has been logged in the category `category`
+    pub fn has_logged_category(&self, category: &str, name: &str) -> bool {
+        self.last_logged(name)
+            .map_or(false, |snapshot| snapshot.category == Some(category))
+    }
+}
+
+static INIT_MOCK: std::sync::Once = std::sync::Once::new();
+static INIT_PROFILER: std::sync::Once = std::sync::Once::new();
+static mut MOCK_PROFILER: Option<StdMockProfiler> = None;
+
+/// The mock profiler that gets installed as the global profiler by [`set_profiler`]
+#[allow(static_mut_refs)]
+pub fn mock_profiler() -> &'static StdMockProfiler {
+    unsafe {
+        INIT_MOCK.call_once(|| {
+            if MOCK_PROFILER.is_none() {
+                MOCK_PROFILER = Some(StdMockProfiler::default());
+            }
+        });
+        MOCK_PROFILER.as_ref().unwrap()
+    }
+}
+
+/// Sets the global profiler to [`mock_profiler`], if it hasn't been already
+pub fn set_profiler() {
+    INIT_PROFILER.call_once(|| unsafe {
+        crate::set_profiler(mock_profiler()).unwrap();
+    });
+}
+
 impl super::EmbeddedProfiler for StdMockProfiler {
     fn read_clock(&self) -> crate::EPInstant {
         // First, log that we've been called and when
@@ -80,10 +145,22 @@ impl super::EmbeddedProfiler for StdMockProfiler {
         }
 
         // now actually do the profiler stuff
+        self.logged.lock().unwrap().push(*snapshot);
         println!("{}", snapshot);
+        std::thread::sleep(std::time::Duration::from_micros(
+            self.log_delay.load(SeqCst),
+        ));
     }
 
-    fn at_start(&self) {
+    fn log_begin(&self, name: &'static str, _at: crate::EPInstant) {
+        self.events.lock().unwrap().push(("begin", name));
+    }
+
+    fn log_end(&self, name: &'static str, _at: crate::EPInstant) {
+        self.events.lock().unwrap().push(("end", name));
+    }
+
+    fn at_start(&self, _name: &'static str) {
         // First, log that we've been called and when
         if !self.funcs_called.at_start.called.load(SeqCst) {
             let when = self.funcs_called.count.load(SeqCst);
@@ -94,7 +171,8 @@ impl super::EmbeddedProfiler for StdMockProfiler {
         }
     }
 
-    fn at_end(&self) {
+    fn at_end(&self, _name: &'static str) {
+        self.ended.fetch_add(1, SeqCst);
         // First, log that we've been called and when
         if !self.funcs_called.at_end.called.load(SeqCst) {
             let when = self.funcs_called.count.load(SeqCst);
@@ -105,3 +183,102 @@ impl super::EmbeddedProfiler for StdMockProfiler {
         }
     }
 }
+
+/// A clock that only moves when told to.
+#[derive(Default)]
+pub struct FakeClock {
+    now: core::cell::Cell<crate::EPContainer>,
+}
+
+impl FakeClock {
+    pub fn set(&self, ticks: crate::EPContainer) {
+        self.now.set(ticks);
+    }
+
+    pub fn advance(&self, ticks: crate::EPContainer) {
+        self.now.set(self.now.get() + ticks);
+    }
+}
+
+impl super::EmbeddedProfiler for FakeClock {
+    fn read_clock(&self) -> EPInstant {
+        EPInstant::from_ticks(self.now.get())
+    }
+}
+
+/// A clock that moves by `step` every time it's read.
+pub struct TickingClock {
+    now: core::cell::Cell<crate::EPContainer>,
+    step: crate::EPContainer,
+}
+
+impl TickingClock {
+    pub fn new(step: crate::EPContainer) -> Self {
+        Self {
+            now: Default::default(),
+            step,
+        }
+    }
+}
+
+impl super::EmbeddedProfiler for TickingClock {
+    fn read_clock(&self) -> EPInstant {
+        let now = self.now.get();
+        self.now.set(now + self.step);
+        EPInstant::from_ticks(now)
+    }
+}
+
+/// Pending the first time it's polled, like waiting on a peripheral.
+pub struct YieldOnce(pub bool);
+
+impl core::future::Future for YieldOnce {
+    type Output = u8;
+
+    fn poll(
+        mut self: core::pin::Pin<&mut Self>,
+        _cx: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<u8> {
+        if self.0 {
+            core::task::Poll::Ready(42)
+        } else {
+            self.0 = true;
+            core::task::Poll::Pending
+        }
+    }
+}
+
+struct NoopWake;
+
+impl std::task::Wake for NoopWake {
+    fn wake(self: std::sync::Arc<Self>) {}
+}
+
+/// A waker that does nothing, for polling futures by hand.
+pub fn noop_waker() -> core::task::Waker {
+    std::sync::Arc::new(NoopWake).into()
+}
+
+/// A spin lock as the critical section of the tests, the restore state depends on the
+/// features other crates of the workspace enable.
+#[cfg(feature = "critical-section")]
+struct HostCriticalSection;
+#[cfg(feature = "critical-section")]
+critical_section::set_impl!(HostCriticalSection);
+
+#[cfg(feature = "critical-section")]
+static LOCKED: atomic::AtomicBool = atomic::AtomicBool::new(false);
+
+#[cfg(feature = "critical-section")]
+unsafe impl critical_section::Impl for HostCriticalSection {
+    unsafe fn acquire() -> critical_section::RawRestoreState {
+        while LOCKED.swap(true, atomic::Ordering::Acquire) {
+            core::hint::spin_loop();
+        }
+        critical_section::RawRestoreState::default()
+    }
+
+    unsafe fn release(_restore_state: critical_section::RawRestoreState) {
+        LOCKED.store(false, atomic::Ordering::Release);
+    }
+}
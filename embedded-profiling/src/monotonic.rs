@@ -0,0 +1,131 @@
+//! Debugging wrapper that checks the clock never runs backwards.
+
+use crate::{EPInstant, EPSnapshot, EmbeddedProfiler};
+
+/// Wraps the [`EmbeddedProfiler`] `P`, asserting that every [`read_clock`](EmbeddedProfiler::read_clock)
+/// is at or after the previous one.
+///
+/// A misconfigured clock (wrong `FREQ`, wrong clock source) shows up as absurd durations that are
+/// hard to trace back to the clock. With this wrapper installed during bring-up, a backwards
+/// tick panics immediately with a clear message instead.
+///
+/// Snapshots are read and ended by `P`, so wrappers changing how they're measured (like
+/// [`SleepAwareProfiler`](crate::SleepAwareProfiler)) keep working. Their starts are checked
+/// like any other reading, and a snapshot whose end is before its start trips the check too.
+///
+/// The check only exists with `debug_assertions` enabled. In release builds this wrapper only
+/// forwards to `P`. The last reading is kept in a [`Cell`](core::cell::Cell) in every build,
+/// so the wrapper is `!Sync` regardless of `debug_assertions`, and code sharing it between
+/// threads doesn't only compile in release.
+///
+/// Note that a free-running counter wrapping around is indistinguishable from a backwards
+/// jump if the wrap is larger than half of the counter's range, so long spans on non-extended
+/// profilers may trip the check too.
+pub struct MonotonicProfiler<P> {
+    inner: P,
+    // only read by the check, but kept without it to not change the auto traits
+    #[cfg_attr(not(debug_assertions), allow(dead_code))]
+    last: core::cell::Cell<Option<EPInstant>>,
+}
+
+impl<P> MonotonicProfiler<P> {
+    /// Creates a new [`MonotonicProfiler`] around the profiler `inner`.
+    #[must_use]
+    pub const fn new(inner: P) -> Self {
+        Self {
+            inner,
+            last: core::cell::Cell::new(None),
+        }
+    }
+
+    /// Consumes [`MonotonicProfiler`], returning the wrapped profiler.
+    pub fn free(self) -> P {
+        self.inner
+    }
+
+    /// Asserts `now` is at or after the previous reading, and returns it.
+    fn check(&self, now: EPInstant) -> EPInstant {
+        #[cfg(debug_assertions)]
+        {
+            if let Some(last) = self.last.get() {
+                assert!(
+                    now >= last,
+                    "profiler clock went backwards ({} us -> {} us), check the clock source and `FREQ`",
+                    last.ticks(),
+                    now.ticks()
+                );
+            }
+            self.last.set(Some(now));
+        }
+
+        now
+    }
+}
+
+impl<P> EmbeddedProfiler for MonotonicProfiler<P>
+where
+    P: EmbeddedProfiler,
+{
+    fn read_clock(&self) -> EPInstant {
+        self.check(self.inner.read_clock())
+    }
+
+    fn read_snapshot(&self) -> EPInstant {
+        self.check(self.inner.read_snapshot())
+    }
+
+    fn end_snapshot_silent(&self, start: EPInstant, name: &'static str) -> Option<EPSnapshot> {
+        let snapshot = self.inner.end_snapshot_silent(start, name);
+        // the end of the snapshot is read by `P`, which only fails if it's before the start
+        #[cfg(debug_assertions)]
+        assert!(
+            snapshot.is_some(),
+            "profiler clock went backwards during `{}`, check the clock source and `FREQ`",
+            name
+        );
+        snapshot
+    }
+
+    forward_profiler!(self.inner =>
+        log_snapshot,
+        hooks,
+        clock_is_running,
+        overflow_events,
+        name,
+        backward_jitter,
+        synchronize,
+        has_real_clock,
+        read_clock_raw,
+        duration_from_raw,
+        read_clock_ns,
+        end_snapshot_clamped,
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::FakeClock;
+
+    #[test]
+    fn monotonic_clock_passes() {
+        let profiler = MonotonicProfiler::new(FakeClock::default());
+
+        let start = profiler.start_snapshot("monotonic");
+        profiler.inner.advance(10);
+        let snapshot = profiler.end_snapshot(start, "monotonic").unwrap();
+        assert_eq!(snapshot.duration.ticks(), 10);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "profiler clock went backwards")]
+    fn backwards_clock_trips() {
+        let profiler = MonotonicProfiler::new(FakeClock::default());
+
+        profiler.inner.set(100);
+        let _ = profiler.read_clock();
+        profiler.inner.set(50);
+        let _ = profiler.read_clock();
+    }
+}
@@ -0,0 +1,196 @@
+//! Diagnostic wrapper that detects spans ending out of order.
+
+use crate::{EPInstant, EPSnapshot, EmbeddedProfiler};
+use core::cell::RefCell;
+use critical_section::Mutex;
+
+/// Wraps the [`EmbeddedProfiler`] `P`, calling `on_overlap` with the name of every span that
+/// ends while it isn't the innermost open span.
+///
+/// Properly nested spans form a tree, which flamegraphs rely on. A span that ends while
+/// another span started after it is still open overlaps it instead, which happens when an
+/// interrupt ends a span it didn't start, when a thread is preempted mid-span, or when
+/// snapshots are ended in the wrong order. The hook is called right as the offending span
+/// ends, before it is passed on to `P`.
+///
+/// Open spans are identified by their start instant, so two spans started within the same
+/// tick can't be told apart. Up to `N` nested spans are tracked. Spans opened while `N`
+/// are already open aren't checked, and neither is the order they end in.
+///
+/// Requires the `critical-section` feature. The open spans are updated inside a
+/// [`critical-section`](https://docs.rs/critical-section) critical section, so interrupts
+/// can start and end spans at any time. `on_overlap` is called outside of it.
+///
+/// ```
+/// # use embedded_profiling::*;
+/// # struct MyProfiler;
+/// # impl EmbeddedProfiler for MyProfiler { fn read_clock(&self) -> EPInstant { EPInstant::from_ticks(0) } }
+/// fn report_overlap(name: &'static str) {
+///     println!("span {} overlaps a later one", name);
+/// }
+///
+/// let profiler = OverlapCheckProfiler::<_, 8>::new(MyProfiler, report_overlap);
+/// ```
+pub struct OverlapCheckProfiler<P, const N: usize> {
+    inner: P,
+    on_overlap: fn(&'static str),
+    spans: Mutex<RefCell<OpenSpans<N>>>,
+}
+
+/// The spans currently open, innermost last.
+struct OpenSpans<const N: usize> {
+    open: [EPInstant; N],
+    depth: usize,
+    /// Spans opened while `N` were already open.
+    untracked: usize,
+}
+
+impl<const N: usize> OpenSpans<N> {
+    /// Tracks the span started at `start` as the innermost open one.
+    fn open(&mut self, start: EPInstant) {
+        match self.open.get_mut(self.depth) {
+            Some(slot) => {
+                *slot = start;
+                self.depth += 1;
+            }
+            None => self.untracked += 1,
+        }
+    }
+
+    /// Stops tracking the span started at `start`, returning whether it overlaps a later one.
+    fn close(&mut self, start: EPInstant) -> bool {
+        if self.untracked > 0 {
+            self.untracked -= 1;
+            return false;
+        }
+
+        let depth = self.depth;
+        let open = &mut self.open[..depth];
+        match open.iter().rposition(|&open| open == start) {
+            Some(position) => {
+                // everything started after it is still open
+                open[position..].rotate_left(1);
+                self.depth = depth - 1;
+                position + 1 != depth
+            }
+            // ends a span that was never opened through us
+            None => true,
+        }
+    }
+}
+
+impl<P, const N: usize> OverlapCheckProfiler<P, N> {
+    /// Creates a new [`OverlapCheckProfiler`] around the profiler `inner`, calling
+    /// `on_overlap` for every span ending out of order.
+    #[must_use]
+    pub const fn new(inner: P, on_overlap: fn(&'static str)) -> Self {
+        Self {
+            inner,
+            on_overlap,
+            spans: Mutex::new(RefCell::new(OpenSpans {
+                open: [EPInstant::from_ticks(0); N],
+                depth: 0,
+                untracked: 0,
+            })),
+        }
+    }
+
+    /// Consumes [`OverlapCheckProfiler`], returning the wrapped profiler.
+    pub fn free(self) -> P {
+        self.inner
+    }
+
+    /// Tracks the span started at `start` as the innermost open one.
+    fn open(&self, start: EPInstant) {
+        critical_section::with(|cs| self.spans.borrow(cs).borrow_mut().open(start));
+    }
+
+    /// Stops tracking the span `name` started at `start`, checking it was the innermost one.
+    fn close(&self, start: EPInstant, name: &'static str) {
+        if critical_section::with(|cs| self.spans.borrow(cs).borrow_mut().close(start)) {
+            (self.on_overlap)(name);
+        }
+    }
+}
+
+impl<P, const N: usize> EmbeddedProfiler for OverlapCheckProfiler<P, N>
+where
+    P: EmbeddedProfiler,
+{
+    fn read_snapshot(&self) -> EPInstant {
+        let start = self.inner.read_snapshot();
+        self.open(start);
+        start
+    }
+
+    fn end_snapshot_silent(&self, start: EPInstant, name: &'static str) -> Option<EPSnapshot> {
+        self.close(start, name);
+        self.inner.end_snapshot_silent(start, name)
+    }
+
+    fn end_snapshot_clamped(&self, start: EPInstant, name: &'static str) -> Option<EPSnapshot> {
+        self.close(start, name);
+        self.inner.end_snapshot_clamped(start, name)
+    }
+
+    forward_profiler!(self.inner => clock, log_snapshot, hooks);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::FakeClock;
+    use std::sync::Mutex;
+
+    static OVERLAPPING: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+    fn record_overlap(name: &'static str) {
+        OVERLAPPING.lock().unwrap().push(name);
+    }
+
+    #[test]
+    fn out_of_order_end_flagged() {
+        let profiler = OverlapCheckProfiler::<_, 4>::new(FakeClock::default(), record_overlap);
+
+        // properly nested
+        let outer = profiler.start_snapshot("outer");
+        profiler.inner.advance(1);
+        let inner = profiler.start_snapshot("nested");
+        profiler.inner.advance(1);
+        assert!(profiler.end_snapshot(inner, "nested").is_some());
+        assert!(profiler.end_snapshot(outer, "outer").is_some());
+        assert!(OVERLAPPING.lock().unwrap().is_empty());
+
+        // the thread's span ends while the interrupt's is still open
+        let thread = profiler.start_snapshot("thread");
+        profiler.inner.advance(1);
+        let isr = profiler.start_snapshot("isr");
+        profiler.inner.advance(1);
+        assert!(profiler.end_snapshot(thread, "thread").is_some());
+        assert!(profiler.end_snapshot(isr, "isr").is_some());
+        assert_eq!(*OVERLAPPING.lock().unwrap(), ["thread"]);
+    }
+
+    static WRAPPED_OVERLAPPING: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+    fn record_wrapped_overlap(name: &'static str) {
+        WRAPPED_OVERLAPPING.lock().unwrap().push(name);
+    }
+
+    #[test]
+    fn nested_through_wrapper() {
+        let clock = FakeClock::default();
+        let profiler = crate::AggregatingProfiler::<_, 2>::new(OverlapCheckProfiler::<_, 4>::new(
+            &clock,
+            record_wrapped_overlap,
+        ));
+
+        let outer = profiler.start_snapshot("wrapped_outer");
+        clock.advance(1);
+        let inner = profiler.start_snapshot("wrapped_nested");
+        clock.advance(1);
+        assert!(profiler.end_snapshot(inner, "wrapped_nested").is_some());
+        assert!(profiler.end_snapshot(outer, "wrapped_outer").is_some());
+        assert!(WRAPPED_OVERLAPPING.lock().unwrap().is_empty());
+    }
+}
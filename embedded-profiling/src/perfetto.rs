@@ -0,0 +1,271 @@
+//! Encodes snapshots as a [Perfetto](https://perfetto.dev) protobuf trace.
+//!
+//! Much more compact than Chrome's JSON trace format, so it scales to far larger captures.
+//! Every snapshot becomes a `SLICE_BEGIN` and a `SLICE_END` track event on a single track,
+//! which Perfetto nests by their timestamps. Only snapshots that know their
+//! [`start`](crate::EPSnapshot::start) can be placed on the timeline.
+//!
+//! The output is a `Trace` message, i.e. a stream of length-delimited `TracePacket`s, which
+//! can be opened directly in the [Perfetto UI](https://ui.perfetto.dev).
+//!
+//! ```
+//! # use embedded_profiling::*;
+//! let mut writer = perfetto::PerfettoWriter::new(Vec::new(), "firmware").unwrap();
+//! let snapshot = EPSnapshot::new("poll", EPDuration::from_ticks(12))
+//!     .with_start(EPInstant::from_ticks(100));
+//! writer.write_snapshot(&snapshot).unwrap();
+//! let trace: Vec<u8> = writer.free();
+//! ```
+
+use crate::EPSnapshot;
+use std::io::{self, Write};
+use std::vec::Vec;
+
+/// `Trace.packet`
+const TRACE_PACKET: u32 = 1;
+/// `TracePacket.timestamp`
+const PACKET_TIMESTAMP: u32 = 8;
+/// `TracePacket.trusted_packet_sequence_id`
+const PACKET_SEQUENCE_ID: u32 = 10;
+/// `TracePacket.track_event`
+const PACKET_TRACK_EVENT: u32 = 11;
+/// `TracePacket.track_descriptor`
+const PACKET_TRACK_DESCRIPTOR: u32 = 60;
+/// `TrackDescriptor.uuid`
+const TRACK_UUID: u32 = 1;
+/// `TrackDescriptor.name`
+const TRACK_NAME: u32 = 2;
+/// `TrackEvent.type`
+const EVENT_TYPE: u32 = 9;
+/// `TrackEvent.track_uuid`
+const EVENT_TRACK_UUID: u32 = 11;
+/// `TrackEvent.categories`
+const EVENT_CATEGORIES: u32 = 22;
+/// `TrackEvent.name`
+const EVENT_NAME: u32 = 23;
+
+/// `TrackEvent.Type.TYPE_SLICE_BEGIN`
+const SLICE_BEGIN: u64 = 1;
+/// `TrackEvent.Type.TYPE_SLICE_END`
+const SLICE_END: u64 = 2;
+
+/// The uuid of the single track all snapshots are put on.
+const TRACK: u64 = 1;
+/// The id of the single packet sequence we write.
+const SEQUENCE_ID: u64 = 1;
+
+/// Perfetto timestamps are in nanoseconds.
+// only a conversion without `container-u64`
+#[allow(clippy::useless_conversion)]
+fn micros_to_nanos(micros: crate::EPContainer) -> u64 {
+    u64::from(micros).saturating_mul(1_000)
+}
+
+/// A protobuf message being encoded.
+#[derive(Default)]
+struct Message(Vec<u8>);
+
+impl Message {
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            // the low 7 bits, with the continuation bit set
+            #[allow(clippy::cast_possible_truncation)]
+            self.0.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        #[allow(clippy::cast_possible_truncation)]
+        self.0.push(value as u8);
+    }
+
+    fn uint(&mut self, field: u32, value: u64) -> &mut Self {
+        self.varint(u64::from(field) << 3);
+        self.varint(value);
+        self
+    }
+
+    fn bytes(&mut self, field: u32, value: &[u8]) -> &mut Self {
+        self.varint(u64::from(field) << 3 | 2);
+        self.varint(value.len() as u64);
+        self.0.extend_from_slice(value);
+        self
+    }
+
+    fn message(&mut self, field: u32, value: &Message) -> &mut Self {
+        self.bytes(field, &value.0)
+    }
+}
+
+/// Writes snapshots as Perfetto trace packets to the [`Write`]r `W`.
+pub struct PerfettoWriter<W> {
+    writer: W,
+}
+
+impl<W: Write> PerfettoWriter<W> {
+    /// Creates a new [`PerfettoWriter`], writing the descriptor of the track named `track_name`
+    /// all snapshots are put on.
+    ///
+    /// # Errors
+    /// propagates any error of `writer`
+    pub fn new(writer: W, track_name: &str) -> io::Result<Self> {
+        let mut this = Self { writer };
+
+        let mut track = Message::default();
+        track
+            .uint(TRACK_UUID, TRACK)
+            .bytes(TRACK_NAME, track_name.as_bytes());
+        let mut packet = Message::default();
+        packet
+            .uint(PACKET_SEQUENCE_ID, SEQUENCE_ID)
+            .message(PACKET_TRACK_DESCRIPTOR, &track);
+        this.write_packet(&packet)?;
+
+        Ok(this)
+    }
+
+    /// Writes `snapshot` as a slice, if it knows its [`start`](EPSnapshot::start).
+    ///
+    /// Returns whether the snapshot was written.
+    ///
+    /// # Errors
+    /// propagates any error of `writer`
+    pub fn write_snapshot(&mut self, snapshot: &EPSnapshot) -> io::Result<bool> {
+        let start = match snapshot.start {
+            Some(start) => start,
+            None => return Ok(false),
+        };
+        let start_ns = micros_to_nanos(start.ticks());
+        let end_ns = start_ns.saturating_add(micros_to_nanos(snapshot.duration.ticks()));
+
+        let mut begin = Message::default();
+        begin
+            .uint(EVENT_TYPE, SLICE_BEGIN)
+            .uint(EVENT_TRACK_UUID, TRACK)
+            .bytes(EVENT_NAME, snapshot.name.as_bytes());
+        if let Some(category) = snapshot.category {
+            begin.bytes(EVENT_CATEGORIES, category.as_bytes());
+        }
+        self.write_event(start_ns, &begin)?;
+
+        let mut end = Message::default();
+        end.uint(EVENT_TYPE, SLICE_END)
+            .uint(EVENT_TRACK_UUID, TRACK);
+        self.write_event(end_ns, &end)?;
+
+        Ok(true)
+    }
+
+    /// Consumes [`PerfettoWriter`], returning the writer.
+    pub fn free(self) -> W {
+        self.writer
+    }
+
+    fn write_event(&mut self, timestamp_ns: u64, event: &Message) -> io::Result<()> {
+        let mut packet = Message::default();
+        packet
+            .uint(PACKET_TIMESTAMP, timestamp_ns)
+            .uint(PACKET_SEQUENCE_ID, SEQUENCE_ID)
+            .message(PACKET_TRACK_EVENT, event);
+        self.write_packet(&packet)
+    }
+
+    fn write_packet(&mut self, packet: &Message) -> io::Result<()> {
+        let mut trace = Message::default();
+        trace.message(TRACE_PACKET, packet);
+        self.writer.write_all(&trace.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{EPDuration, EPInstant};
+    use prost::Message as _;
+
+    /// The subset of Perfetto's `trace.proto` we write.
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct Trace {
+        #[prost(message, repeated, tag = "1")]
+        packet: Vec<TracePacket>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct TracePacket {
+        #[prost(uint64, optional, tag = "8")]
+        timestamp: Option<u64>,
+        #[prost(uint32, optional, tag = "10")]
+        trusted_packet_sequence_id: Option<u32>,
+        #[prost(message, optional, tag = "11")]
+        track_event: Option<TrackEvent>,
+        #[prost(message, optional, tag = "60")]
+        track_descriptor: Option<TrackDescriptor>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct TrackDescriptor {
+        #[prost(uint64, optional, tag = "1")]
+        uuid: Option<u64>,
+        #[prost(string, optional, tag = "2")]
+        name: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct TrackEvent {
+        #[prost(int32, optional, tag = "9")]
+        r#type: Option<i32>,
+        #[prost(uint64, optional, tag = "11")]
+        track_uuid: Option<u64>,
+        #[prost(string, repeated, tag = "22")]
+        categories: Vec<String>,
+        #[prost(string, optional, tag = "23")]
+        name: Option<String>,
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut writer = PerfettoWriter::new(Vec::new(), "firmware").unwrap();
+        let outer = EPSnapshot::new("outer", EPDuration::from_ticks(50))
+            .with_start(EPInstant::from_ticks(100));
+        let inner = EPSnapshot::new("inner", EPDuration::from_ticks(10))
+            .with_start(EPInstant::from_ticks(120))
+            .with_category("io");
+        // logged as they end, so the child comes first
+        assert!(writer.write_snapshot(&inner).unwrap());
+        assert!(writer.write_snapshot(&outer).unwrap());
+        assert!(!writer
+            .write_snapshot(&EPSnapshot::new("no start", EPDuration::from_ticks(1)))
+            .unwrap());
+
+        let trace = Trace::decode(writer.free().as_slice()).unwrap();
+        assert_eq!(trace.packet.len(), 5);
+
+        let track = trace.packet[0].track_descriptor.as_ref().unwrap();
+        assert_eq!(track.uuid, Some(TRACK));
+        assert_eq!(track.name.as_deref(), Some("firmware"));
+
+        let events: Vec<_> = trace.packet[1..]
+            .iter()
+            .map(|packet| {
+                let event = packet.track_event.as_ref().unwrap();
+                assert_eq!(event.track_uuid, Some(TRACK));
+                (
+                    packet.timestamp.unwrap(),
+                    event.r#type.unwrap(),
+                    event.name.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            events,
+            [
+                (120_000, 1, Some("inner".to_string())),
+                (130_000, 2, None),
+                (100_000, 1, Some("outer".to_string())),
+                (150_000, 2, None),
+            ]
+        );
+        assert_eq!(
+            trace.packet[1].track_event.as_ref().unwrap().categories,
+            ["io"]
+        );
+    }
+}
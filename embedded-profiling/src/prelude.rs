@@ -0,0 +1,30 @@
+//! The commonly used items, for glob importing.
+//!
+//! ```
+//! use embedded_profiling::prelude::*;
+//!
+//! struct MyProfiler;
+//!
+//! impl EmbeddedProfiler for MyProfiler {
+//!     fn read_clock(&self) -> EPInstant {
+//!         convert_instant(EPInstantGeneric::<1, 1_000>::from_ticks(0))
+//!     }
+//! }
+//!
+//! profile("closure", || println!("profiled"));
+//! ```
+
+// traits
+pub use crate::EmbeddedProfiler;
+
+// types
+pub use crate::{EPContainer, EPDuration, EPInstant, EPInstantGeneric, EPSnapshot};
+
+// functions
+pub use crate::{
+    convert_instant, end_snapshot, log_snapshot, profile, profile_with_profiler, profiler,
+    set_profiler, start_snapshot,
+};
+
+#[cfg(feature = "proc-macros")]
+pub use crate::profile_function;
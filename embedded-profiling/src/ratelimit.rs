@@ -0,0 +1,147 @@
+//! Sink dropping snapshots beyond a rate limit.
+
+use crate::{EPDuration, EPInstant, EPSnapshot, EmbeddedProfiler, Sink};
+use core::cell::Cell;
+
+/// Wraps the [`Sink`] `S`, dropping snapshots once `limit` of them were emitted in the
+/// current time window, so a flood of snapshots can't stall the system in
+/// [`log_snapshot`](EmbeddedProfiler::log_snapshot).
+///
+/// The window is measured with the clock of the profiler emitting to this sink. It starts
+/// with the first emitted snapshot and ends with the first one after `window` has elapsed.
+///
+/// Dropped snapshots are counted in [`dropped`](RateLimitedSink::dropped). The number
+/// dropped during a window is also reported to the function given to
+/// [`with_drop_report`](RateLimitedSink::with_drop_report), when the next window starts.
+///
+/// ```
+/// # use embedded_profiling::*;
+/// # struct MyClock;
+/// # impl EmbeddedProfiler for MyClock { fn read_clock(&self) -> EPInstant {EPInstant::from_ticks(0)} }
+/// let sink = RateLimitedSink::new(
+///     |snapshot: &EPSnapshot| println!("{}", snapshot),
+///     10,
+///     EPDuration::from_ticks(1_000),
+/// )
+/// .with_drop_report(|dropped| println!("dropped {} snapshots", dropped));
+/// let profiler = ProfilerBuilder::new().clock(MyClock).sink(sink).build();
+/// ```
+pub struct RateLimitedSink<S> {
+    inner: S,
+    limit: u32,
+    window: EPDuration,
+    window_start: Cell<Option<EPInstant>>,
+    emitted: Cell<u32>,
+    dropped_in_window: Cell<u32>,
+    dropped: Cell<u32>,
+    report: Option<fn(u32)>,
+}
+
+impl<S> RateLimitedSink<S> {
+    /// Creates a new [`RateLimitedSink`] around the sink `inner`, emitting at most `limit`
+    /// snapshots every `window`.
+    #[must_use]
+    pub const fn new(inner: S, limit: u32, window: EPDuration) -> Self {
+        Self {
+            inner,
+            limit,
+            window,
+            window_start: Cell::new(None),
+            emitted: Cell::new(0),
+            dropped_in_window: Cell::new(0),
+            dropped: Cell::new(0),
+            report: None,
+        }
+    }
+
+    /// Calls `report` with the number of snapshots dropped during a window, when the next
+    /// window starts. Windows without any dropped snapshots aren't reported.
+    #[must_use]
+    pub const fn with_drop_report(mut self, report: fn(u32)) -> Self {
+        self.report = Some(report);
+        self
+    }
+
+    /// Consumes [`RateLimitedSink`], returning the wrapped sink.
+    pub fn free(self) -> S {
+        self.inner
+    }
+
+    /// How many snapshots were dropped in total, saturating at the maximum.
+    #[must_use]
+    pub fn dropped(&self) -> u32 {
+        self.dropped.get()
+    }
+}
+
+impl<S> Sink for RateLimitedSink<S>
+where
+    S: Sink,
+{
+    fn emit<C: EmbeddedProfiler>(&self, clock: &C, snapshot: &EPSnapshot) {
+        let now = clock.read_clock();
+        let elapsed = self
+            .window_start
+            .get()
+            .map(|window_start| now.checked_duration_since(window_start));
+        match elapsed {
+            Some(Some(elapsed)) if elapsed < self.window => {}
+            // no window yet, or the clock went backwards since it started
+            _ => {
+                let dropped = self.dropped_in_window.replace(0);
+                if let (Some(report), true) = (self.report, dropped > 0) {
+                    report(dropped);
+                }
+                self.window_start.set(Some(now));
+                self.emitted.set(0);
+            }
+        }
+
+        let emitted = self.emitted.get();
+        if emitted < self.limit {
+            self.emitted.set(emitted + 1);
+            self.inner.emit(clock, snapshot);
+        } else {
+            self.dropped_in_window
+                .set(self.dropped_in_window.get().saturating_add(1));
+            self.dropped.set(self.dropped.get().saturating_add(1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::FakeClock;
+    use core::sync::atomic::{AtomicU32, Ordering};
+
+    static REPORTED: AtomicU32 = AtomicU32::new(0);
+
+    #[test]
+    fn burst_dropped_and_counted() {
+        let emitted = Cell::new(0);
+        let sink = RateLimitedSink::new(
+            |_: &EPSnapshot| emitted.set(emitted.get() + 1),
+            3,
+            EPDuration::from_ticks(1_000),
+        )
+        .with_drop_report(|dropped| REPORTED.store(dropped, Ordering::Relaxed));
+        let clock = FakeClock::default();
+        let snapshot = EPSnapshot::new("flood", EPDuration::from_ticks(1));
+
+        for _ in 0..10 {
+            sink.emit(&clock, &snapshot);
+            clock.advance(10);
+        }
+        assert_eq!(emitted.get(), 3);
+        assert_eq!(sink.dropped(), 7);
+        assert_eq!(REPORTED.load(Ordering::Relaxed), 0);
+
+        // the next window emits again and reports the drops of the previous one
+        clock.advance(1_000);
+        sink.emit(&clock, &snapshot);
+        assert_eq!(emitted.get(), 4);
+        assert_eq!(sink.dropped(), 7);
+        assert_eq!(REPORTED.load(Ordering::Relaxed), 7);
+    }
+}
@@ -0,0 +1,131 @@
+//! Wrapper buffering the last snapshots in RAM, to be drained later.
+
+use crate::{EPSnapshot, EmbeddedProfiler};
+use core::cell::RefCell;
+use critical_section::Mutex;
+
+/// A fixed ring of the last `N` snapshots, overwriting the oldest one when full.
+struct Ring<const N: usize> {
+    slots: [Option<EPSnapshot>; N],
+    /// The index of the oldest snapshot.
+    head: usize,
+    len: usize,
+}
+
+impl<const N: usize> Ring<N> {
+    const fn new() -> Self {
+        Self {
+            slots: [None; N],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, snapshot: EPSnapshot) {
+        if N == 0 {
+            return;
+        }
+        self.slots[(self.head + self.len) % N] = Some(snapshot);
+        if self.len < N {
+            self.len += 1;
+        } else {
+            self.head = (self.head + 1) % N;
+        }
+    }
+
+    fn pop(&mut self) -> Option<EPSnapshot> {
+        if self.len == 0 {
+            return None;
+        }
+        let snapshot = self.slots[self.head].take();
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        snapshot
+    }
+}
+
+/// Wraps the [`EmbeddedProfiler`] `P`, buffering the last `N` logged snapshots in RAM instead
+/// of logging them, so a burst of snapshots from a fast interrupt handler can be
+/// [`drain`](RingProfiler::drain)ed later, e.g. from the idle loop where a slow serial port
+/// doesn't matter.
+///
+/// Once `N` snapshots are buffered, every new one overwrites the oldest. The buffer is only
+/// accessed inside a [`critical-section`](https://docs.rs/critical-section) critical section,
+/// so snapshots can be logged from interrupts that preempt draining.
+///
+/// Only available with the `critical-section` feature.
+///
+/// ```ignore
+/// # use embedded_profiling::*;
+/// # struct MyClock;
+/// # impl EmbeddedProfiler for MyClock { fn read_clock(&self) -> EPInstant {EPInstant::from_ticks(0)} }
+/// static PROFILER: RingProfiler<MyClock, 32> = RingProfiler::new(MyClock);
+///
+/// // in the interrupt handler
+/// PROFILER.profile("isr", || {});
+///
+/// // in the idle loop
+/// PROFILER.drain(|snapshot| println!("{}", snapshot));
+/// ```
+pub struct RingProfiler<P, const N: usize> {
+    inner: P,
+    ring: Mutex<RefCell<Ring<N>>>,
+}
+
+impl<P, const N: usize> RingProfiler<P, N> {
+    /// Creates a new [`RingProfiler`] around the profiler `inner`, buffering up to `N` snapshots.
+    #[must_use]
+    pub const fn new(inner: P) -> Self {
+        Self {
+            inner,
+            ring: Mutex::new(RefCell::new(Ring::new())),
+        }
+    }
+
+    /// Consumes [`RingProfiler`], returning the wrapped profiler.
+    pub fn free(self) -> P {
+        self.inner
+    }
+
+    /// Calls `f` with every buffered snapshot, oldest first, emptying the buffer.
+    ///
+    /// Each snapshot is taken out in its own short critical section and `f` is called
+    /// outside of it, so interrupts aren't held off while `f` writes it out. Snapshots logged
+    /// while draining are drained too.
+    pub fn drain(&self, mut f: impl FnMut(&EPSnapshot)) {
+        while let Some(snapshot) =
+            critical_section::with(|cs| self.ring.borrow(cs).borrow_mut().pop())
+        {
+            f(&snapshot);
+        }
+    }
+
+    /// How many snapshots are buffered.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        critical_section::with(|cs| self.ring.borrow(cs).borrow().len)
+    }
+
+    /// Whether no snapshots are buffered.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether `N` snapshots are buffered, so the next one overwrites the oldest.
+    #[must_use]
+    pub fn is_full(&self) -> bool {
+        self.len() == N
+    }
+}
+
+impl<P, const N: usize> EmbeddedProfiler for RingProfiler<P, N>
+where
+    P: EmbeddedProfiler,
+{
+    fn log_snapshot(&self, snapshot: &EPSnapshot) {
+        critical_section::with(|cs| self.ring.borrow(cs).borrow_mut().push(*snapshot));
+    }
+
+    forward_profiler!(self.inner => clock, hooks, snapshots);
+}
@@ -0,0 +1,82 @@
+//! Helpers for profiling [RTIC](https://rtic.rs) tasks.
+//!
+//! Place [`profile_task!`](crate::profile_task) at the top of a `#[task]` and the rest of
+//! the task's body is profiled under the task's name, including any early return.
+//!
+//! ```
+//! # mod app {
+//! fn blink() {
+//!     let _guard = embedded_profiling::profile_task!();
+//!     // (...)
+//! }
+//! # }
+//! ```
+//!
+//! ## RTIC's monotonic
+//!
+//! The profiler and RTIC's monotonic are independent: durations are measured with the
+//! globally configured profiler's clock, never with the monotonic, and profiling doesn't
+//! schedule or delay anything. Both can't own the same peripheral though. If the monotonic
+//! is built on `SysTick`, use a profiler on another clock like `ep-dwt`'s `DwtProfiler` (and
+//! vice versa, `ep-systick` can't be used alongside a `SysTick` monotonic).
+//!
+//! Only the time spent *executing* the task is measured. Time a software task spends waiting
+//! to be dispatched, or preempted by higher priority tasks, is not subtracted.
+
+/// Extracts a task's name from the [`type_name`](core::any::type_name) of an item nested
+/// in the task function, as produced by [`profile_task!`](crate::profile_task).
+#[doc(hidden)]
+#[must_use]
+pub fn task_name(nested_type_name: &'static str) -> &'static str {
+    nested_type_name
+        .rsplit("::")
+        .find(|segment| !segment.starts_with("__") && !segment.starts_with('{'))
+        .unwrap_or(nested_type_name)
+}
+
+/// Profiles the rest of the enclosing RTIC task, returning a [`ProfileGuard`](crate::ProfileGuard).
+///
+/// Without arguments, the snapshot is named after the enclosing function. A `&'static str`
+/// can be given to override the name.
+#[macro_export]
+macro_rules! profile_task {
+    () => {{
+        fn __profile_task() {}
+        fn __type_name_of<T>(_: T) -> &'static str {
+            core::any::type_name::<T>()
+        }
+        $crate::ProfileGuard::new($crate::rtic::task_name(__type_name_of(__profile_task)))
+    }};
+    ($name:expr) => {
+        $crate::ProfileGuard::new($name)
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::{mock_profiler, set_profiler};
+
+    #[test]
+    fn extracts_task_name() {
+        assert_eq!(task_name("app::app::blink::__profile_task"), "blink");
+        assert_eq!(
+            task_name("app::app::blink::{{closure}}::__profile_task"),
+            "blink"
+        );
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn profiles_task_body() {
+        fn simulated_task() {
+            let _guard = crate::profile_task!();
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+
+        set_profiler();
+
+        simulated_task();
+        assert!(mock_profiler().has_logged("simulated_task"));
+    }
+}
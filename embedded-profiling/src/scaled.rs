@@ -0,0 +1,142 @@
+//! Wrapper correcting durations by a constant factor.
+
+use crate::{EPContainer, EPDuration, EPInstant, EPSnapshot, EmbeddedProfiler, WideContainer};
+
+/// Wraps the [`EmbeddedProfiler`] `P`, multiplying every reported duration by the
+/// correction factor `num / denom`.
+///
+/// This fixes a systematic timing bias, like a clock running at a known factor off the
+/// frequency it was declared with, without changing the profiler or the instrumented
+/// code. Both the [`duration`](EPSnapshot::duration) and the [`active`](EPSnapshot::active)
+/// time are scaled, while [`raw`](EPSnapshot::raw) keeps the native ticks. Durations
+/// that would overflow [`EPContainer`] after scaling saturate.
+///
+/// ```
+/// # use embedded_profiling::*;
+/// # struct MyProfiler;
+/// # impl EmbeddedProfiler for MyProfiler { fn read_clock(&self) -> EPInstant { EPInstant::from_ticks(0) } }
+/// // the clock was measured to run 1% slower than declared
+/// let profiler = ScaledProfiler::new(MyProfiler, 101, 100);
+/// ```
+pub struct ScaledProfiler<P> {
+    inner: P,
+    num: u32,
+    denom: u32,
+}
+
+impl<P> ScaledProfiler<P> {
+    /// Creates a new [`ScaledProfiler`] around the profiler `inner`, scaling durations
+    /// by `num / denom`.
+    ///
+    /// # Panics
+    /// if `denom` is zero
+    #[must_use]
+    pub const fn new(inner: P, num: u32, denom: u32) -> Self {
+        assert!(
+            denom != 0,
+            "the correction factor's denominator can't be zero"
+        );
+        Self { inner, num, denom }
+    }
+
+    /// Consumes [`ScaledProfiler`], returning the wrapped profiler.
+    pub fn free(self) -> P {
+        self.inner
+    }
+
+    /// Multiplies `duration` by the correction factor, saturating on overflow.
+    fn scale(&self, duration: EPDuration) -> EPDuration {
+        // can't overflow, the container is at most half as wide as `WideContainer`
+        let scaled = WideContainer::from(duration.ticks()) * WideContainer::from(self.num)
+            / WideContainer::from(self.denom);
+        EPDuration::from_ticks(EPContainer::try_from(scaled).unwrap_or(EPContainer::MAX))
+    }
+
+    /// Scales the durations of `snapshot`.
+    fn correct(&self, snapshot: Option<EPSnapshot>) -> Option<EPSnapshot> {
+        snapshot.map(|mut snapshot| {
+            snapshot.duration = self.scale(snapshot.duration);
+            snapshot.active = snapshot.active.map(|active| self.scale(active));
+            snapshot
+        })
+    }
+}
+
+impl<P> EmbeddedProfiler for ScaledProfiler<P>
+where
+    P: EmbeddedProfiler,
+{
+    forward_profiler!(self.inner =>
+        read_clock,
+        clock_is_running,
+        overflow_events,
+        name,
+        backward_jitter,
+        synchronize,
+        has_real_clock,
+        read_clock_raw,
+        read_clock_ns,
+        log_snapshot,
+        hooks,
+        read_snapshot,
+    );
+
+    fn duration_from_raw(&self, raw: EPContainer) -> EPDuration {
+        self.scale(self.inner.duration_from_raw(raw))
+    }
+
+    fn end_snapshot_silent(&self, start: EPInstant, name: &'static str) -> Option<EPSnapshot> {
+        self.correct(self.inner.end_snapshot_silent(start, name))
+    }
+
+    fn end_snapshot_clamped(&self, start: EPInstant, name: &'static str) -> Option<EPSnapshot> {
+        self.correct(self.inner.end_snapshot_clamped(start, name))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::FakeClock;
+
+    #[test]
+    fn durations_scaled_by_factor() {
+        let profiler = ScaledProfiler::new(FakeClock::default(), 101, 100);
+
+        let start = profiler.start_snapshot("scaled");
+        profiler.inner.advance(10_000);
+        let snapshot = profiler.end_snapshot(start, "scaled").unwrap();
+        assert_eq!(snapshot.duration.ticks(), 10_100);
+
+        let start = profiler.start_snapshot("rounded down");
+        profiler.inner.advance(150);
+        let snapshot = profiler.end_snapshot(start, "rounded down").unwrap();
+        assert_eq!(snapshot.duration.ticks(), 151);
+    }
+
+    #[test]
+    fn active_scaled_through_wrapper() {
+        let (active, wall) = (FakeClock::default(), FakeClock::default());
+        let profiler = ScaledProfiler::new(
+            crate::SleepAwareProfiler::<_, _, 1>::new(&active, &wall),
+            2,
+            1,
+        );
+        active.advance(7);
+
+        let start = profiler.start_snapshot("scaled_sleep");
+        active.advance(10);
+        wall.advance(100);
+        let snapshot = profiler.end_snapshot(start, "scaled_sleep").unwrap();
+        assert_eq!(snapshot.duration.ticks(), 200);
+        assert_eq!(snapshot.active, Some(EPDuration::from_ticks(20)));
+    }
+
+    #[test]
+    fn saturates_on_overflow() {
+        let profiler = ScaledProfiler::new(FakeClock::default(), 2, 1);
+        let snapshot = EPSnapshot::new("huge", EPDuration::from_ticks(EPContainer::MAX));
+        let snapshot = profiler.correct(Some(snapshot)).unwrap();
+        assert_eq!(snapshot.duration.ticks(), EPContainer::MAX);
+    }
+}
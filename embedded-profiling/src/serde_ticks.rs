@@ -0,0 +1,172 @@
+//! `serde` support, serializing durations and instants as their ticks in microseconds.
+//!
+//! [`EPSnapshot`] implements [`Serialize`], also on `no_std` targets. [`Deserialize`] is
+//! only implemented with the `std` feature: the names of a snapshot are `&'static str`, so
+//! deserialized names are leaked, once for every distinct name and thread, which is fine on a host
+//! post-processing snapshots but not on a device.
+//!
+//! [`EPDuration`] is a type of `fugit`, so it can't implement the `serde` traits here. Use
+//! this module with `#[serde(with = "embedded_profiling::serde_ticks")]` for fields of your
+//! own types instead.
+//!
+//! ```
+//! # use embedded_profiling::*;
+//! #[derive(serde::Serialize, serde::Deserialize)]
+//! struct Budget {
+//!     #[serde(with = "embedded_profiling::serde_ticks")]
+//!     limit: EPDuration,
+//! }
+//! ```
+
+use crate::{EPContainer, EPDuration, EPInstant, EPSnapshot};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Serializes `duration` as its ticks in microseconds.
+///
+/// # Errors
+/// propagates any error of `serializer`
+pub fn serialize<S>(duration: &EPDuration, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    duration.ticks().serialize(serializer)
+}
+
+/// Deserializes an [`EPDuration`] from its ticks in microseconds.
+///
+/// # Errors
+/// propagates any error of `deserializer`
+pub fn deserialize<'de, D>(deserializer: D) -> Result<EPDuration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    EPContainer::deserialize(deserializer).map(EPDuration::from_ticks)
+}
+
+/// The serialized form of [`EPSnapshot`], with its durations and instants as ticks.
+#[derive(Serialize)]
+struct SnapshotRef<'a> {
+    name: &'a str,
+    duration: EPContainer,
+    raw: Option<EPContainer>,
+    target: Option<&'a str>,
+    panicked: bool,
+    location: Option<(&'a str, u32)>,
+    category: Option<&'a str>,
+    active: Option<EPContainer>,
+    start: Option<EPContainer>,
+    suspect: bool,
+    self_time: Option<EPContainer>,
+}
+
+impl Serialize for EPSnapshot {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        SnapshotRef {
+            name: self.name,
+            duration: self.duration.ticks(),
+            raw: self.raw,
+            target: self.target,
+            panicked: self.panicked,
+            location: self.location,
+            category: self.category,
+            active: self.active.map(|active| active.ticks()),
+            start: self.start.map(|start| start.ticks()),
+            suspect: self.suspect,
+            self_time: self.self_time.map(|self_time| self_time.ticks()),
+        }
+        .serialize(serializer)
+    }
+}
+
+/// The deserialized form of [`EPSnapshot`], owning its names.
+#[cfg(feature = "std")]
+#[derive(Deserialize)]
+struct SnapshotOwned {
+    name: String,
+    duration: EPContainer,
+    raw: Option<EPContainer>,
+    target: Option<String>,
+    panicked: bool,
+    location: Option<(String, u32)>,
+    category: Option<String>,
+    active: Option<EPContainer>,
+    start: Option<EPContainer>,
+    suspect: bool,
+    self_time: Option<EPContainer>,
+}
+
+#[cfg(feature = "std")]
+std::thread_local! {
+    /// Every name deserialized so far on this thread, each leaked once.
+    static NAMES: core::cell::RefCell<Vec<&'static str>> = core::cell::RefCell::new(Vec::new());
+}
+
+/// The `'static` copy of `name`, leaking it if it's the first time it's seen.
+#[cfg(feature = "std")]
+fn intern(name: String) -> &'static str {
+    NAMES.with(|names| {
+        let mut names = names.borrow_mut();
+        match names.iter().find(|interned| **interned == name) {
+            Some(interned) => interned,
+            None => {
+                let interned: &'static str = Box::leak(name.into_boxed_str());
+                names.push(interned);
+                interned
+            }
+        }
+    })
+}
+
+#[cfg(feature = "std")]
+impl<'de> Deserialize<'de> for EPSnapshot {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let owned = SnapshotOwned::deserialize(deserializer)?;
+        Ok(Self {
+            name: intern(owned.name),
+            duration: EPDuration::from_ticks(owned.duration),
+            raw: owned.raw,
+            target: owned.target.map(intern),
+            panicked: owned.panicked,
+            location: owned.location.map(|(file, line)| (intern(file), line)),
+            category: owned.category.map(intern),
+            active: owned.active.map(EPDuration::from_ticks),
+            start: owned.start.map(EPInstant::from_ticks),
+            suspect: owned.suspect,
+            self_time: owned.self_time.map(EPDuration::from_ticks),
+        })
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let snapshot = EPSnapshot::new("serialized", EPDuration::from_ticks(1_234))
+            .with_raw(148_080)
+            .with_location("src/main.rs", 12)
+            .with_start(EPInstant::from_ticks(500))
+            .with_self_time(EPDuration::from_ticks(34));
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let decoded: EPSnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.name, "serialized");
+        assert_eq!(decoded.duration, snapshot.duration);
+        assert_eq!(decoded.raw, Some(148_080));
+        assert_eq!(decoded.location, Some(("src/main.rs", 12)));
+        assert_eq!(decoded.start, snapshot.start);
+        assert_eq!(decoded.self_time, snapshot.self_time);
+        assert_eq!(decoded.target, None);
+
+        // equal names are only leaked once
+        let again: EPSnapshot = serde_json::from_str(&json).unwrap();
+        assert!(core::ptr::eq(decoded.name, again.name));
+    }
+}
@@ -0,0 +1,194 @@
+//! Profiler pairing a cycle clock with a wall clock, to profile across sleeps.
+
+use crate::{EPContainer, EPDuration, EPInstant, EPInstantNs, EPSnapshot, EmbeddedProfiler};
+use core::cell::{Cell, RefCell};
+
+/// Profiles with both the cycle clock `A` and the wall clock `W` (e.g. an RTC), so spans
+/// containing a `wfi` report both the time spent awake and the total time.
+///
+/// - [`duration`](EPSnapshot::duration) is the total time, measured by `W`. The wall clock
+///   keeps running during sleep, so it's authoritative for how long the span took.
+/// - [`active`](EPSnapshot::active) is the time spent awake, measured by `A`. A cycle clock
+///   like the `DWT` stops while the core clock is gated during `wfi`, so it only counts the
+///   time spent running. If `A` keeps counting during sleep (depending on the debug
+///   configuration), the active time includes the sleep. It is never reported longer than
+///   the total time, to hide the different resolutions of both clocks.
+///
+/// [`read_clock`](EmbeddedProfiler::read_clock) reads `W`. The hooks and
+/// [`log_snapshot`](EmbeddedProfiler::log_snapshot) are forwarded to `A`, as that is
+/// usually the full backend.
+///
+/// The active start of up to `N` nested snapshots is kept, deeper ones only get the total
+/// time. Snapshots have to be ended in the reverse order they were started, and this isn't
+/// safe to use reentrantly (from interrupts preempting each other).
+pub struct SleepAwareProfiler<A, W, const N: usize> {
+    active: A,
+    wall: W,
+    active_starts: RefCell<[EPInstant; N]>,
+    depth: Cell<usize>,
+}
+
+impl<A, W, const N: usize> SleepAwareProfiler<A, W, N> {
+    /// Creates a new [`SleepAwareProfiler`] from the cycle clock `active` and the wall clock `wall`.
+    #[must_use]
+    pub const fn new(active: A, wall: W) -> Self {
+        Self {
+            active,
+            wall,
+            active_starts: RefCell::new([EPInstant::from_ticks(0); N]),
+            depth: Cell::new(0),
+        }
+    }
+
+    /// Consumes [`SleepAwareProfiler`], returning the cycle clock and the wall clock.
+    pub fn free(self) -> (A, W) {
+        (self.active, self.wall)
+    }
+}
+
+impl<A, W, const N: usize> EmbeddedProfiler for SleepAwareProfiler<A, W, N>
+where
+    A: EmbeddedProfiler,
+    W: EmbeddedProfiler,
+{
+    fn read_clock(&self) -> EPInstant {
+        self.wall.read_clock()
+    }
+
+    fn log_snapshot(&self, snapshot: &EPSnapshot) {
+        self.active.log_snapshot(snapshot);
+    }
+
+    fn log_begin(&self, name: &'static str, at: EPInstant) {
+        self.active.log_begin(name, at);
+    }
+
+    fn log_end(&self, name: &'static str, at: EPInstant) {
+        self.active.log_end(name, at);
+    }
+
+    fn at_start(&self, name: &'static str) {
+        self.active.at_start(name);
+    }
+
+    fn at_end(&self, name: &'static str) {
+        self.active.at_end(name);
+    }
+
+    fn overflow_events(&self) -> Option<u32> {
+        self.active.overflow_events()
+    }
+
+    fn name(&self) -> &'static str {
+        self.active.name()
+    }
+
+    fn backward_jitter(&self) -> EPDuration {
+        self.wall.backward_jitter()
+    }
+
+    fn synchronize(&self, rtc_now: EPInstant) {
+        self.wall.synchronize(rtc_now);
+    }
+
+    fn has_real_clock(&self) -> bool {
+        self.wall.has_real_clock()
+    }
+
+    fn read_clock_raw(&self) -> EPContainer {
+        self.wall.read_clock_raw()
+    }
+
+    fn duration_from_raw(&self, raw: EPContainer) -> EPDuration {
+        self.wall.duration_from_raw(raw)
+    }
+
+    fn read_clock_ns(&self) -> EPInstantNs {
+        self.wall.read_clock_ns()
+    }
+
+    fn read_snapshot(&self) -> EPInstant {
+        let depth = self.depth.get();
+        if let Some(active_start) = self.active_starts.borrow_mut().get_mut(depth) {
+            *active_start = self.active.read_clock();
+        }
+        self.depth.set(depth + 1);
+        self.read_clock()
+    }
+
+    fn end_snapshot_silent(&self, start: EPInstant, name: &'static str) -> Option<EPSnapshot> {
+        let now = self.read_clock();
+        let active_now = self.active.read_clock();
+
+        let depth = self.depth.get().saturating_sub(1);
+        self.depth.set(depth);
+        let active_start = self.active_starts.borrow().get(depth).copied();
+
+        let duration = now.checked_duration_since(start)?;
+        let snapshot = EPSnapshot::new(name, duration).with_start(start);
+        match active_start.and_then(|active_start| active_now.checked_duration_since(active_start))
+        {
+            Some(active) => Some(snapshot.with_active(active.min(duration))),
+            None => Some(snapshot),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::FakeClock;
+
+    #[test]
+    fn active_and_total_across_sleep() {
+        let profiler =
+            SleepAwareProfiler::<_, _, 2>::new(FakeClock::default(), FakeClock::default());
+        let (active, wall) = (&profiler.active, &profiler.wall);
+
+        let outer = profiler.start_snapshot("work");
+        active.advance(10);
+        wall.advance(10);
+
+        let inner = profiler.start_snapshot("sleep");
+        // simulated `wfi`: the core clock is gated, the RTC keeps going
+        wall.advance(1_000);
+        let inner = profiler.end_snapshot(inner, "sleep").unwrap();
+        assert_eq!(inner.duration.ticks(), 1_000);
+        assert_eq!(inner.active, Some(EPDuration::from_ticks(0)));
+
+        active.advance(5);
+        wall.advance(5);
+        let outer = profiler.end_snapshot(outer, "work").unwrap();
+        assert_eq!(outer.duration.ticks(), 1_015);
+        assert_eq!(outer.active, Some(EPDuration::from_ticks(15)));
+    }
+
+    #[test]
+    fn too_deep_gets_total_only() {
+        let profiler =
+            SleepAwareProfiler::<_, _, 0>::new(FakeClock::default(), FakeClock::default());
+
+        let start = profiler.start_snapshot("deep");
+        profiler.wall.advance(3);
+        let snapshot = profiler.end_snapshot(start, "deep").unwrap();
+        assert_eq!(snapshot.duration.ticks(), 3);
+        assert_eq!(snapshot.active, None);
+    }
+
+    #[test]
+    fn active_kept_through_wrappers() {
+        let (active, wall) = (FakeClock::default(), FakeClock::default());
+        let profiler = crate::AggregatingProfiler::<_, 1>::new(SleepAwareProfiler::<_, _, 1>::new(
+            &active, &wall,
+        ));
+        // the active clock already counted before the span
+        active.advance(7);
+
+        let start = profiler.start_snapshot("wrapped_sleep");
+        active.advance(10);
+        wall.advance(100);
+        let snapshot = profiler.end_snapshot(start, "wrapped_sleep").unwrap();
+        assert_eq!(snapshot.duration.ticks(), 100);
+        assert_eq!(snapshot.active, Some(EPDuration::from_ticks(10)));
+    }
+}
@@ -0,0 +1,246 @@
+//! Per trace statistics, shared by the profilers that summarize snapshots.
+
+use crate::{EPDuration, EPInstant, EPSnapshot};
+use core::fmt;
+
+/// Running statistics of all snapshots of a single trace.
+#[derive(Clone, Copy, Debug)]
+pub struct Stats {
+    /// The name of the trace.
+    pub name: &'static str,
+    /// The number of snapshots recorded.
+    pub count: u32,
+    /// The sum of all recorded durations, saturating at the maximum.
+    pub total: EPDuration,
+    /// The shortest recorded duration.
+    pub min: EPDuration,
+    /// The longest recorded duration.
+    pub max: EPDuration,
+    /// When the snapshot with the longest duration started, to correlate the worst case with
+    /// other events. `None` if that snapshot didn't know its [`start`](EPSnapshot::start).
+    pub max_at: Option<EPInstant>,
+}
+
+impl Stats {
+    /// Starts the statistics of a trace with its first snapshot.
+    fn new(snapshot: &EPSnapshot) -> Self {
+        Self {
+            name: snapshot.name,
+            count: 1,
+            total: snapshot.duration,
+            min: snapshot.duration,
+            max: snapshot.duration,
+            max_at: snapshot.start,
+        }
+    }
+
+    /// Adds `snapshot` to the statistics.
+    fn record(&mut self, snapshot: &EPSnapshot) {
+        let duration = snapshot.duration;
+        self.count = self.count.saturating_add(1);
+        self.total = EPDuration::from_ticks(self.total.ticks().saturating_add(duration.ticks()));
+        self.min = self.min.min(duration);
+        if duration > self.max {
+            self.max = duration;
+            self.max_at = snapshot.start;
+        }
+    }
+
+    /// The mean duration of the recorded snapshots.
+    #[must_use]
+    pub fn mean(&self) -> EPDuration {
+        self.total / self.count.max(1)
+    }
+
+    /// The header line matching the columns of [`Stats::csv`].
+    pub const CSV_HEADER: &'static str = "name,count,min_us,avg_us,max_us";
+
+    /// Formats these statistics as a line of comma separated values, with the columns of
+    /// [`Stats::CSV_HEADER`].
+    ///
+    /// ```
+    /// # use embedded_profiling::*;
+    /// # let profiler = AggregatingProfiler::<_, 1>::new(embedded_profiling::profiler());
+    /// # profiler.log_snapshot(&EPSnapshot::new("poll", EPDuration::from_ticks(3)));
+    /// let stats = profiler.stats("poll").unwrap();
+    /// assert_eq!(stats.csv().to_string(), "poll,1,3,3,3");
+    /// ```
+    #[must_use]
+    pub const fn csv(&self) -> StatsCsv<'_> {
+        StatsCsv(self)
+    }
+}
+
+/// Formats as `name: count=4 min=12us avg=15us max=20us`, with the durations in integer
+/// microseconds so it can be parsed back easily.
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: count={} min={}us avg={}us max={}us",
+            self.name,
+            self.count,
+            self.min.ticks(),
+            self.mean().ticks(),
+            self.max.ticks()
+        )
+    }
+}
+
+/// Formats [`Stats`] as a line of comma separated values, see [`Stats::csv`].
+pub struct StatsCsv<'a>(&'a Stats);
+
+impl fmt::Display for StatsCsv<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stats = self.0;
+        write!(
+            f,
+            "{},{},{},{},{}",
+            stats.name,
+            stats.count,
+            stats.min.ticks(),
+            stats.mean().ticks(),
+            stats.max.ticks()
+        )
+    }
+}
+
+/// 32 bit FNV-1a hash of `name`'s content.
+fn name_hash(name: &str) -> u32 {
+    name.bytes().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// A fixed capacity table of [`Stats`], one entry per trace name.
+///
+/// Names are compared by content, so equal names from different crates (which the compiler
+/// doesn't merge into one string) share an entry. Each entry keeps a hash of its name,
+/// which costs one pass over the name's bytes per recorded snapshot. The full content is
+/// only compared when the hashes match, which keeps lookups close to the cost of comparing
+/// pointers.
+#[derive(Clone, Copy)]
+pub(crate) struct StatsTable<const N: usize> {
+    entries: [Option<Stats>; N],
+    hashes: [u32; N],
+}
+
+impl<const N: usize> StatsTable<N> {
+    pub(crate) const fn new() -> Self {
+        Self {
+            entries: [None; N],
+            hashes: [0; N],
+        }
+    }
+
+    /// Records `snapshot` in the entry of its name, creating the entry if needed.
+    ///
+    /// Returns `false` if the table is full and `snapshot` could not be recorded.
+    pub(crate) fn record(&mut self, snapshot: &EPSnapshot) -> bool {
+        let hash = name_hash(snapshot.name);
+        for (entry, entry_hash) in self.entries.iter_mut().zip(&mut self.hashes) {
+            match entry {
+                Some(stats) if *entry_hash == hash && stats.name == snapshot.name => {
+                    stats.record(snapshot);
+                    return true;
+                }
+                Some(_) => {}
+                None => {
+                    *entry = Some(Stats::new(snapshot));
+                    *entry_hash = hash;
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// The statistics of the trace `name`, if it has been recorded.
+    pub(crate) fn get(&self, name: &str) -> Option<&Stats> {
+        let hash = name_hash(name);
+        self.entries
+            .iter()
+            .zip(&self.hashes)
+            .map_while(|(entry, entry_hash)| entry.as_ref().map(|stats| (stats, *entry_hash)))
+            .find(|(stats, entry_hash)| *entry_hash == hash && stats.name == name)
+            .map(|(stats, _)| stats)
+    }
+
+    /// Iterates over the statistics of every recorded trace.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Stats> {
+        self.entries.iter().map_while(Option::as_ref)
+    }
+
+    /// Iterates over copies of the statistics of every recorded trace, consuming the table.
+    #[cfg(feature = "critical-section")]
+    pub(crate) fn into_stats(self) -> impl Iterator<Item = Stats> {
+        self.entries.into_iter().map_while(|entry| entry)
+    }
+
+    /// Forgets every recorded trace.
+    pub(crate) fn clear(&mut self) {
+        self.entries = [None; N];
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn equal_names_share_an_entry() {
+        let first: &'static str = Box::leak(String::from("poll").into_boxed_str());
+        let second: &'static str = Box::leak(String::from("poll").into_boxed_str());
+        assert!(!core::ptr::eq(first, second));
+
+        let mut table = StatsTable::<2>::new();
+        assert!(table.record(&EPSnapshot::new(first, EPDuration::from_ticks(1))));
+        assert!(table.record(&EPSnapshot::new(second, EPDuration::from_ticks(3))));
+        assert!(table.record(&EPSnapshot::new("other", EPDuration::from_ticks(5))));
+
+        let mut entries = table.iter();
+        let poll = entries.next().unwrap();
+        assert_eq!(poll.count, 2);
+        assert_eq!(poll.total.ticks(), 4);
+        assert_eq!(entries.next().unwrap().name, "other");
+        assert!(entries.next().is_none());
+    }
+
+    #[test]
+    fn max_at_follows_new_maximum() {
+        let snapshot = |duration, start| {
+            EPSnapshot::new("poll", EPDuration::from_ticks(duration))
+                .with_start(EPInstant::from_ticks(start))
+        };
+
+        let mut table = StatsTable::<1>::new();
+        assert!(table.record(&snapshot(5, 100)));
+        assert!(table.record(&snapshot(3, 200)));
+        assert_eq!(
+            table.get("poll").unwrap().max_at,
+            Some(EPInstant::from_ticks(100))
+        );
+
+        assert!(table.record(&snapshot(9, 300)));
+        assert!(table.record(&snapshot(9, 400)));
+        let stats = table.get("poll").unwrap();
+        assert_eq!(stats.max.ticks(), 9);
+        assert_eq!(stats.max_at, Some(EPInstant::from_ticks(300)));
+    }
+
+    #[test]
+    fn formatted() {
+        let mut table = StatsTable::<1>::new();
+        for duration in [12, 13, 20] {
+            assert!(table.record(&EPSnapshot::new("poll", EPDuration::from_ticks(duration))));
+        }
+        let stats = table.get("poll").unwrap();
+
+        assert_eq!(
+            stats.to_string(),
+            "poll: count=3 min=12us avg=15us max=20us"
+        );
+        assert_eq!(Stats::CSV_HEADER, "name,count,min_us,avg_us,max_us");
+        assert_eq!(stats.csv().to_string(), "poll,3,12,15,20");
+    }
+}
@@ -0,0 +1,117 @@
+//! Wrapper accumulating statistics of every trace, safe to use from interrupts.
+
+use crate::stats::{Stats, StatsTable};
+use crate::{EPSnapshot, EmbeddedProfiler};
+use core::cell::RefCell;
+use critical_section::Mutex;
+
+/// Wraps the [`EmbeddedProfiler`] `P`, accumulating [`Stats`] of every logged snapshot
+/// instead of logging them, like [`AggregatingProfiler`](crate::AggregatingProfiler) but
+/// safe to log to from interrupts.
+///
+/// Statistics are kept for up to `N` distinct names. Snapshots of any further names are
+/// logged individually by `P` as usual. The table is only accessed inside a
+/// [`critical-section`](https://docs.rs/critical-section) critical section, so snapshots can
+/// be logged from interrupts that preempt reading or resetting the statistics.
+///
+/// Only available with the `critical-section` feature.
+///
+/// ```ignore
+/// # use embedded_profiling::*;
+/// # struct MyClock;
+/// # impl EmbeddedProfiler for MyClock { fn read_clock(&self) -> EPInstant {EPInstant::from_ticks(0)} }
+/// static PROFILER: StatsProfiler<MyClock, 8> = StatsProfiler::new(MyClock);
+///
+/// // in the soak test's loop, or an interrupt handler
+/// PROFILER.profile("soak", || {});
+///
+/// // on demand, e.g. from the idle loop
+/// for stats in PROFILER.stats() {
+///     println!("{}", stats);
+/// }
+/// PROFILER.reset_stats();
+/// ```
+pub struct StatsProfiler<P, const N: usize> {
+    inner: P,
+    table: Mutex<RefCell<StatsTable<N>>>,
+}
+
+impl<P, const N: usize> StatsProfiler<P, N> {
+    /// Creates a new [`StatsProfiler`] around the profiler `inner`.
+    #[must_use]
+    pub const fn new(inner: P) -> Self {
+        Self {
+            inner,
+            table: Mutex::new(RefCell::new(StatsTable::new())),
+        }
+    }
+
+    /// Consumes [`StatsProfiler`], returning the wrapped profiler.
+    pub fn free(self) -> P {
+        self.inner
+    }
+
+    /// The statistics of every recorded trace, in the order they were first seen.
+    ///
+    /// The table is copied out in one critical section, so the statistics are consistent
+    /// with each other and interrupts aren't held off while iterating.
+    pub fn stats(&self) -> impl Iterator<Item = Stats> {
+        critical_section::with(|cs| *self.table.borrow(cs).borrow()).into_stats()
+    }
+
+    /// Forgets the statistics of every trace.
+    pub fn reset_stats(&self) {
+        critical_section::with(|cs| self.table.borrow(cs).borrow_mut().clear());
+    }
+}
+
+impl<P, const N: usize> EmbeddedProfiler for StatsProfiler<P, N>
+where
+    P: EmbeddedProfiler,
+{
+    fn log_snapshot(&self, snapshot: &EPSnapshot) {
+        if !critical_section::with(|cs| self.table.borrow(cs).borrow_mut().record(snapshot)) {
+            self.inner.log_snapshot(snapshot);
+        }
+    }
+
+    forward_profiler!(self.inner => clock, hooks, snapshots);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::{FakeClock, StdMockProfiler};
+    use crate::EPDuration;
+
+    #[test]
+    fn soak_then_reset() {
+        let profiler = StatsProfiler::<_, 4>::new(FakeClock::default());
+        for _ in 0..1_000 {
+            let start = profiler.start_snapshot("soak");
+            profiler.inner.advance(3);
+            let snapshot = profiler.end_snapshot(start, "soak").unwrap();
+            profiler.log_snapshot(&snapshot);
+        }
+
+        let stats: Vec<_> = profiler.stats().collect();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].name, "soak");
+        assert_eq!(stats[0].count, 1_000);
+        assert_eq!(stats[0].total.ticks(), 3_000);
+        assert_eq!((stats[0].min.ticks(), stats[0].max.ticks()), (3, 3));
+
+        profiler.reset_stats();
+        assert_eq!(profiler.stats().count(), 0);
+    }
+
+    #[test]
+    fn full_table_logs_through() {
+        let profiler = StatsProfiler::<_, 1>::new(StdMockProfiler::default());
+        profiler.log_snapshot(&EPSnapshot::new("poll", EPDuration::from_ticks(1)));
+        profiler.log_snapshot(&EPSnapshot::new("isr", EPDuration::from_ticks(2)));
+
+        assert!(!profiler.inner.has_logged("poll"));
+        assert!(profiler.inner.has_logged("isr"));
+    }
+}
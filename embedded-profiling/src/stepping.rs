@@ -0,0 +1,51 @@
+//! Deterministic clock for examples and host tests.
+
+use crate::{EPContainer, EPInstant, EmbeddedProfiler};
+use core::cell::Cell;
+
+/// An [`EmbeddedProfiler`] whose clock starts at zero and advances by a fixed `step` every
+/// time it's read, so every snapshot measures exactly `step` microseconds.
+///
+/// Meant for documentation examples and host tests, which can show real durations this way
+/// without sleeping. Snapshots are logged to stdout.
+///
+/// ```
+/// # use embedded_profiling::*;
+/// let profiler = SteppingProfiler::new(5);
+/// let start = profiler.start_snapshot("stepped");
+/// // (...)
+/// let snapshot = profiler.end_snapshot(start, "stepped").unwrap();
+/// assert_eq!(snapshot.duration, EPDuration::from_ticks(5));
+/// assert_eq!(snapshot.to_string(), "<EPSS stepped: 5 us>");
+/// ```
+pub struct SteppingProfiler {
+    now: Cell<EPContainer>,
+    step: EPContainer,
+}
+
+impl SteppingProfiler {
+    /// Creates a new [`SteppingProfiler`] advancing by `step` microseconds every read.
+    #[must_use]
+    pub const fn new(step: EPContainer) -> Self {
+        Self {
+            now: Cell::new(0),
+            step,
+        }
+    }
+}
+
+impl EmbeddedProfiler for SteppingProfiler {
+    fn read_clock(&self) -> EPInstant {
+        let now = self.now.get();
+        self.now.set(now.wrapping_add(self.step));
+        EPInstant::from_ticks(now)
+    }
+
+    fn log_snapshot(&self, snapshot: &crate::EPSnapshot) {
+        println!("{}", snapshot);
+    }
+
+    fn name(&self) -> &'static str {
+        "stepping"
+    }
+}
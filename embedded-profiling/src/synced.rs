@@ -0,0 +1,136 @@
+//! Wrapper aligning snapshot starts with an external real time clock.
+
+use crate::{EPContainer, EPInstant, EPSnapshot, EmbeddedProfiler};
+use core::cell::Cell;
+
+/// Wraps the [`EmbeddedProfiler`] `P`, reporting the [`start`](EPSnapshot::start) of every
+/// snapshot in the timebase of a real time clock once [synchronized](EmbeddedProfiler::synchronize).
+///
+/// Synchronizing records the offset between `P`'s clock and the RTC, which is then added to
+/// every start. Durations are still measured with `P` alone. Until the first
+/// synchronization, starts are reported in `P`'s own timebase.
+///
+/// The alignment is only as precise as the synchronization: `P`'s clock drifts relative to
+/// the RTC (crystals are typically off by tens of ppm), and the RTC's own resolution and the
+/// time between reading it and calling [`synchronize`](EmbeddedProfiler::synchronize) add
+/// to the error. Synchronize periodically to keep the drift bounded.
+///
+/// ```
+/// # use embedded_profiling::*;
+/// # struct MyProfiler;
+/// # impl EmbeddedProfiler for MyProfiler { fn read_clock(&self) -> EPInstant { EPInstant::from_ticks(0) } }
+/// let profiler = RtcSyncedProfiler::new(MyProfiler);
+/// // e.g. the microseconds since midnight, read from the RTC
+/// profiler.synchronize(EPInstant::from_ticks(1_000_000));
+/// let snapshot = profiler.end_snapshot(profiler.start_snapshot("synced"), "synced").unwrap();
+/// assert_eq!(snapshot.start, Some(EPInstant::from_ticks(1_000_000)));
+/// ```
+pub struct RtcSyncedProfiler<P> {
+    inner: P,
+    /// Ticks to add to `inner`'s instants to get RTC instants, wrapping.
+    offset: Cell<Option<EPContainer>>,
+}
+
+impl<P> RtcSyncedProfiler<P> {
+    /// Creates a new [`RtcSyncedProfiler`] around the profiler `inner`, not synchronized yet.
+    #[must_use]
+    pub const fn new(inner: P) -> Self {
+        Self {
+            inner,
+            offset: Cell::new(None),
+        }
+    }
+
+    /// Consumes [`RtcSyncedProfiler`], returning the wrapped profiler.
+    pub fn free(self) -> P {
+        self.inner
+    }
+
+    /// Reports the start of `snapshot` in the RTC's timebase, if synchronized.
+    fn align(&self, snapshot: Option<EPSnapshot>) -> Option<EPSnapshot> {
+        match (snapshot, self.offset.get()) {
+            (Some(snapshot), Some(offset)) => match snapshot.start {
+                Some(start) => Some(
+                    snapshot.with_start(EPInstant::from_ticks(start.ticks().wrapping_add(offset))),
+                ),
+                None => Some(snapshot),
+            },
+            (snapshot, _) => snapshot,
+        }
+    }
+}
+
+impl<P> EmbeddedProfiler for RtcSyncedProfiler<P>
+where
+    P: EmbeddedProfiler,
+{
+    forward_profiler!(self.inner =>
+        read_clock,
+        clock_is_running,
+        overflow_events,
+        name,
+        backward_jitter,
+        has_real_clock,
+        read_clock_raw,
+        duration_from_raw,
+        read_clock_ns,
+        log_snapshot,
+        hooks,
+        read_snapshot,
+    );
+
+    fn synchronize(&self, rtc_now: EPInstant) {
+        let now = self.inner.read_clock();
+        self.offset
+            .set(Some(rtc_now.ticks().wrapping_sub(now.ticks())));
+    }
+
+    fn end_snapshot_silent(&self, start: EPInstant, name: &'static str) -> Option<EPSnapshot> {
+        self.align(self.inner.end_snapshot_silent(start, name))
+    }
+
+    fn end_snapshot_clamped(&self, start: EPInstant, name: &'static str) -> Option<EPSnapshot> {
+        self.align(self.inner.end_snapshot_clamped(start, name))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::FakeClock;
+
+    #[test]
+    fn starts_offset_by_sync_point() {
+        let profiler = RtcSyncedProfiler::new(FakeClock::default());
+        profiler.inner.set(100);
+
+        // not synchronized yet
+        let start = profiler.start_snapshot("unsynced");
+        profiler.inner.advance(5);
+        let snapshot = profiler.end_snapshot(start, "unsynced").unwrap();
+        assert_eq!(snapshot.start, Some(EPInstant::from_ticks(100)));
+
+        // the RTC reads 50_000 while the profiler's clock reads 105
+        profiler.synchronize(EPInstant::from_ticks(50_000));
+        profiler.inner.advance(10);
+        let start = profiler.start_snapshot("synced");
+        profiler.inner.advance(7);
+        let snapshot = profiler.end_snapshot(start, "synced").unwrap();
+        assert_eq!(snapshot.start, Some(EPInstant::from_ticks(50_010)));
+        assert_eq!(snapshot.duration.ticks(), 7);
+    }
+
+    #[test]
+    fn active_kept_through_sync() {
+        let (active, wall) = (FakeClock::default(), FakeClock::default());
+        let profiler =
+            RtcSyncedProfiler::new(crate::SleepAwareProfiler::<_, _, 1>::new(&active, &wall));
+        active.advance(7);
+
+        let start = profiler.start_snapshot("synced_sleep");
+        active.advance(10);
+        wall.advance(100);
+        let snapshot = profiler.end_snapshot(start, "synced_sleep").unwrap();
+        assert_eq!(snapshot.active, Some(crate::EPDuration::from_ticks(10)));
+    }
+}
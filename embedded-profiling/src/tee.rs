@@ -0,0 +1,114 @@
+//! Fanning out to two profilers, like a pin toggle and a cycle counter.
+
+use crate::EmbeddedProfiler;
+
+/// Fans out to the two profilers `A` and `B`, for example toggling a pin with
+/// `ep-pin-toggle` for a scope while logging durations measured by `ep-dwt`.
+///
+/// This is the tuple `(A, B)` under a name, and forwards everything to it: `A` is the clock,
+/// and the hooks and logging methods are called on `A`, then `B`, so `A`'s pin toggles first
+/// at both ends of a snapshot. Both can be references, as `&P` is an [`EmbeddedProfiler`] too.
+///
+/// ```
+/// # use embedded_profiling::*;
+/// # struct Dwt;
+/// # impl EmbeddedProfiler for Dwt { fn read_clock(&self) -> EPInstant {EPInstant::from_ticks(0)} }
+/// # struct PinToggle;
+/// # impl EmbeddedProfiler for PinToggle { fn read_clock(&self) -> EPInstant {EPInstant::from_ticks(0)} }
+/// let dwt = Dwt;
+/// let pin = PinToggle;
+/// let profiler = TeeProfiler::new(&dwt, &pin);
+/// profiler.profile("measured twice", || {});
+/// ```
+pub struct TeeProfiler<A, B> {
+    inner: (A, B),
+}
+
+impl<A, B> TeeProfiler<A, B> {
+    /// Creates a new [`TeeProfiler`] reading the clock of `a` and fanning out to `a`, then `b`.
+    #[must_use]
+    pub const fn new(a: A, b: B) -> Self {
+        Self { inner: (a, b) }
+    }
+
+    /// Consumes [`TeeProfiler`], returning the wrapped profilers.
+    pub fn free(self) -> (A, B) {
+        self.inner
+    }
+}
+
+impl<A, B> EmbeddedProfiler for TeeProfiler<A, B>
+where
+    A: EmbeddedProfiler,
+    B: EmbeddedProfiler,
+{
+    forward_profiler!(self.inner => all);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::FakeClock;
+    use crate::{EPInstant, EPSnapshot};
+    use core::cell::RefCell;
+
+    /// Records the hooks it gets, tagged with `tag`, in a log shared with the other backend.
+    struct Recording<'a> {
+        tag: char,
+        calls: &'a RefCell<Vec<(char, &'static str)>>,
+    }
+
+    impl EmbeddedProfiler for Recording<'_> {
+        fn read_clock(&self) -> EPInstant {
+            unreachable!("only the first profiler is read")
+        }
+
+        fn log_snapshot(&self, _snapshot: &EPSnapshot) {
+            self.calls.borrow_mut().push((self.tag, "log"));
+        }
+
+        fn at_start(&self, _name: &'static str) {
+            self.calls.borrow_mut().push((self.tag, "start"));
+        }
+
+        fn at_end(&self, _name: &'static str) {
+            self.calls.borrow_mut().push((self.tag, "end"));
+        }
+    }
+
+    #[test]
+    fn a_before_b() {
+        let calls = RefCell::new(Vec::new());
+        let clock = FakeClock::default();
+        let a = (
+            &clock,
+            Recording {
+                tag: 'a',
+                calls: &calls,
+            },
+        );
+        let b = Recording {
+            tag: 'b',
+            calls: &calls,
+        };
+        let profiler = TeeProfiler::new(&a, &b);
+
+        let start = profiler.start_snapshot("tee");
+        clock.advance(25);
+        let snapshot = profiler.end_snapshot(start, "tee").unwrap();
+        profiler.log_snapshot(&snapshot);
+
+        assert_eq!(snapshot.duration.ticks(), 25);
+        assert_eq!(
+            *calls.borrow(),
+            [
+                ('a', "start"),
+                ('b', "start"),
+                ('a', "end"),
+                ('b', "end"),
+                ('a', "log"),
+                ('b', "log"),
+            ]
+        );
+    }
+}
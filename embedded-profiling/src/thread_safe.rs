@@ -0,0 +1,93 @@
+//! Wrapper making any profiler safe to share between threads on `std`.
+
+use crate::EmbeddedProfiler;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Wraps the [`EmbeddedProfiler`] `P` in a [`Mutex`], locking it for every call.
+///
+/// This makes profilers with interior mutability that isn't thread safe (like a capturing
+/// profiler built on a `RefCell`) usable from multiple threads, e.g. when profiling
+/// concurrent code in host tests. A panic in `P` doesn't poison it for the other threads.
+///
+/// Every call contends for the same lock, which serializes the threads being profiled and
+/// adds the wait to the measured durations. Don't use this on a device's hot path.
+pub struct ThreadSafe<P>(Mutex<P>);
+
+impl<P> ThreadSafe<P> {
+    /// Creates a new [`ThreadSafe`] around the profiler `inner`.
+    #[must_use]
+    pub fn new(inner: P) -> Self {
+        Self(Mutex::new(inner))
+    }
+
+    /// Consumes [`ThreadSafe`], returning the wrapped profiler.
+    pub fn free(self) -> P {
+        self.0.into_inner().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, P> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<P> EmbeddedProfiler for ThreadSafe<P>
+where
+    P: EmbeddedProfiler,
+{
+    forward_profiler!(self.lock() => all);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{EPDuration, EPInstant, EPSnapshot};
+    use core::cell::RefCell;
+    use std::sync::Arc;
+
+    /// Captures the names of logged snapshots, but isn't `Sync` on its own.
+    #[derive(Default)]
+    struct CapturingProfiler {
+        names: RefCell<Vec<&'static str>>,
+    }
+
+    impl EmbeddedProfiler for CapturingProfiler {
+        fn read_clock(&self) -> EPInstant {
+            EPInstant::from_ticks(0)
+        }
+
+        fn log_snapshot(&self, snapshot: &EPSnapshot) {
+            self.names.borrow_mut().push(snapshot.name);
+        }
+    }
+
+    #[test]
+    fn profile_from_two_threads() {
+        let profiler = Arc::new(ThreadSafe::new(CapturingProfiler::default()));
+
+        let threads: Vec<_> = ["first thread", "second thread"]
+            .into_iter()
+            .map(|name| {
+                let profiler = Arc::clone(&profiler);
+                std::thread::spawn(move || {
+                    for _ in 0..100 {
+                        let start = profiler.start_snapshot(name);
+                        let snapshot = profiler.end_snapshot(start, name).unwrap();
+                        assert_eq!(snapshot.duration, EPDuration::from_ticks(0));
+                        profiler.log_snapshot(&snapshot);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let profiler = Arc::try_unwrap(profiler).ok().unwrap();
+        let names = profiler.free().names.into_inner();
+        assert_eq!(names.len(), 200);
+        assert_eq!(
+            names.iter().filter(|&&name| name == "first thread").count(),
+            100
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! Fanning out to several profilers at once through tuples.
+
+use crate::{EPInstant, EPSnapshot, EmbeddedProfiler};
+
+/// Implements [`EmbeddedProfiler`] for a tuple, with the first element as the clock and
+/// the hooks and logging broadcast to every element.
+macro_rules! impl_tuple {
+    ($first:ident $(, $rest:ident)+) => {
+        /// Fans out to every profiler of the tuple, so a few backends can be combined, like a
+        /// pin toggle, a cycle counter and a serial sink.
+        ///
+        /// The first element is the clock: it alone is read, measures the snapshots, and
+        /// provides the clock's properties, like [`name`](EmbeddedProfiler::name) and
+        /// [`overflow_events`](EmbeddedProfiler::overflow_events).
+        /// [`at_start`](EmbeddedProfiler::at_start), [`at_end`](EmbeddedProfiler::at_end) and
+        /// the logging methods are called on every element, in order.
+        #[allow(non_snake_case)]
+        impl<$first, $($rest),+> EmbeddedProfiler for ($first, $($rest),+)
+        where
+            $first: EmbeddedProfiler,
+            $($rest: EmbeddedProfiler),+
+        {
+            forward_profiler!(self.0 => clock, read_snapshot, end_snapshot_silent);
+
+            fn log_snapshot(&self, snapshot: &EPSnapshot) {
+                let ($first, $($rest),+) = self;
+                $first.log_snapshot(snapshot);
+                $($rest.log_snapshot(snapshot);)+
+            }
+
+            fn log_begin(&self, name: &'static str, at: EPInstant) {
+                let ($first, $($rest),+) = self;
+                $first.log_begin(name, at);
+                $($rest.log_begin(name, at);)+
+            }
+
+            fn log_end(&self, name: &'static str, at: EPInstant) {
+                let ($first, $($rest),+) = self;
+                $first.log_end(name, at);
+                $($rest.log_end(name, at);)+
+            }
+
+            fn at_start(&self, name: &'static str) {
+                let ($first, $($rest),+) = self;
+                $first.at_start(name);
+                $($rest.at_start(name);)+
+            }
+
+            fn at_end(&self, name: &'static str) {
+                let ($first, $($rest),+) = self;
+                $first.at_end(name);
+                $($rest.at_end(name);)+
+            }
+        }
+    };
+}
+
+impl_tuple!(A, B);
+impl_tuple!(A, B, C);
+impl_tuple!(A, B, C, D);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::cell::Cell;
+
+    /// A fixed clock counting the calls it gets.
+    struct Recording {
+        now: crate::EPContainer,
+        started: Cell<u32>,
+        logged: Cell<u32>,
+    }
+
+    impl Recording {
+        const fn new(now: crate::EPContainer) -> Self {
+            Self {
+                now,
+                started: Cell::new(0),
+                logged: Cell::new(0),
+            }
+        }
+    }
+
+    impl EmbeddedProfiler for Recording {
+        fn read_clock(&self) -> EPInstant {
+            EPInstant::from_ticks(self.now)
+        }
+
+        fn log_snapshot(&self, _snapshot: &EPSnapshot) {
+            self.logged.set(self.logged.get() + 1);
+        }
+
+        fn at_start(&self, _name: &'static str) {
+            self.started.set(self.started.get() + 1);
+        }
+    }
+
+    #[test]
+    fn first_is_the_clock() {
+        let profiler = (Recording::new(5), Recording::new(100), Recording::new(200));
+        assert_eq!(profiler.read_clock(), EPInstant::from_ticks(5));
+
+        profiler.profile("fan_out", || {});
+
+        let (first, second, third) = profiler;
+        for element in [&first, &second, &third] {
+            assert_eq!(element.started.get(), 1);
+            assert_eq!(element.logged.get(), 1);
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! Profiler that writes snapshots straight to a [`core::fmt::Write`]r.
+
+use crate::{EPSnapshot, EmbeddedProfiler};
+use core::marker::PhantomData;
+
+/// Runs the given closure on the borrowed writer, see [`WriterProfiler`].
+pub type BorrowWriter<W> = fn(&mut dyn FnMut(&mut W));
+
+/// Wraps the clock `C`, writing snapshots to a `W` with [`write_snapshot`](crate::write_snapshot).
+///
+/// This is a synchronous output path that doesn't need `log`. The writer usually lives in
+/// a `static` that must be locked before use (a USB serial port, a UART), so rather than
+/// owning it, the profiler calls `borrow_writer` with a closure to run on the writer while
+/// it's borrowed. If the writer isn't available, `borrow_writer` may simply not call it.
+///
+/// Any [`core::fmt::Error`] while writing is ignored.
+///
+/// ```
+/// # use embedded_profiling::{EPInstant, EmbeddedProfiler, WriterProfiler};
+/// # struct Clock;
+/// # impl EmbeddedProfiler for Clock { fn read_clock(&self) -> EPInstant { EPInstant::from_ticks(0) } }
+/// struct Stdout;
+///
+/// impl core::fmt::Write for Stdout {
+///     fn write_str(&mut self, s: &str) -> core::fmt::Result {
+///         print!("{}", s);
+///         Ok(())
+///     }
+/// }
+///
+/// let profiler = WriterProfiler::new(Clock, |with_writer| with_writer(&mut Stdout));
+/// profiler.log_snapshot(&profiler.end_snapshot(profiler.start_snapshot("stdout"), "stdout").unwrap());
+/// ```
+pub struct WriterProfiler<C, W> {
+    clock: C,
+    borrow_writer: BorrowWriter<W>,
+    _writer: PhantomData<fn(&mut W)>,
+}
+
+impl<C, W> WriterProfiler<C, W> {
+    /// Creates a new [`WriterProfiler`] timed by `clock` and writing through `borrow_writer`.
+    #[must_use]
+    pub fn new(clock: C, borrow_writer: BorrowWriter<W>) -> Self {
+        Self {
+            clock,
+            borrow_writer,
+            _writer: PhantomData,
+        }
+    }
+
+    /// Consumes [`WriterProfiler`], returning the clock.
+    pub fn free(self) -> C {
+        self.clock
+    }
+}
+
+impl<C, W> EmbeddedProfiler for WriterProfiler<C, W>
+where
+    C: EmbeddedProfiler,
+    W: core::fmt::Write,
+{
+    fn log_snapshot(&self, snapshot: &EPSnapshot) {
+        (self.borrow_writer)(&mut |writer| {
+            crate::write_snapshot(writer, snapshot).ok();
+        });
+    }
+
+    forward_profiler!(self.clock => clock, at_start, at_end, snapshots);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::FakeClock;
+    use std::sync::Mutex;
+
+    static OUTPUT: Mutex<String> = Mutex::new(String::new());
+
+    #[test]
+    fn writes_to_string() {
+        let profiler = WriterProfiler::<_, String>::new(FakeClock::default(), |with_writer| {
+            with_writer(&mut OUTPUT.lock().unwrap());
+        });
+
+        let start = profiler.start_snapshot("first");
+        profiler.clock.advance(12);
+        profiler.log_snapshot(&profiler.end_snapshot(start, "first").unwrap());
+        profiler.log_snapshot(&profiler.end_snapshot(start, "second").unwrap());
+
+        assert_eq!(
+            *OUTPUT.lock().unwrap(),
+            "<EPSS first: 12 us>\n<EPSS second: 12 us>\n"
+        );
+    }
+}
@@ -0,0 +1,25 @@
+//! Fixtures shared by the integration tests. Each test runs in its own process, so each
+//! links in its own copy of the critical section implementation.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+use critical_section::RawRestoreState;
+
+/// A spin lock as the critical section, the restore state depends on the features other
+/// crates of the workspace enable.
+struct HostCriticalSection;
+critical_section::set_impl!(HostCriticalSection);
+
+static LOCKED: AtomicBool = AtomicBool::new(false);
+
+unsafe impl critical_section::Impl for HostCriticalSection {
+    unsafe fn acquire() -> RawRestoreState {
+        while LOCKED.swap(true, Ordering::Acquire) {
+            core::hint::spin_loop();
+        }
+        RawRestoreState::default()
+    }
+
+    unsafe fn release(_restore_state: RawRestoreState) {
+        LOCKED.store(false, Ordering::Release);
+    }
+}
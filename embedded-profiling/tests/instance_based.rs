@@ -0,0 +1,67 @@
+//! Runs in its own process, so the global profiler is known to never have been set.
+
+use core::cell::Cell;
+use embedded_profiling::{
+    AggregatingProfiler, CoalescingProfiler, EPDuration, EPInstant, EmbeddedProfiler, ProfilerKind,
+};
+
+/// A clock that moves by 10 ticks on every read.
+#[derive(Default)]
+struct SteppingClock(Cell<u32>);
+
+impl EmbeddedProfiler for SteppingClock {
+    // only a conversion with `container-u64`
+    #[allow(clippy::useless_conversion)]
+    fn read_clock(&self) -> EPInstant {
+        let now = self.0.get();
+        self.0.set(now + 10);
+        EPInstant::from_ticks(now.into())
+    }
+}
+
+#[test]
+fn profile_aggregate_drain() {
+    let profiler = AggregatingProfiler::<_, 2>::new(SteppingClock::default());
+
+    let mut calls = 0;
+    for _ in 0..3 {
+        calls = profiler.profile("work", || calls + 1);
+    }
+    assert_eq!(calls, 3);
+
+    let mut drained = 0;
+    profiler.for_each(|stats| {
+        assert_eq!(stats.name, "work");
+        assert_eq!(stats.count, 3);
+        assert_eq!(stats.total.ticks(), 30);
+        drained += 1;
+    });
+    profiler.clear();
+    assert_eq!(drained, 1);
+    assert!(profiler.stats("work").is_none());
+
+    assert_eq!(embedded_profiling::profiler_kind(), ProfilerKind::Noop);
+}
+
+#[test]
+fn profile_through_coalescing() {
+    let profiler = CoalescingProfiler::<_, 2>::new(
+        AggregatingProfiler::<_, 2>::new(SteppingClock::default()),
+        EPDuration::from_ticks(20),
+    );
+
+    assert!(!profiler.tick(|_| unreachable!("the window was only just opened")));
+    for _ in 0..3 {
+        profiler.profile("work", || ());
+    }
+
+    let mut drained = 0;
+    assert!(profiler.tick(|stats| {
+        assert_eq!(stats.name, "work");
+        assert_eq!(stats.count, 3);
+        drained += 1;
+    }));
+    assert_eq!(drained, 1);
+
+    assert_eq!(embedded_profiling::profiler_kind(), ProfilerKind::Noop);
+}
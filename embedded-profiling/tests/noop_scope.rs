@@ -0,0 +1,19 @@
+//! Runs in its own process, so the global profiler is known to be the no-op one.
+
+use embedded_profiling::ProfilerKind;
+
+#[test]
+fn scope_with_noop_profiler() {
+    assert_eq!(embedded_profiling::profiler_kind(), ProfilerKind::Noop);
+
+    let before = embedded_profiling::snapshots_emitted();
+    {
+        let guard = embedded_profiling::profile_scope("noop_scope");
+        let _child = guard.child("noop_child");
+    }
+    assert_eq!(embedded_profiling::nesting_depth(), 0);
+    assert_eq!(
+        embedded_profiling::snapshots_emitted().wrapping_sub(before),
+        2
+    );
+}
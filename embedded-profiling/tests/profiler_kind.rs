@@ -0,0 +1,22 @@
+//! Runs in its own process, so the global profiler is known to be unset at first.
+
+use embedded_profiling::{EPInstant, EmbeddedProfiler, ProfilerKind};
+
+struct MyProfiler;
+
+impl EmbeddedProfiler for MyProfiler {
+    fn read_clock(&self) -> EPInstant {
+        EPInstant::from_ticks(0)
+    }
+}
+
+static MY_PROFILER: MyProfiler = MyProfiler;
+
+#[test]
+fn noop_until_set() {
+    assert_eq!(embedded_profiling::profiler_kind(), ProfilerKind::Noop);
+    assert_eq!(embedded_profiling::profiler().name(), "noop");
+
+    unsafe { embedded_profiling::set_profiler(&MY_PROFILER).unwrap() };
+    assert_eq!(embedded_profiling::profiler_kind(), ProfilerKind::User);
+}
@@ -0,0 +1,38 @@
+//! Runs in its own process, so the global profiler is known to be unset at first.
+
+use embedded_profiling::{EPInstant, EmbeddedProfiler, ProfilerKind};
+
+struct NamedProfiler(&'static str);
+
+impl EmbeddedProfiler for NamedProfiler {
+    fn read_clock(&self) -> EPInstant {
+        EPInstant::from_ticks(0)
+    }
+
+    fn name(&self) -> &'static str {
+        self.0
+    }
+}
+
+static FIRST: NamedProfiler = NamedProfiler("first");
+static SECOND: NamedProfiler = NamedProfiler("second");
+
+#[test]
+fn replaced_and_cleared() {
+    unsafe { embedded_profiling::set_profiler(&FIRST).unwrap() };
+    assert_eq!(embedded_profiling::profiler().name(), "first");
+
+    let previous = unsafe { embedded_profiling::replace_profiler(&SECOND) };
+    assert_eq!(previous.unwrap().name(), "first");
+    assert_eq!(embedded_profiling::profiler().name(), "second");
+
+    unsafe { embedded_profiling::clear_profiler() };
+    assert_eq!(embedded_profiling::profiler_kind(), ProfilerKind::Noop);
+    assert_eq!(embedded_profiling::profiler().name(), "noop");
+
+    // a cleared profiler can be set again, and isn't returned when replaced
+    unsafe { embedded_profiling::set_profiler(&FIRST).unwrap() };
+    unsafe { embedded_profiling::clear_profiler() };
+    assert!(unsafe { embedded_profiling::replace_profiler(&SECOND) }.is_none());
+    assert_eq!(embedded_profiling::profiler().name(), "second");
+}
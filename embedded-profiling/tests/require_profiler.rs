@@ -0,0 +1,14 @@
+//! Runs in its own process, so the global profiler is known to be unset.
+
+#[test]
+#[cfg_attr(
+    debug_assertions,
+    should_panic(expected = "was `set_profiler` called yet?")
+)]
+fn profiling_before_set_profiler_trips() {
+    // not checked unless enabled
+    embedded_profiling::profile("before enabling", || {});
+
+    embedded_profiling::require_profiler(true);
+    embedded_profiling::profile("too early", || {});
+}
@@ -0,0 +1,35 @@
+//! Runs in its own process, to provide its own critical section implementation.
+#![cfg(feature = "critical-section")]
+
+mod common;
+
+use embedded_profiling::{EPDuration, EPInstant, EPSnapshot, EmbeddedProfiler, RingProfiler};
+
+struct MyProfiler;
+
+impl EmbeddedProfiler for MyProfiler {
+    fn read_clock(&self) -> EPInstant {
+        EPInstant::from_ticks(0)
+    }
+
+    fn log_snapshot(&self, _snapshot: &EPSnapshot) {
+        panic!("buffered snapshots aren't logged by the wrapped profiler");
+    }
+}
+
+static PROFILER: RingProfiler<MyProfiler, 3> = RingProfiler::new(MyProfiler);
+
+#[test]
+fn keeps_the_last_snapshots() {
+    assert!(PROFILER.is_empty());
+    for ticks in 1..=5 {
+        PROFILER.log_snapshot(&EPSnapshot::new("burst", EPDuration::from_ticks(ticks)));
+    }
+    assert_eq!(PROFILER.len(), 3);
+    assert!(PROFILER.is_full());
+
+    let mut drained = Vec::new();
+    PROFILER.drain(|snapshot| drained.push(snapshot.duration.ticks()));
+    assert_eq!(drained, [3, 4, 5]);
+    assert!(PROFILER.is_empty());
+}
@@ -0,0 +1,26 @@
+//! Runs in its own process, so the global profiler is known to be unset at first.
+#![cfg(feature = "critical-section")]
+
+mod common;
+
+use embedded_profiling::{EPInstant, EmbeddedProfiler, ProfilerKind, SetProfilerError};
+
+struct MyProfiler;
+
+impl EmbeddedProfiler for MyProfiler {
+    fn read_clock(&self) -> EPInstant {
+        EPInstant::from_ticks(0)
+    }
+}
+
+static MY_PROFILER: MyProfiler = MyProfiler;
+
+#[test]
+fn set_once_in_critical_section() {
+    assert!(embedded_profiling::set_profiler_cs(&MY_PROFILER).is_ok());
+    assert_eq!(embedded_profiling::profiler_kind(), ProfilerKind::User);
+    assert!(matches!(
+        embedded_profiling::set_profiler_cs(&MY_PROFILER),
+        Err(SetProfilerError::AlreadySet)
+    ));
+}
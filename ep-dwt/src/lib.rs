@@ -8,9 +8,14 @@
 //! to expire just before overflow, so you can expect an exception to fire every 2**32
 //! clock cycles.
 //!
-//! Snapshots are logged using [`log::info!`], so having a logger installed is required
-//! if you want to use [`embedded_profiling::log_snapshot`] or functions that call it
-//! (like [`embedded_profiling::profile_function`]).
+//! [`DwtProfiler`] can also busy wait with [`DwtProfiler::delay_cycles`] and
+//! [`DwtProfiler::delay_us`], reusing the profiler's cycle counter for precise delays.
+//!
+//! Snapshots are logged using [`log::info!`] (or `defmt` with the `defmt` feature), so
+//! having a logger installed is required if you want to use
+//! [`embedded_profiling::log_snapshot`] or functions that call it (like
+//! [`embedded_profiling::profile_function`]). The record's target is the snapshot's
+//! `target` if it has one, so profiling output can be filtered by the profiled module.
 //!
 //! ## Example Usage
 //!
@@ -29,6 +34,24 @@
 //! embedded_profiling::profile("print_profile", || println!("Hello, world"));
 //! ```
 //!
+//! The [`dwt_profiler!`] macro does the same while only naming the frequency once:
+//!
+//!```no_run
+//! # use cortex_m::peripheral::Peripherals as CorePeripherals;
+//! # const CORE_FREQ: u32 = 120_000_000;
+//! let mut core = CorePeripherals::take().unwrap();
+//! unsafe {
+//!     ep_dwt::dwt_profiler!(CORE_FREQ, &mut core.DCB, core.DWT).unwrap();
+//! }
+//! ```
+//!
+//! ## Runtime Frequency Changes
+//!
+//! [`DwtProfiler`] encodes the core clock frequency as the const generic `FREQ`, which can't
+//! follow a HAL that changes the core clock at runtime (e.g. for dynamic voltage and frequency
+//! scaling). For those systems, [`DynamicFreqProfiler`] keeps the frequency as a runtime value
+//! that is updated with [`DynamicFreqProfiler::set_freq`].
+//!
 //! ## Features
 //!
 //! ### `extended`
@@ -43,13 +66,21 @@
 //! enables the `proc-macros` feature in [`embedded-profiling`](embedded_profiling). Enables
 //! the [`embedded_profiling::profile_function`] procedural macro.
 //!
+//! ### `defmt`
+//!
+//! enables the `defmt` feature in [`embedded-profiling`](embedded_profiling). Snapshots are
+//! logged with `defmt::info!` instead of [`log::info!`].
+//!
 //! [`DWT`]: cortex_m::peripheral::DWT
 //! [`DebugMonitor`]: `cortex_m::peripheral::scb::Exception::DebugMonitor`
 //! [`embedded_profiling::profile_function`]: https://docs.rs/embedded-profiling/latest/embedded_profiling/attr.profile_function.html
 #![cfg_attr(not(test), no_std)]
 
-use embedded_profiling::{EPContainer, EPInstant, EPInstantGeneric, EPSnapshot, EmbeddedProfiler};
+use embedded_profiling::{
+    EPContainer, EPDuration, EPInstant, EPInstantGeneric, EPInstantNs, EPSnapshot, EmbeddedProfiler,
+};
 
+use core::cell::Cell;
 use cortex_m::peripheral::{DCB, DWT};
 
 #[cfg(feature = "extended")]
@@ -61,9 +92,75 @@ use cortex_m_rt::exception;
 /// Tracker of `cyccnt` cycle count overflows to extend this timer to 64 bit
 static ROLLOVER_COUNT: AtomicU32 = AtomicU32::new(0);
 
+/// Resets the count of `cyccnt` overflows to zero, so tests can start from a known state.
+///
+/// Only available in this crate's tests or with the `test-utils` feature. The count is
+/// shared by every profiler of this crate, so resetting it moves the clock of all of them
+/// backwards: don't use it in production code.
+#[cfg(all(feature = "extended", any(test, feature = "test-utils")))]
+pub fn reset_rollover_count() {
+    ROLLOVER_COUNT.store(0, Ordering::Relaxed);
+}
+
+/// Checks at compile time that the container `T` is wide enough for the `extended` feature.
+///
+/// Evaluating [`ContainerCheck::OK`] fails the build with a message naming the missing
+/// feature unless `T` is 64 bit:
+///
+/// ```compile_fail,E0080
+/// let () = ep_dwt::ContainerCheck::<u32>::OK;
+/// ```
+///
+/// ```
+/// let () = ep_dwt::ContainerCheck::<u64>::OK;
+/// ```
+#[doc(hidden)]
+pub struct ContainerCheck<T>(core::marker::PhantomData<T>);
+
+impl<T> ContainerCheck<T> {
+    /// Fails to evaluate unless `T` is 64 bit.
+    pub const OK: () = assert!(
+        core::mem::size_of::<T>() == 8,
+        "the `extended` feature of ep-dwt requires the `container-u64` feature of embedded-profiling"
+    );
+}
+
 #[cfg(feature = "extended")]
 // For extended mode to work, we really need a u64 container. Double check this.
-static_assertions::assert_type_eq_all!(EPContainer, u64);
+const _: () = ContainerCheck::<EPContainer>::OK;
+
+/// Enables the [`DWT`] cycle counter, starting it from zero.
+fn enable_cycle_counter(dcb: &mut DCB, dwt: &mut DWT) {
+    // Enable the DWT block
+    dcb.enable_trace();
+    #[cfg(feature = "extended")]
+    // Enable DebugMonitor exceptions to fire to track overflows
+    unsafe {
+        dcb.demcr.modify(|f| f | 1 << 16);
+    }
+    DWT::unlock();
+
+    // reset cycle count and enable it to run
+    unsafe { dwt.cyccnt.write(0) };
+    dwt.enable_cycle_counter();
+}
+
+/// Reads the cycle count, adding the rollovers if we're extended.
+fn read_cycles(dwt: &DWT) -> EPContainer {
+    debug_assert!(
+        DWT::cycle_counter_enabled(),
+        "DWT cycle counter is disabled, did something else reconfigure the `DWT`?"
+    );
+
+    #[allow(unused_mut)]
+    let mut count = EPContainer::from(dwt.cyccnt.read());
+    #[cfg(feature = "extended")]
+    {
+        count +=
+            EPContainer::from(ROLLOVER_COUNT.load(Ordering::Relaxed)) * EPContainer::from(u32::MAX);
+    }
+    count
+}
 
 /// DWT trace unit implementing [`EmbeddedProfiler`].
 ///
@@ -84,40 +181,338 @@ impl<const FREQ: u32> DwtProfiler<FREQ> {
     pub fn new(dcb: &mut DCB, mut dwt: DWT, sysclk: u32) -> Self {
         assert!(FREQ == sysclk);
 
-        // Enable the DWT block
-        dcb.enable_trace();
-        #[cfg(feature = "extended")]
-        // Enable DebugMonitor exceptions to fire to track overflows
-        unsafe {
-            dcb.demcr.modify(|f| f | 1 << 16);
-        }
-        DWT::unlock();
-
-        // reset cycle count and enable it to run
-        unsafe { dwt.cyccnt.write(0) };
-        dwt.enable_cycle_counter();
+        enable_cycle_counter(dcb, &mut dwt);
 
         Self { dwt }
     }
+
+    /// Core clock cycles per microsecond, as the reduced fraction `FREQ / 1_000_000`.
+    const CYCLES_PER_MICRO: (u64, u64) = {
+        let divisor = embedded_profiling::gcd(FREQ as u64, 1_000_000);
+        (FREQ as u64 / divisor, 1_000_000 / divisor)
+    };
+
+    /// Busy waits until the [`DWT`] cycle counter has counted at least `cycles`.
+    ///
+    /// This spins on the counter, keeping the core awake the whole time, so it isn't suited
+    /// for low-power waits. The counter wrapping around during the wait is accounted for.
+    pub fn delay_cycles(&self, cycles: u32) {
+        spin_cycles(|| self.dwt.cyccnt.read(), cycles);
+    }
+
+    /// Busy waits for at least `us` microseconds, see [`DwtProfiler::delay_cycles`].
+    pub fn delay_us(&self, us: u32) {
+        let (nom, denom) = Self::CYCLES_PER_MICRO;
+        // round up to wait at least `us`, and split waits longer than the counter can count
+        let mut cycles = (u64::from(us) * nom + denom - 1) / denom;
+        while cycles > 0 {
+            let chunk = u32::try_from(cycles).unwrap_or(u32::MAX);
+            self.delay_cycles(chunk);
+            cycles -= u64::from(chunk);
+        }
+    }
+}
+
+/// Spins until the wrapping counter read by `read` has advanced by at least `cycles`.
+fn spin_cycles(mut read: impl FnMut() -> u32, cycles: u32) {
+    let start = read();
+    while read().wrapping_sub(start) < cycles {}
 }
 
 impl<const FREQ: u32> EmbeddedProfiler for DwtProfiler<FREQ> {
     fn read_clock(&self) -> EPInstant {
-        // get the cycle count and add the rollover if we're extended
-        #[allow(unused_mut)]
-        let mut count = EPContainer::from(self.dwt.cyccnt.read());
-        #[cfg(feature = "extended")]
-        {
-            count += EPContainer::from(ROLLOVER_COUNT.load(Ordering::Relaxed))
-                * EPContainer::from(u32::MAX);
-        }
+        let count = read_cycles(&self.dwt);
 
         // convert count and return the instant
         embedded_profiling::convert_instant(EPInstantGeneric::<1, FREQ>::from_ticks(count))
     }
 
+    fn read_clock_raw(&self) -> EPContainer {
+        read_cycles(&self.dwt)
+    }
+
+    fn duration_from_raw(&self, raw: EPContainer) -> EPDuration {
+        embedded_profiling::convert_instant(EPInstantGeneric::<1, FREQ>::from_ticks(raw))
+            .duration_since_epoch()
+    }
+
+    fn read_clock_ns(&self) -> EPInstantNs {
+        let count = read_cycles(&self.dwt);
+        embedded_profiling::convert_instant_ns(EPInstantGeneric::<1, FREQ>::from_ticks(count))
+    }
+
+    fn log_snapshot(&self, snapshot: &EPSnapshot) {
+        #[cfg(feature = "defmt")]
+        embedded_profiling::defmt_log_snapshot(snapshot);
+        #[cfg(not(feature = "defmt"))]
+        log::info!(target: snapshot.target.unwrap_or(module_path!()), "{}", snapshot);
+    }
+
+    fn clock_is_running(&self) -> bool {
+        DWT::cycle_counter_enabled()
+    }
+
+    fn name(&self) -> &'static str {
+        "dwt"
+    }
+
+    #[cfg(feature = "extended")]
+    fn overflow_events(&self) -> Option<u32> {
+        Some(ROLLOVER_COUNT.load(Ordering::Relaxed))
+    }
+}
+
+#[doc(hidden)]
+pub use cortex_m as __cortex_m;
+#[doc(hidden)]
+pub use embedded_profiling as __embedded_profiling;
+
+/// Creates a [`DwtProfiler`] in a [`singleton!`](cortex_m::singleton) and installs it
+/// with [`set_profiler`](embedded_profiling::set_profiler).
+///
+/// `FREQ` must be a constant expression. It is used as the const generic parameter, and
+/// also as the runtime `sysclk` unless one is given (e.g. from the HAL) to be checked
+/// against it. Evaluates to the [`set_profiler`](embedded_profiling::set_profiler) result
+/// with the installed profiler on success.
+///
+/// ```no_run
+/// # use cortex_m::peripheral::Peripherals as CorePeripherals;
+/// # const CORE_FREQ: u32 = 120_000_000;
+/// # let sysclk = CORE_FREQ;
+/// let mut core = CorePeripherals::take().unwrap();
+/// let profiler: &'static ep_dwt::DwtProfiler<CORE_FREQ> =
+///     unsafe { ep_dwt::dwt_profiler!(CORE_FREQ, &mut core.DCB, core.DWT, sysclk).unwrap() };
+/// ```
+///
+/// # Safety
+/// The expansion calls [`set_profiler`](embedded_profiling::set_profiler), so it must be used
+/// in an `unsafe` block and upholds the same requirements.
+///
+/// # Panics
+/// if the same invocation of the macro is evaluated more than once, as each call site owns
+/// a single static profiler, or if `sysclk` doesn't match `FREQ`.
+#[macro_export]
+macro_rules! dwt_profiler {
+    ($freq:expr, $dcb:expr, $dwt:expr) => {
+        $crate::dwt_profiler!($freq, $dcb, $dwt, $freq)
+    };
+    ($freq:expr, $dcb:expr, $dwt:expr, $sysclk:expr) => {{
+        let profiler: &'static $crate::DwtProfiler<{ $freq }> = $crate::__cortex_m::singleton!(
+            : $crate::DwtProfiler<{ $freq }> = $crate::DwtProfiler::<{ $freq }>::new($dcb, $dwt, $sysclk)
+        )
+        .unwrap();
+        $crate::__embedded_profiling::set_profiler(profiler).map(|()| profiler)
+    }};
+}
+
+/// Converts cycles to microseconds across changes of the clock frequency.
+///
+/// Cycles counted before the last frequency change have already been converted into
+/// `base_micros`, so only the cycles since then are scaled by the current frequency.
+#[derive(Clone, Copy)]
+struct CycleScaler {
+    freq: u32,
+    base_cycles: EPContainer,
+    base_micros: EPContainer,
+}
+
+impl CycleScaler {
+    const fn new(freq: u32) -> Self {
+        assert!(freq != 0, "the core clock frequency can't be zero");
+        Self {
+            freq,
+            base_cycles: 0,
+            base_micros: 0,
+        }
+    }
+
+    /// The microseconds elapsed at the cycle count `cycles`.
+    // the conversions are only useless if the container is already 64 bits
+    #[allow(clippy::useless_conversion)]
+    fn micros(&self, cycles: EPContainer) -> EPContainer {
+        let freq = u64::from(self.freq);
+        let elapsed = u64::from(cycles.wrapping_sub(self.base_cycles));
+        // split the division so the multiplication can't overflow
+        let micros = elapsed / freq * 1_000_000 + elapsed % freq * 1_000_000 / freq;
+        // wraps like the counter does if the container is only 32 bits
+        #[allow(clippy::cast_possible_truncation)]
+        let micros = micros as EPContainer;
+        self.base_micros.wrapping_add(micros)
+    }
+
+    /// Switches to the frequency `freq` as of the cycle count `cycles`.
+    fn set_freq(&mut self, freq: u32, cycles: EPContainer) {
+        assert!(freq != 0, "the core clock frequency can't be zero");
+        self.base_micros = self.micros(cycles);
+        self.base_cycles = cycles;
+        self.freq = freq;
+    }
+}
+
+/// DWT trace unit implementing [`EmbeddedProfiler`] with a frequency that can change at runtime.
+///
+/// Unlike [`DwtProfiler`], there is no compile time frequency to check `sysclk` against.
+/// Instead, call [`DynamicFreqProfiler::set_freq`] whenever the core clock changes, e.g. from
+/// the HAL's clock change callback.
+///
+/// # Frequency changes during a snapshot
+///
+/// Durations spanning a frequency change are still correct, as the cycles counted before the
+/// change are converted with the old frequency. However, any cycles counted between the actual
+/// clock change and the call to [`DynamicFreqProfiler::set_freq`] are converted with the
+/// wrong frequency, so call it as close to the clock change as possible.
+pub struct DynamicFreqProfiler {
+    dwt: DWT,
+    scaler: Cell<CycleScaler>,
+}
+
+impl DynamicFreqProfiler {
+    /// Enable the [`DWT`] and provide a new [`EmbeddedProfiler`] running at `sysclk` Hz.
+    ///
+    /// # Panics
+    /// if `sysclk` is zero
+    #[must_use]
+    pub fn new(dcb: &mut DCB, mut dwt: DWT, sysclk: u32) -> Self {
+        enable_cycle_counter(dcb, &mut dwt);
+
+        Self {
+            dwt,
+            scaler: Cell::new(CycleScaler::new(sysclk)),
+        }
+    }
+
+    /// Updates the core clock frequency to `hz`, effective immediately.
+    ///
+    /// # Panics
+    /// if `hz` is zero
+    pub fn set_freq(&self, hz: u32) {
+        cortex_m::interrupt::free(|_| {
+            let mut scaler = self.scaler.get();
+            scaler.set_freq(hz, read_cycles(&self.dwt));
+            self.scaler.set(scaler);
+        });
+    }
+
+    /// The core clock frequency currently used for conversion, in Hz.
+    pub fn freq(&self) -> u32 {
+        self.scaler.get().freq
+    }
+}
+
+impl EmbeddedProfiler for DynamicFreqProfiler {
+    fn read_clock(&self) -> EPInstant {
+        // read the counter and the scaler together so `set_freq` can't interrupt in between
+        let micros =
+            cortex_m::interrupt::free(|_| self.scaler.get().micros(read_cycles(&self.dwt)));
+        EPInstant::from_ticks(micros)
+    }
+
+    fn read_clock_raw(&self) -> EPContainer {
+        read_cycles(&self.dwt)
+    }
+
+    /// Converts `raw` cycles with the current frequency, even if they were counted before a
+    /// frequency change.
+    fn duration_from_raw(&self, raw: EPContainer) -> EPDuration {
+        EPDuration::from_ticks(CycleScaler::new(self.freq()).micros(raw))
+    }
+
     fn log_snapshot(&self, snapshot: &EPSnapshot) {
-        log::info!("{}", snapshot);
+        #[cfg(feature = "defmt")]
+        embedded_profiling::defmt_log_snapshot(snapshot);
+        #[cfg(not(feature = "defmt"))]
+        log::info!(target: snapshot.target.unwrap_or(module_path!()), "{}", snapshot);
+    }
+
+    fn clock_is_running(&self) -> bool {
+        DWT::cycle_counter_enabled()
+    }
+
+    fn name(&self) -> &'static str {
+        "dwt"
+    }
+
+    #[cfg(feature = "extended")]
+    fn overflow_events(&self) -> Option<u32> {
+        Some(ROLLOVER_COUNT.load(Ordering::Relaxed))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "extended")]
+    fn rollover_count_reset() {
+        ROLLOVER_COUNT.store(3, Ordering::Relaxed);
+        reset_rollover_count();
+        assert_eq!(ROLLOVER_COUNT.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn dwt_profiler_macro_expands() {
+        // the peripherals can't be taken on the host, so only check the expansion type checks
+        fn install(
+            dcb: &mut DCB,
+            dwt: DWT,
+            sysclk: u32,
+        ) -> Result<&'static DwtProfiler<120_000_000>, embedded_profiling::SetProfilerError>
+        {
+            unsafe { dwt_profiler!(120_000_000, dcb, dwt, sysclk) }
+        }
+        let _ = install;
+    }
+
+    #[test]
+    fn spins_until_cycles_elapsed() {
+        use core::cell::Cell;
+
+        // a fake counter advancing 7 cycles per read, starting just before wrapping
+        let counter = Cell::new(u32::MAX - 20);
+        let reads = Cell::new(0);
+        spin_cycles(
+            || {
+                reads.set(reads.get() + 1);
+                counter.set(counter.get().wrapping_add(7));
+                counter.get()
+            },
+            100,
+        );
+        // 15 reads of 7 cycles are the first to reach 100 after the starting read
+        assert_eq!(reads.get(), 16);
+        assert_eq!(counter.get(), 91);
+    }
+
+    #[test]
+    fn reduced_cycles_per_micro() {
+        assert_eq!(DwtProfiler::<120_000_000>::CYCLES_PER_MICRO, (120, 1));
+        assert_eq!(DwtProfiler::<32_768>::CYCLES_PER_MICRO, (512, 15_625));
+    }
+
+    #[test]
+    fn scales_across_frequency_change() {
+        let mut scaler = CycleScaler::new(1_000_000);
+        assert_eq!(scaler.micros(1_000), 1_000);
+
+        // double the frequency, only the cycles after the change count at the new speed
+        scaler.set_freq(2_000_000, 1_000);
+        assert_eq!(scaler.micros(1_000), 1_000);
+        assert_eq!(scaler.micros(3_000), 2_000);
+    }
+
+    #[test]
+    fn scales_without_overflow() {
+        let scaler = CycleScaler::new(120_000_000);
+        assert_eq!(scaler.micros(119_999_999), 999_999);
+        assert_eq!(scaler.micros(120_000_000), 1_000_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "frequency can't be zero")]
+    fn zero_frequency_rejected() {
+        let mut scaler = CycleScaler::new(1_000_000);
+        scaler.set_freq(0, 1_000);
     }
 }
 
@@ -0,0 +1,160 @@
+//! [`EmbeddedProfiler`] implementation based on the system timer (`SYSTIMER`) of ESP devices.
+//!
+//! ESP chips don't have the DWT or SysTick of cortex-M devices, but the ESP32-S2 and newer
+//! ones (the C and H series, S3, ...) have a system timer, clocked from the crystal
+//! independently of the CPU clock. It counts at 16 MHz on most chips with a 40 MHz crystal,
+//! but at 80 MHz on the ESP32-S2, so the frequency is taken from `esp-hal` at runtime. The
+//! timer is 52 bits wide (64 on the ESP32-S2) and takes years to overflow, so unlike `ep-dwt`
+//! and `ep-systick` there is no need to extend it. The timer's microseconds are truncated to
+//! the [`EPInstant`] though, which wraps after about 71 minutes unless the `container-u64`
+//! feature is used.
+//!
+//! The original ESP32 has no system timer and isn't supported.
+//!
+//! The system timer is read through [`esp-hal`](https://docs.rs/esp-hal), which requires the
+//! `esp` feature and an ESP target. `esp-hal` must have its chip feature (e.g. `esp32c3`)
+//! enabled by the application.
+//!
+//! Snapshots are logged using [`log::info!`], so having a logger installed is required
+//! if you want to use [`embedded_profiling::log_snapshot`] or functions that call it
+//! (like [`embedded_profiling::profile_function`]). The record's target is the snapshot's
+//! `target` if it has one, so profiling output can be filtered by the profiled module.
+//!
+//! ## Example Usage
+//!
+//!```ignore
+//! let peripherals = esp_hal::init(esp_hal::Config::default());
+//! // the system timer is started by `esp_hal::init`
+//! static ESP_PROFILER: ep_esp::EspProfiler = ep_esp::EspProfiler::new();
+//! unsafe {
+//!     embedded_profiling::set_profiler(&ESP_PROFILER).unwrap();
+//! }
+//! // (...)
+//! embedded_profiling::profile("print_profile", || println!("Hello, world"));
+//! ```
+//!
+//! ## Features
+//!
+//! ### `esp`
+//!
+//! enables [`EspProfiler`], reading the system timer using `esp-hal`. Only has an effect
+//! on ESP (`riscv32` or `xtensa`) targets.
+//!
+//! ### `container-u64`
+//!
+//! enables the `container-u64` feature in [`embedded-profiling`](embedded_profiling). Use
+//! a [`u64`] as the time storage type instead of [`u32`] for longer running profiling.
+//!
+//! ### `proc-macros`
+//!
+//! enables the `proc-macros` feature in [`embedded-profiling`](embedded_profiling). Enables
+//! the [`embedded_profiling::profile_function`] procedural macro.
+//!
+//! [`embedded_profiling::profile_function`]: https://docs.rs/embedded-profiling/latest/embedded_profiling/attr.profile_function.html
+#![cfg_attr(not(test), no_std)]
+
+use embedded_profiling::{EPContainer, EPInstant};
+
+#[cfg(all(feature = "esp", any(target_arch = "riscv32", target_arch = "xtensa")))]
+use embedded_profiling::{EPSnapshot, EmbeddedProfiler};
+#[cfg(all(feature = "esp", any(target_arch = "riscv32", target_arch = "xtensa")))]
+use esp_hal::timer::systimer::{SystemTimer, Unit};
+
+/// Converts a system timer count at `ticks_per_second` to an [`EPInstant`], truncating it to
+/// the [`EPContainer`].
+///
+/// The whole seconds and the remainder are converted separately, so frequencies that
+/// aren't a whole number of MHz (like the 10.4 MHz of a 26 MHz crystal) are exact and the
+/// conversion can't overflow.
+#[must_use]
+// only a truncation without `container-u64`
+#[allow(clippy::unnecessary_cast)]
+pub const fn ticks_to_instant(ticks: u64, ticks_per_second: u64) -> EPInstant {
+    let micros = ticks / ticks_per_second * 1_000_000
+        + ticks % ticks_per_second * 1_000_000 / ticks_per_second;
+    EPInstant::from_ticks(micros as EPContainer)
+}
+
+/// ESP system timer implementation of [`EmbeddedProfiler`].
+///
+/// Reads unit 0 of the system timer, which `esp-hal` also uses for its own timekeeping.
+#[cfg(all(feature = "esp", any(target_arch = "riscv32", target_arch = "xtensa")))]
+pub struct EspProfiler {
+    _private: (),
+}
+
+#[cfg(all(feature = "esp", any(target_arch = "riscv32", target_arch = "xtensa")))]
+impl EspProfiler {
+    /// Provides a new [`EmbeddedProfiler`] reading the system timer.
+    ///
+    /// The system timer isn't configured here, it's started by `esp_hal::init`.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { _private: () }
+    }
+}
+
+#[cfg(all(feature = "esp", any(target_arch = "riscv32", target_arch = "xtensa")))]
+impl Default for EspProfiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(all(feature = "esp", any(target_arch = "riscv32", target_arch = "xtensa")))]
+impl EmbeddedProfiler for EspProfiler {
+    fn read_clock(&self) -> EPInstant {
+        ticks_to_instant(
+            SystemTimer::unit_value(Unit::Unit0),
+            SystemTimer::ticks_per_second(),
+        )
+    }
+
+    fn log_snapshot(&self, snapshot: &EPSnapshot) {
+        log::info!(target: snapshot.target.unwrap_or(module_path!()), "{}", snapshot);
+    }
+
+    fn name(&self) -> &'static str {
+        "esp"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MHZ_16: u64 = 16_000_000;
+
+    #[test]
+    fn tick_to_microsecond_conversion() {
+        assert_eq!(ticks_to_instant(0, MHZ_16).ticks(), 0);
+        assert_eq!(ticks_to_instant(15, MHZ_16).ticks(), 0);
+        assert_eq!(ticks_to_instant(16, MHZ_16).ticks(), 1);
+        assert_eq!(ticks_to_instant(16_000_000, MHZ_16).ticks(), 1_000_000);
+    }
+
+    #[test]
+    fn other_frequencies() {
+        // ESP32-S2
+        assert_eq!(ticks_to_instant(80_000_040, 80_000_000).ticks(), 1_000_000);
+        // 26 MHz crystal
+        assert_eq!(ticks_to_instant(10_400_104, 10_400_000).ticks(), 1_000_010);
+    }
+
+    #[test]
+    #[cfg(not(feature = "container-u64"))]
+    fn truncated_to_container() {
+        let micros = u64::from(u32::MAX) + 1 + 5;
+        assert_eq!(ticks_to_instant(micros * 16, MHZ_16).ticks(), 5);
+    }
+
+    #[test]
+    fn no_overflow_at_full_width() {
+        // 52 bits of ticks, close to 9 years at 16 MHz
+        let ticks = (1 << 52) - 1;
+        let micros = ticks / 16;
+        #[allow(clippy::unnecessary_cast)]
+        let expected = micros as EPContainer;
+        assert_eq!(ticks_to_instant(ticks, MHZ_16).ticks(), expected);
+    }
+}
@@ -0,0 +1,246 @@
+//! [`EmbeddedProfiler`] wrapper that logs snapshots as structured [`log`] key-values.
+//!
+//! Wraps another [`EmbeddedProfiler`], which still provides the clock and the
+//! [`at_start`](EmbeddedProfiler::at_start)/[`at_end`](EmbeddedProfiler::at_end) hooks.
+//! Only [`log_snapshot`](EmbeddedProfiler::log_snapshot) is replaced: instead of
+//! interpolating the snapshot into the message, the name and duration are attached to the
+//! record as the key-values `name` and `duration_us`, so log aggregators can parse them
+//! reliably.
+//!
+//! As with the other backends, snapshots are logged using [`log::info!`], so having a
+//! logger installed is required. The record's target is the snapshot's `target` if it has
+//! one, so profiling output can be filtered by the profiled module.
+//!
+//! ## Example Usage
+//!
+//!```no_run
+//! # struct MyProfiler;
+//! # impl embedded_profiling::EmbeddedProfiler for MyProfiler { fn read_clock(&self) -> embedded_profiling::EPInstant { embedded_profiling::EPInstant::from_ticks(0) } }
+//! # let clock_profiler = MyProfiler;
+//! let kv_profiler = ep_log_kv::LogKvProfiler::new(clock_profiler);
+//! let kv_profiler: &'static _ = Box::leak(Box::new(kv_profiler));
+//! unsafe {
+//!     embedded_profiling::set_profiler(kv_profiler).unwrap();
+//! }
+//! // (...)
+//! embedded_profiling::profile("print_profile", || println!("Hello, world"));
+//! ```
+//!
+//! ## Features
+//!
+//! ### `kv` (default)
+//!
+//! enables the `kv` feature of [`log`] and emits `name`/`duration_us` as structured
+//! key-values. Without it, the snapshot falls back to being formatted into the message
+//! like the other backends do.
+//!
+//! ### `proc-macros`
+//!
+//! enables the `proc-macros` feature in [`embedded-profiling`](embedded_profiling). Enables
+//! the [`embedded_profiling::profile_function`] procedural macro.
+//!
+//! [`embedded_profiling::profile_function`]: https://docs.rs/embedded-profiling/latest/embedded_profiling/attr.profile_function.html
+#![cfg_attr(not(test), no_std)]
+
+use embedded_profiling::{EPSnapshot, EmbeddedProfiler};
+
+/// Wraps the [`EmbeddedProfiler`] `P`, logging its snapshots as structured key-values.
+pub struct LogKvProfiler<P> {
+    inner: P,
+}
+
+impl<P> LogKvProfiler<P> {
+    /// Creates a new [`LogKvProfiler`] around the profiler `inner`.
+    #[must_use]
+    pub const fn new(inner: P) -> Self {
+        Self { inner }
+    }
+
+    /// Consumes [`LogKvProfiler`], returning the wrapped profiler.
+    pub fn free(self) -> P {
+        self.inner
+    }
+}
+
+impl<P> EmbeddedProfiler for LogKvProfiler<P>
+where
+    P: EmbeddedProfiler,
+{
+    fn log_snapshot(&self, snapshot: &EPSnapshot) {
+        let target = snapshot.target.unwrap_or(module_path!());
+
+        #[cfg(feature = "kv")]
+        log::info!(
+            target: target,
+            name = snapshot.name,
+            duration_us = snapshot.duration.ticks();
+            "snapshot"
+        );
+
+        #[cfg(not(feature = "kv"))]
+        log::info!(target: target, "{}", snapshot);
+    }
+
+    embedded_profiling::forward_profiler!(self.inner => clock, hooks, snapshots);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use embedded_profiling::{EPDuration, EPInstant};
+    use std::sync::{Mutex, Once};
+
+    /// A single captured `log` record: the message and its key-values.
+    struct Captured {
+        target: String,
+        message: String,
+        key_values: Vec<(String, String)>,
+    }
+
+    struct CaptureLogger {
+        records: Mutex<Vec<Captured>>,
+    }
+
+    #[cfg(feature = "kv")]
+    struct CollectKeyValues<'a>(&'a mut Vec<(String, String)>);
+
+    #[cfg(feature = "kv")]
+    impl<'kvs> log::kv::VisitSource<'kvs> for CollectKeyValues<'_> {
+        fn visit_pair(
+            &mut self,
+            key: log::kv::Key<'kvs>,
+            value: log::kv::Value<'kvs>,
+        ) -> Result<(), log::kv::Error> {
+            self.0.push((key.to_string(), value.to_string()));
+            Ok(())
+        }
+    }
+
+    impl log::Log for CaptureLogger {
+        fn enabled(&self, _metadata: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            #[allow(unused_mut)]
+            let mut key_values = Vec::new();
+            #[cfg(feature = "kv")]
+            record
+                .key_values()
+                .visit(&mut CollectKeyValues(&mut key_values))
+                .unwrap();
+
+            self.records.lock().unwrap().push(Captured {
+                target: record.target().to_string(),
+                message: record.args().to_string(),
+                key_values,
+            });
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: CaptureLogger = CaptureLogger {
+        records: Mutex::new(Vec::new()),
+    };
+    static INIT_LOGGER: Once = Once::new();
+
+    fn set_logger() {
+        INIT_LOGGER.call_once(|| {
+            log::set_logger(&LOGGER).unwrap();
+            log::set_max_level(log::LevelFilter::Info);
+        });
+    }
+
+    struct FixedClock;
+
+    impl EmbeddedProfiler for FixedClock {
+        fn read_clock(&self) -> EPInstant {
+            EPInstant::from_ticks(0)
+        }
+    }
+
+    #[test]
+    fn logs_snapshot() {
+        set_logger();
+
+        let profiler = LogKvProfiler::new(FixedClock);
+        profiler.log_snapshot(&EPSnapshot::new(
+            "kv_snapshot",
+            EPDuration::from_ticks(1234),
+        ));
+
+        let records = LOGGER.records.lock().unwrap();
+        // the only record without a target of its own
+        let record = records
+            .iter()
+            .find(|record| record.target == "ep_log_kv")
+            .unwrap();
+
+        #[cfg(feature = "kv")]
+        {
+            assert_eq!(record.message, "snapshot");
+            assert_eq!(
+                record.key_values,
+                [
+                    ("name".to_string(), "kv_snapshot".to_string()),
+                    ("duration_us".to_string(), "1234".to_string()),
+                ]
+            );
+        }
+
+        #[cfg(not(feature = "kv"))]
+        {
+            assert_eq!(record.message, "<EPSS kv_snapshot: 1234 us>");
+            assert!(record.key_values.is_empty());
+        }
+    }
+
+    /// A cycle counter at 2 ticks per microsecond, with native reads of its own.
+    struct CycleClock;
+
+    impl EmbeddedProfiler for CycleClock {
+        fn read_clock(&self) -> EPInstant {
+            EPInstant::from_ticks(21)
+        }
+
+        fn read_clock_raw(&self) -> embedded_profiling::EPContainer {
+            42
+        }
+
+        fn duration_from_raw(&self, raw: embedded_profiling::EPContainer) -> EPDuration {
+            EPDuration::from_ticks(raw / 2)
+        }
+    }
+
+    #[test]
+    fn native_reads_forwarded() {
+        let profiler = LogKvProfiler::new(CycleClock);
+        assert_eq!(profiler.read_clock_raw(), 42);
+        assert_eq!(profiler.duration_from_raw(42).ticks(), 21);
+        assert_eq!(profiler.read_snapshot().ticks(), 21);
+    }
+
+    #[test]
+    fn logs_with_snapshot_target() {
+        set_logger();
+
+        let profiler = LogKvProfiler::new(FixedClock);
+        profiler.log_snapshot(
+            &EPSnapshot::new("targeted", EPDuration::from_ticks(1)).with_target("my_app::sensors"),
+        );
+
+        let records = LOGGER.records.lock().unwrap();
+        let record = records
+            .iter()
+            .find(|record| record.target == "my_app::sensors")
+            .unwrap();
+        #[cfg(feature = "kv")]
+        assert_eq!(
+            record.key_values[0],
+            ("name".to_string(), "targeted".to_string())
+        );
+        #[cfg(not(feature = "kv"))]
+        assert_eq!(record.message, "<EPSS targeted: 1 us>");
+    }
+}
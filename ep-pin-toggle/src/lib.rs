@@ -23,6 +23,42 @@
 //! embedded_profiling::profile("print_profile", || println!("Hello, world"));
 //! ```
 //!
+//! ## Markers Only
+//!
+//! There's no clock to read, so the profiler only produces markers. [`EPPinToggle`] overrides
+//! the snapshot methods so they return constants instead of reading a clock and computing
+//! durations: every snapshot has a duration of zero. It also overrides
+//! [`read_clock_ns`](EmbeddedProfiler::read_clock_ns), the only default method converting
+//! between units, so `fugit`'s conversions aren't reachable from the profiler, even when
+//! it's installed with [`set_profiler`](embedded_profiling::set_profiler) and used as a
+//! trait object.
+//!
+//! ### Limitations
+//!
+//! That the conversions are left out of the binary isn't checked. With optimizations they
+//! would be inlined into the profiler's methods rather than show up as symbols of their own,
+//! so there is no reliable check of the linked binary, and the tests only check the values
+//! the profiler returns.
+//!
+//! ## Pulse Codes
+//!
+//! A single pin can't tell you *which* trace just ran. Constructing the profiler with
+//! [`EPPinToggle::with_pulse_codes`] additionally emits a burst of pulses after every logged
+//! snapshot, where the number of pulses is derived from the snapshot's name. By default the
+//! name is hashed with [`default_pulse_code`], but any `fn(&'static str) -> u8` mapping can
+//! be given to [`EPPinToggle::with_pulse_code_fn`] to hand-assign codes to the traces you care
+//! about and avoid collisions between them.
+//!
+//! ## Depth Pulses
+//!
+//! With nested traces, a single pin can't tell you which level a transition belongs to.
+//! Constructing the profiler with [`EPPinToggle::with_depth_pulses`] emits a burst of
+//! pulses before raising the pin in [`at_start`](EmbeddedProfiler::at_start), and after
+//! lowering it in [`at_end`](EmbeddedProfiler::at_end), with one pulse per level of
+//! [`embedded_profiling::nesting_depth`]. Every transition then costs `depth` more
+//! pin toggles, so deeply nested traces get noticeably longer and the durations measured
+//! by an outer trace include the pulses of the inner ones.
+//!
 //! ## Features
 //!
 //! ### `proc-macros`
@@ -35,7 +71,31 @@
 
 use core::cell::RefCell;
 use embedded_hal::digital::v2::OutputPin;
-use embedded_profiling::{EPInstant, EmbeddedProfiler};
+use embedded_profiling::{EPDuration, EPInstant, EPInstantNs, EPSnapshot, EmbeddedProfiler};
+
+/// What [`EPPinToggle`] reads from its (nonexistent) clock.
+const ZERO_INSTANT: EPInstant = EPInstant::from_ticks(0);
+/// The duration of every snapshot taken by [`EPPinToggle`].
+const ZERO_DURATION: EPDuration = EPDuration::from_ticks(0);
+
+/// The default mapping from a snapshot name to its pulse count.
+///
+/// Hashes `name` using 32 bit [FNV-1a](http://www.isthe.com/chongo/tech/comp/fnv/) and
+/// reduces it modulo 8, giving a pulse count between 1 and 8 inclusive. With so few codes,
+/// collisions between names are expected.
+#[must_use]
+pub fn default_pulse_code(name: &'static str) -> u8 {
+    const FNV_OFFSET_BASIS: u32 = 0x811C_9DC5;
+    const FNV_PRIME: u32 = 0x0100_0193;
+
+    let hash = name.bytes().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(FNV_PRIME)
+    });
+    // `hash % 8` always fits in a `u8`
+    #[allow(clippy::cast_possible_truncation)]
+    let code = (hash % 8) as u8;
+    code + 1
+}
 
 /// Implements [`EmbeddedProfiler`] by toggling the given pin.
 pub struct EPPinToggle<E, P>
@@ -43,6 +103,8 @@ where
     P: OutputPin<Error = E>,
 {
     pin: RefCell<P>,
+    pulse_code: Option<fn(&'static str) -> u8>,
+    depth_pulses: bool,
 }
 
 impl<E, P> EPPinToggle<E, P>
@@ -54,6 +116,37 @@ where
     pub const fn new(pin: P) -> Self {
         Self {
             pin: RefCell::new(pin),
+            pulse_code: None,
+            depth_pulses: false,
+        }
+    }
+
+    /// Creates a new [`EPPinToggle`] with the given `pin` that emits pulse codes derived
+    /// from the snapshot names using [`default_pulse_code`].
+    #[must_use]
+    pub const fn with_pulse_codes(pin: P) -> Self {
+        Self::with_pulse_code_fn(pin, default_pulse_code)
+    }
+
+    /// Creates a new [`EPPinToggle`] with the given `pin` that emits pulse codes derived
+    /// from the snapshot names using `pulse_code`. A code of `0` emits no pulses.
+    #[must_use]
+    pub const fn with_pulse_code_fn(pin: P, pulse_code: fn(&'static str) -> u8) -> Self {
+        Self {
+            pin: RefCell::new(pin),
+            pulse_code: Some(pulse_code),
+            depth_pulses: false,
+        }
+    }
+
+    /// Creates a new [`EPPinToggle`] with the given `pin` that emits the nesting depth as a
+    /// burst of pulses around every transition, see [depth pulses](crate#depth-pulses).
+    #[must_use]
+    pub const fn with_depth_pulses(pin: P) -> Self {
+        Self {
+            pin: RefCell::new(pin),
+            pulse_code: None,
+            depth_pulses: true,
         }
     }
 
@@ -61,21 +154,175 @@ where
     pub fn free(self) -> P {
         self.pin.into_inner()
     }
+
+    /// Emits the current nesting depth as pulses, if enabled.
+    fn pulse_depth(&self, pin: &mut P) {
+        if self.depth_pulses {
+            for _ in 0..embedded_profiling::nesting_depth() {
+                pin.set_high().ok();
+                pin.set_low().ok();
+            }
+        }
+    }
 }
 
 impl<E, P> EmbeddedProfiler for EPPinToggle<E, P>
 where
     P: OutputPin<Error = E>,
 {
+    #[inline(always)]
     fn read_clock(&self) -> EPInstant {
-        EPInstant::from_ticks(0)
+        ZERO_INSTANT
+    }
+
+    #[inline(always)]
+    fn read_snapshot(&self) -> EPInstant {
+        ZERO_INSTANT
+    }
+
+    #[inline(always)]
+    fn read_clock_ns(&self) -> EPInstantNs {
+        EPInstantNs::from_ticks(0)
+    }
+
+    #[inline(always)]
+    fn end_snapshot_silent(&self, _start: EPInstant, name: &'static str) -> Option<EPSnapshot> {
+        Some(EPSnapshot::new(name, ZERO_DURATION))
+    }
+
+    fn at_start(&self, _name: &'static str) {
+        let mut pin = self.pin.borrow_mut();
+        self.pulse_depth(&mut pin);
+        pin.set_high().ok();
     }
 
-    fn at_start(&self) {
-        self.pin.borrow_mut().set_high().ok();
+    fn at_end(&self, _name: &'static str) {
+        let mut pin = self.pin.borrow_mut();
+        pin.set_low().ok();
+        self.pulse_depth(&mut pin);
     }
 
-    fn at_end(&self) {
-        self.pin.borrow_mut().set_low().ok();
+    fn log_snapshot(&self, snapshot: &EPSnapshot) {
+        if let Some(pulse_code) = self.pulse_code {
+            let mut pin = self.pin.borrow_mut();
+            for _ in 0..pulse_code(snapshot.name) {
+                pin.set_high().ok();
+                pin.set_low().ok();
+            }
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "pin-toggle"
+    }
+
+    fn has_real_clock(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Counts the rising edges it has seen.
+    #[derive(Default)]
+    struct CountingPin {
+        rising_edges: usize,
+    }
+
+    impl OutputPin for CountingPin {
+        type Error = ();
+
+        fn set_low(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Self::Error> {
+            self.rising_edges += 1;
+            Ok(())
+        }
+    }
+
+    fn snapshot(name: &'static str) -> EPSnapshot {
+        EPSnapshot::new(name, ZERO_DURATION)
+    }
+
+    #[test]
+    fn markers_only() {
+        let profiler = EPPinToggle::new(CountingPin::default());
+        let start = profiler.start_snapshot("marker");
+        assert_eq!(start, ZERO_INSTANT);
+        let snapshot = profiler.end_snapshot(start, "marker").unwrap();
+        assert_eq!(snapshot.name, "marker");
+        assert_eq!(snapshot.duration, ZERO_DURATION);
+        assert_eq!(profiler.free().rising_edges, 1);
+    }
+
+    #[test]
+    fn markers_only_as_trait_object() {
+        let profiler = EPPinToggle::new(CountingPin::default());
+        let dyn_profiler: &dyn EmbeddedProfiler = &profiler;
+        assert_eq!(dyn_profiler.read_clock_ns(), EPInstantNs::from_ticks(0));
+        let start = dyn_profiler.start_snapshot("marker");
+        let snapshot = dyn_profiler.end_snapshot_clamped(start, "marker").unwrap();
+        assert_eq!(snapshot.duration, ZERO_DURATION);
+        assert_eq!(profiler.free().rising_edges, 1);
+    }
+
+    #[test]
+    fn profiler_name() {
+        assert_eq!(
+            EPPinToggle::new(CountingPin::default()).name(),
+            "pin-toggle"
+        );
+    }
+
+    #[test]
+    fn marker_only() {
+        assert!(!EPPinToggle::new(CountingPin::default()).has_real_clock());
+    }
+
+    #[test]
+    fn no_pulses_by_default() {
+        let profiler = EPPinToggle::new(CountingPin::default());
+        profiler.log_snapshot(&snapshot("quiet"));
+        assert_eq!(profiler.free().rising_edges, 0);
+    }
+
+    #[test]
+    fn default_pulse_code_in_range() {
+        for name in ["a", "b", "poll", "flush", "my_long_running_function"] {
+            assert!((1..=8).contains(&default_pulse_code(name)));
+        }
+    }
+
+    #[test]
+    fn depth_pulses() {
+        let profiler = EPPinToggle::with_depth_pulses(CountingPin::default());
+
+        // simulate being nested two levels deep
+        let outer = embedded_profiling::start_snapshot();
+        let inner = embedded_profiling::start_snapshot();
+        profiler.at_start("inner");
+        assert_eq!(profiler.pin.borrow().rising_edges, 2 + 1);
+        profiler.at_end("inner");
+        assert_eq!(profiler.pin.borrow().rising_edges, 2 + 1 + 2);
+        let _ = embedded_profiling::end_snapshot(inner, "inner");
+        let _ = embedded_profiling::end_snapshot(outer, "outer");
+    }
+
+    #[test]
+    fn custom_pulse_code() {
+        fn pulse_code(name: &'static str) -> u8 {
+            match name {
+                "important" => 5,
+                _ => 1,
+            }
+        }
+
+        let profiler = EPPinToggle::with_pulse_code_fn(CountingPin::default(), pulse_code);
+        profiler.log_snapshot(&snapshot("important"));
+        assert_eq!(profiler.free().rising_edges, 5);
     }
 }
@@ -8,9 +8,16 @@
 //! to expire just before overflow, so you can expect an exception to fire every 2**24
 //! clock cycles.
 //!
-//! Snapshots are logged using [`log::info!`], so having a logger installed is required
-//! if you want to use [`embedded_profiling::log_snapshot`] or functions that call it
-//! (like [`embedded_profiling::profile_function`]).
+//! The profiler also implements the blocking [`DelayUs`] and [`DelayMs`] traits by counting
+//! cycles of the running counter, so it can stand in for a HAL `Delay`, which would need to
+//! own [`SYST`] as well. This works with a shared reference too, e.g. to the profiler that
+//! was passed to [`embedded_profiling::set_profiler`].
+//!
+//! Snapshots are logged using [`log::info!`] (or `defmt` with the `defmt` feature), so
+//! having a logger installed is required if you want to use
+//! [`embedded_profiling::log_snapshot`] or functions that call it (like
+//! [`embedded_profiling::profile_function`]). The record's target is the snapshot's
+//! `target` if it has one, so profiling output can be filtered by the profiled module.
 //!
 //! ## Example Usage
 //!
@@ -36,6 +43,10 @@
 //! as discussed above, extend the native resolution of 24 bits to either 32 or 64 bits
 //! using the [`SysTick`] exception. The exception fires ever 2**24 clock cycles.
 //!
+//! The width follows the container: without `container-u64`, the extended cycle count is a
+//! [`u32`] that wraps every 2**32 clock cycles (like the `DWT` cycle counter). Enable
+//! `container-u64` along with `extended` to never wrap in practice.
+//!
 //! ### `container-u64`
 //!
 //! enables the `container-u64` feature in [`embedded-profiling`](embedded_profiling). Use
@@ -46,13 +57,21 @@
 //! enables the `proc-macros` feature in [`embedded-profiling`](embedded_profiling). Enables
 //! the [`embedded_profiling::profile_function`] procedural macro.
 //!
+//! ### `defmt`
+//!
+//! enables the `defmt` feature in [`embedded-profiling`](embedded_profiling). Snapshots are
+//! logged with `defmt::info!` instead of [`log::info!`].
+//!
 //! [`SYST`]: cortex_m::peripheral::SYST
 //! [`SysTick`]: `cortex_m::peripheral::scb::Exception::SysTick`
 //! [`embedded_profiling::profile_function`]: https://docs.rs/embedded-profiling/latest/embedded_profiling/attr.profile_function.html
 #![cfg_attr(not(test), no_std)]
 
 use cortex_m::peripheral::{syst::SystClkSource, SYST};
-use embedded_profiling::{EPContainer, EPInstant, EPInstantGeneric, EPSnapshot, EmbeddedProfiler};
+use embedded_hal::blocking::delay::{DelayMs, DelayUs};
+use embedded_profiling::{
+    EPContainer, EPDuration, EPInstant, EPInstantGeneric, EPSnapshot, EmbeddedProfiler,
+};
 
 #[cfg(feature = "extended")]
 use core::sync::atomic::{AtomicU32, Ordering};
@@ -61,8 +80,20 @@ use core::sync::atomic::{AtomicU32, Ordering};
 /// Tracker of `systick` cycle count overflows to extend systick's 24 bit timer
 static ROLLOVER_COUNT: AtomicU32 = AtomicU32::new(0);
 
+/// Resets the count of `systick` overflows to zero, so tests can start from a known state.
+///
+/// Only available in this crate's tests or with the `test-utils` feature. The count is
+/// shared by every profiler of this crate, so resetting it moves the clock of all of them
+/// backwards: don't use it in production code.
+#[cfg(all(feature = "extended", any(test, feature = "test-utils")))]
+pub fn reset_rollover_count() {
+    ROLLOVER_COUNT.store(0, Ordering::Release);
+}
+
 /// The reload value of the [`systick`](cortex_m::peripheral::SYST) peripheral. Also is the max it can go (2**24).
 const SYSTICK_RELOAD: u32 = 0x00FF_FFFF;
+/// The counter enable bit of the [`systick`](cortex_m::peripheral::SYST) control and status register.
+const SYST_CSR_ENABLE: u32 = 1 << 0;
 /// the resolution of [`systick`](cortex_m::peripheral::SYST), 2**24
 #[cfg(feature = "extended")]
 const SYSTICK_RESOLUTION: EPContainer = 0x0100_0000;
@@ -71,9 +102,9 @@ const SYSTICK_RESOLUTION: EPContainer = 0x0100_0000;
 ///
 /// The frequency of the [`systick`](cortex_m::peripheral::SYST) is encoded using the parameter `FREQ`.
 pub struct SysTickProfiler<const FREQ: u32> {
-    #[allow(unused)]
-    // we currently take SYST by value only to ensure ownership
     systick: SYST,
+    /// The reload value the counter was configured with, which it counts down from.
+    reload: u32,
 }
 
 impl<const FREQ: u32> SysTickProfiler<FREQ> {
@@ -97,47 +128,196 @@ impl<const FREQ: u32> SysTickProfiler<FREQ> {
         #[cfg(feature = "extended")]
         systick.enable_interrupt();
 
-        Self { systick }
+        Self {
+            systick,
+            reload: SYSTICK_RELOAD,
+        }
     }
-}
 
-impl<const FREQ: u32> EmbeddedProfiler for SysTickProfiler<FREQ> {
-    fn read_clock(&self) -> EPInstant {
-        // Read SYSTICK count and maybe account for rollovers
-        let count = {
-            #[cfg(feature = "extended")]
-            {
-                // read the clock & ROLLOVER_COUNT. We read `SYST` twice because we need to detect
-                // if we've rolled over, and if we have make sure we have the right value for ROLLOVER_COUNT.
-                let first = SYST::get_current();
+    /// The live value of the [`systick`](cortex_m::peripheral::SYST) counter. It counts down.
+    #[must_use]
+    pub fn current(&self) -> u32 {
+        SYST::get_current()
+    }
+
+    /// The [`systick`](cortex_m::peripheral::SYST) reload value.
+    #[must_use]
+    pub fn reload(&self) -> u32 {
+        SYST::get_reload()
+    }
+
+    /// Whether the [`systick`](cortex_m::peripheral::SYST) counter is enabled.
+    #[must_use]
+    pub fn is_counter_enabled(&self) -> bool {
+        self.systick.csr.read() & SYST_CSR_ENABLE != 0
+    }
+
+    /// Busy waits for at least `us` microseconds, without reconfiguring the counter.
+    pub fn delay_us(&self, us: u32) {
+        spin_cycles(SYST::get_current, cycles_to_wait(us, FREQ, 1_000_000));
+    }
+
+    /// Busy waits for at least `ms` milliseconds, without reconfiguring the counter.
+    pub fn delay_ms(&self, ms: u32) {
+        spin_cycles(SYST::get_current, cycles_to_wait(ms, FREQ, 1_000));
+    }
+
+    /// Reads the ticks counted by the [`systick`](cortex_m::peripheral::SYST), including the
+    /// rollovers if we're extended.
+    fn read_ticks(&self) -> EPContainer {
+        debug_assert!(
+            self.is_counter_enabled(),
+            "SysTick counter is disabled, did something else take over `SYST`?"
+        );
+
+        #[cfg(feature = "extended")]
+        {
+            // wraps on purpose if the container is only 32 bits, see the `extended` docs.
+            // read the clock & ROLLOVER_COUNT. We read `SYST` twice because we need to detect
+            // if we've rolled over, and if we have make sure we have the right value for ROLLOVER_COUNT.
+            let first = SYST::get_current();
+            // only a conversion with `container-u64`
+            #[allow(clippy::useless_conversion)]
+            let rollover_count: EPContainer = ROLLOVER_COUNT.load(Ordering::Acquire).into();
+            let second = SYST::get_current();
+
+            // Since the SYSTICK counter is a count down timer, check if first is larger than second
+            if first > second {
+                // The usual case. We did not roll over between the first and second reading,
+                // and because of that we also know we got a valid read on ROLLOVER_COUNT.
+                rollover_count
+                    .wrapping_mul(SYSTICK_RESOLUTION)
+                    .wrapping_add(EPContainer::from(ticks_since_reload(self.reload, first)))
+            } else {
+                // we rolled over sometime between the first and second read. We may or may not have
+                // caught the right ROLLOVER_COUNT, so grab that again and then use the second reading.
+                // only a conversion with `container-u64`
+                #[allow(clippy::useless_conversion)]
                 let rollover_count: EPContainer = ROLLOVER_COUNT.load(Ordering::Acquire).into();
-                let second = SYST::get_current();
-
-                // Since the SYSTICK counter is a count down timer, check if first is larger than second
-                if first > second {
-                    // The usual case. We did not roll over between the first and second reading,
-                    // and because of that we also know we got a valid read on ROLLOVER_COUNT.
-                    rollover_count * SYSTICK_RESOLUTION + EPContainer::from(SYSTICK_RELOAD - first)
-                } else {
-                    // we rolled over sometime between the first and second read. We may or may not have
-                    // caught the right ROLLOVER_COUNT, so grab that again and then use the second reading.
-                    let rollover_count: EPContainer = ROLLOVER_COUNT.load(Ordering::Acquire).into();
-                    rollover_count * SYSTICK_RESOLUTION + EPContainer::from(SYSTICK_RELOAD - second)
-                }
+                rollover_count
+                    .wrapping_mul(SYSTICK_RESOLUTION)
+                    .wrapping_add(EPContainer::from(ticks_since_reload(self.reload, second)))
+            }
+        }
+
+        #[cfg(not(feature = "extended"))]
+        {
+            // We aren't trying to be fancy here, we don't care if this rolled over from the last read.
+            EPContainer::from(ticks_since_reload(self.reload, SYST::get_current()))
+        }
+    }
+}
+
+/// The cycles at `freq` Hz lasting at least `amount` units of `1 / units_per_second` s,
+/// rounded up so delays never wait less than asked for.
+fn cycles_to_wait(amount: u32, freq: u32, units_per_second: u64) -> u64 {
+    (u64::from(amount) * u64::from(freq) + units_per_second - 1) / units_per_second
+}
+
+/// Busy waits until the [`systick`](cortex_m::peripheral::SYST) counter read by `read` has
+/// counted `cycles`.
+///
+/// The counter is polled often enough to never miss a reload, as long as this isn't
+/// preempted for longer than a reload period (2**24 cycles).
+fn spin_cycles(mut read: impl FnMut() -> u32, cycles: u64) {
+    let mut last = read();
+    let mut elapsed = 0;
+    while elapsed < cycles {
+        let now = read();
+        elapsed += u64::from(cycles_between(last, now));
+        last = now;
+    }
+}
+
+/// The cycles counted since the counter was last reloaded with `reload`, given its
+/// `current` value. It counts down from `reload`, so this is only correct if `reload` is
+/// the configured reload value, not the maximum.
+const fn ticks_since_reload(reload: u32, current: u32) -> u32 {
+    reload - current
+}
+
+/// The cycles counted down from `earlier` to `later`, accounting for a reload in between.
+const fn cycles_between(earlier: u32, later: u32) -> u32 {
+    earlier.wrapping_sub(later) & SYSTICK_RELOAD
+}
+
+/// Implements the `embedded-hal` delay traits for the given profiler type.
+macro_rules! impl_delay {
+    ($($profiler:ty),+) => {$(
+        impl<const FREQ: u32> DelayUs<u32> for $profiler {
+            fn delay_us(&mut self, us: u32) {
+                SysTickProfiler::delay_us(self, us);
             }
+        }
 
-            #[cfg(not(feature = "extended"))]
-            {
-                // We aren't trying to be fancy here, we don't care if this rolled over from the last read.
-                EPContainer::from(SYSTICK_RELOAD - SYST::get_current())
+        impl<const FREQ: u32> DelayUs<u16> for $profiler {
+            fn delay_us(&mut self, us: u16) {
+                SysTickProfiler::delay_us(self, us.into());
             }
-        };
+        }
 
-        embedded_profiling::convert_instant(EPInstantGeneric::<1, FREQ>::from_ticks(count))
+        impl<const FREQ: u32> DelayUs<u8> for $profiler {
+            fn delay_us(&mut self, us: u8) {
+                SysTickProfiler::delay_us(self, us.into());
+            }
+        }
+
+        impl<const FREQ: u32> DelayMs<u32> for $profiler {
+            fn delay_ms(&mut self, ms: u32) {
+                SysTickProfiler::delay_ms(self, ms);
+            }
+        }
+
+        impl<const FREQ: u32> DelayMs<u16> for $profiler {
+            fn delay_ms(&mut self, ms: u16) {
+                SysTickProfiler::delay_ms(self, ms.into());
+            }
+        }
+
+        impl<const FREQ: u32> DelayMs<u8> for $profiler {
+            fn delay_ms(&mut self, ms: u8) {
+                SysTickProfiler::delay_ms(self, ms.into());
+            }
+        }
+    )+};
+}
+
+impl_delay!(SysTickProfiler<FREQ>, &SysTickProfiler<FREQ>);
+
+impl<const FREQ: u32> EmbeddedProfiler for SysTickProfiler<FREQ> {
+    fn read_clock(&self) -> EPInstant {
+        embedded_profiling::convert_instant(EPInstantGeneric::<1, FREQ>::from_ticks(
+            self.read_ticks(),
+        ))
+    }
+
+    fn read_clock_raw(&self) -> EPContainer {
+        self.read_ticks()
+    }
+
+    fn duration_from_raw(&self, raw: EPContainer) -> EPDuration {
+        embedded_profiling::convert_instant(EPInstantGeneric::<1, FREQ>::from_ticks(raw))
+            .duration_since_epoch()
     }
 
     fn log_snapshot(&self, snapshot: &EPSnapshot) {
-        log::info!("{}", snapshot);
+        #[cfg(feature = "defmt")]
+        embedded_profiling::defmt_log_snapshot(snapshot);
+        #[cfg(not(feature = "defmt"))]
+        log::info!(target: snapshot.target.unwrap_or(module_path!()), "{}", snapshot);
+    }
+
+    fn clock_is_running(&self) -> bool {
+        self.is_counter_enabled()
+    }
+
+    fn name(&self) -> &'static str {
+        "systick"
+    }
+
+    #[cfg(feature = "extended")]
+    fn overflow_events(&self) -> Option<u32> {
+        Some(ROLLOVER_COUNT.load(Ordering::Relaxed))
     }
 }
 
@@ -150,3 +330,101 @@ use cortex_m_rt::exception;
 fn SysTick() {
     ROLLOVER_COUNT.fetch_add(1, Ordering::Release);
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::cell::Cell;
+
+    #[test]
+    #[cfg(feature = "extended")]
+    fn rollover_count_reset() {
+        ROLLOVER_COUNT.store(3, Ordering::Relaxed);
+        reset_rollover_count();
+        assert_eq!(ROLLOVER_COUNT.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn accessor_types() {
+        // reading the registers needs the hardware, so only check the signatures here
+        let _: fn(&SysTickProfiler<1>) -> u32 = SysTickProfiler::<1>::current;
+        let _: fn(&SysTickProfiler<1>) -> u32 = SysTickProfiler::<1>::reload;
+        let _: fn(&SysTickProfiler<1>) -> bool = SysTickProfiler::<1>::is_counter_enabled;
+    }
+
+    #[test]
+    fn delay_traits() {
+        fn assert_delay<D: DelayUs<u32> + DelayUs<u8> + DelayMs<u32> + DelayMs<u16>>() {}
+        assert_delay::<SysTickProfiler<1>>();
+        assert_delay::<&SysTickProfiler<1>>();
+    }
+
+    /// A counter counting down by 7 cycles of 1 MHz for every read, wrapping like `SYST`.
+    struct FakeSysTick {
+        current: Cell<u32>,
+        counted: Cell<EPContainer>,
+    }
+
+    impl FakeSysTick {
+        fn read(&self) -> u32 {
+            self.current
+                .set(self.current.get().wrapping_sub(7) & SYSTICK_RELOAD);
+            self.counted.set(self.counted.get() + 7);
+            self.current.get()
+        }
+    }
+
+    impl EmbeddedProfiler for FakeSysTick {
+        fn read_clock(&self) -> EPInstant {
+            self.read();
+            EPInstant::from_ticks(self.counted.get())
+        }
+    }
+
+    #[test]
+    fn delay_between_profiled_regions() {
+        // close to a reload, so the delay spans one
+        let systick = FakeSysTick {
+            current: Cell::new(20),
+            counted: Cell::new(0),
+        };
+
+        let first_start = systick.start_snapshot("first");
+        let first = systick.end_snapshot(first_start, "first").unwrap();
+        spin_cycles(|| systick.read(), cycles_to_wait(150, 1_000_000, 1_000_000));
+        let second_start = systick.start_snapshot("second");
+        let second = systick.end_snapshot(second_start, "second").unwrap();
+
+        let between = second_start - first_start - first.duration;
+        assert!(between.ticks() >= 150);
+        assert_eq!(second.duration.ticks(), 7);
+    }
+
+    #[test]
+    fn delays_round_up() {
+        assert_eq!(cycles_to_wait(10, 48_000_000, 1_000_000), 480);
+        // a microsecond is only a fraction of a 32.768 kHz cycle
+        assert_eq!(cycles_to_wait(1, 32_768, 1_000_000), 1);
+        assert_eq!(cycles_to_wait(1, 32_768, 1_000), 33);
+        assert_eq!(cycles_to_wait(0, 32_768, 1_000), 0);
+    }
+
+    #[test]
+    fn cycles_counted_down() {
+        assert_eq!(cycles_between(1_000, 400), 600);
+        assert_eq!(cycles_between(500, 500), 0);
+        // reloaded from 0 to `SYSTICK_RELOAD` in between
+        assert_eq!(cycles_between(10, SYSTICK_RELOAD - 5), 16);
+    }
+
+    #[test]
+    fn ticks_from_custom_reload() {
+        assert_eq!(
+            ticks_since_reload(SYSTICK_RELOAD, SYSTICK_RELOAD - 100),
+            100
+        );
+        // counting down from a smaller reload value
+        assert_eq!(ticks_since_reload(0x000F_FFFF, 0x000F_0000), 0xFFFF);
+        assert_eq!(ticks_since_reload(0x000F_FFFF, 0), 0x000F_FFFF);
+    }
+}
@@ -0,0 +1,160 @@
+//! [`EmbeddedProfiler`] wrapper that writes snapshots straight out over USB serial.
+//!
+//! Wraps another [`EmbeddedProfiler`], which still provides the clock and the
+//! [`at_start`](EmbeddedProfiler::at_start)/[`at_end`](EmbeddedProfiler::at_end) hooks.
+//! Only [`log_snapshot`](EmbeddedProfiler::log_snapshot) is replaced: snapshots are written
+//! with [`embedded_profiling::write_snapshot`] to a serial port, without going through `log`.
+//!
+//! The port is anything implementing [`core::fmt::Write`], typically a thin wrapper around a
+//! [`usbd-serial`](https://docs.rs/usbd-serial) `SerialPort`. Writes to a full port are
+//! dropped rather than blocking, so a host that isn't reading can't stall the profiled code.
+//!
+//! ## Interrupt wiring
+//!
+//! The USB device must be polled from its interrupt handlers, which need the serial port too.
+//! So the port is handed to the profiler with [`UsbSerialProfiler::install`], and the handlers
+//! reach it through [`UsbSerialProfiler::with_port`]. Both that and writing a snapshot happen
+//! inside a [`critical-section`](https://docs.rs/critical-section) critical section, so
+//! snapshots can be logged from any priority without racing the USB interrupts, at the cost of
+//! holding them off while a snapshot is written.
+//!
+//! ## Example Usage
+//!
+//!```ignore
+//! use usb_device::{bus::UsbBus, prelude::*};
+//! use usbd_serial::SerialPort;
+//!
+//! /// A `SerialPort` written to through `core::fmt::Write`.
+//! pub struct Port<'a, B: UsbBus>(SerialPort<'a, B>);
+//!
+//! impl<B: UsbBus> core::fmt::Write for Port<'_, B> {
+//!     fn write_str(&mut self, s: &str) -> core::fmt::Result {
+//!         self.0.write(s.as_bytes()).map(|_| ()).map_err(|_| core::fmt::Error)
+//!     }
+//! }
+//!
+//! // (...) `usb_dev` and `serial` built from the HAL's `UsbBusAllocator`
+//! let profiler = cortex_m::singleton!(: UsbSerialProfiler<DwtProfiler<CORE_FREQ>, Port<'static, Bus>> =
+//!     UsbSerialProfiler::new(DwtProfiler::new(&mut core.DCB, core.DWT, CORE_FREQ)))
+//! .unwrap();
+//! profiler.install(Port(serial));
+//! unsafe {
+//!     embedded_profiling::set_profiler(profiler).unwrap();
+//! }
+//! // (...) unmask the USB interrupts, whose handlers poll the device through the profiler
+//!
+//! #[interrupt]
+//! fn USB() {
+//!     // with `profiler` and `usb_dev` stashed where the handler can reach them
+//!     PROFILER.with_port(|port| USB_DEV.poll(&mut [&mut port.0]));
+//! }
+//! ```
+//!
+//! ## Features
+//!
+//! ### `proc-macros`
+//!
+//! enables the `proc-macros` feature in [`embedded-profiling`](embedded_profiling). Enables
+//! the [`embedded_profiling::profile_function`] procedural macro.
+//!
+//! [`embedded_profiling::profile_function`]: https://docs.rs/embedded-profiling/latest/embedded_profiling/attr.profile_function.html
+#![cfg_attr(not(test), no_std)]
+
+use core::cell::RefCell;
+use critical_section::Mutex;
+use embedded_profiling::{EPSnapshot, EmbeddedProfiler};
+
+/// Wraps the [`EmbeddedProfiler`] `P`, writing its snapshots to the serial port `W`.
+///
+/// Snapshots logged before a port is [`install`](UsbSerialProfiler::install)ed are dropped.
+pub struct UsbSerialProfiler<P, W> {
+    inner: P,
+    port: Mutex<RefCell<Option<W>>>,
+}
+
+impl<P, W> UsbSerialProfiler<P, W> {
+    /// Creates a new [`UsbSerialProfiler`] around the profiler `inner`, without a port yet.
+    #[must_use]
+    pub const fn new(inner: P) -> Self {
+        Self {
+            inner,
+            port: Mutex::new(RefCell::new(None)),
+        }
+    }
+
+    /// Writes snapshots to `port` from now on, returning the previously installed port.
+    pub fn install(&self, port: W) -> Option<W> {
+        critical_section::with(|cs| self.port.borrow(cs).replace(Some(port)))
+    }
+
+    /// Calls `f` with the installed port, e.g. to poll the USB device from its interrupt
+    /// handler. Returns `None` if no port is installed.
+    pub fn with_port<R>(&self, f: impl FnOnce(&mut W) -> R) -> Option<R> {
+        critical_section::with(|cs| self.port.borrow(cs).borrow_mut().as_mut().map(f))
+    }
+
+    /// Consumes [`UsbSerialProfiler`], returning the wrapped profiler and the port, if any.
+    pub fn free(self) -> (P, Option<W>) {
+        (self.inner, self.port.into_inner().into_inner())
+    }
+}
+
+impl<P, W> EmbeddedProfiler for UsbSerialProfiler<P, W>
+where
+    P: EmbeddedProfiler,
+    W: core::fmt::Write,
+{
+    fn log_snapshot(&self, snapshot: &EPSnapshot) {
+        // a full port only means this snapshot is lost
+        let _ = self.with_port(|port| embedded_profiling::write_snapshot(port, snapshot));
+    }
+
+    embedded_profiling::forward_profiler!(self.inner => clock, hooks, snapshots);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use critical_section::RawRestoreState;
+    use embedded_profiling::{EPDuration, EPInstant};
+
+    /// The tests don't share any profiler, so the critical section doesn't need to exclude
+    /// anything. The restore state depends on the features other crates of the workspace enable.
+    struct HostCriticalSection;
+    critical_section::set_impl!(HostCriticalSection);
+
+    unsafe impl critical_section::Impl for HostCriticalSection {
+        unsafe fn acquire() -> RawRestoreState {
+            RawRestoreState::default()
+        }
+
+        unsafe fn release(_restore_state: RawRestoreState) {}
+    }
+
+    struct FixedClock;
+
+    impl EmbeddedProfiler for FixedClock {
+        fn read_clock(&self) -> EPInstant {
+            EPInstant::from_ticks(0)
+        }
+
+        fn log_snapshot(&self, _snapshot: &EPSnapshot) {
+            panic!("snapshots are written to the port instead");
+        }
+    }
+
+    #[test]
+    fn written_to_port() {
+        let profiler = UsbSerialProfiler::<_, String>::new(FixedClock);
+        let snapshot = EPSnapshot::new("usb", EPDuration::from_ticks(42));
+
+        // dropped without a port
+        profiler.log_snapshot(&snapshot);
+        assert!(profiler.install(String::new()).is_none());
+
+        profiler.log_snapshot(&snapshot);
+        profiler.log_snapshot(&snapshot);
+        let written = profiler.with_port(|port| port.clone()).unwrap();
+        assert_eq!(written, "<EPSS usb: 42 us>\n<EPSS usb: 42 us>\n");
+    }
+}
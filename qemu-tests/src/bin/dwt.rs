@@ -0,0 +1,62 @@
+//! Profiles a busy loop of a known number of cycles with [`ep_dwt::DwtProfiler`] and checks
+//! the measured duration, reporting the result over semihosting.
+//!
+//! QEMU doesn't model the DWT, so this only runs on a board or an emulator with a working
+//! cycle counter, and CI only builds it. The tolerance is wide, as emulators aren't cycle
+//! accurate. It still catches a counter that isn't enabled or read correctly and conversions
+//! that are off by orders of magnitude.
+#![no_std]
+#![no_main]
+
+use cortex_m::peripheral::{Peripherals, DWT};
+use cortex_m_rt::entry;
+use cortex_m_semihosting::{debug, hprintln};
+use embedded_profiling::EmbeddedProfiler;
+use panic_semihosting as _;
+
+/// The core clock the profiler is configured with.
+const CORE_FREQ: u32 = 12_000_000;
+/// How many cycles the profiled busy loop takes.
+const BUSY_CYCLES: u32 = 1_200_000;
+/// The duration `BUSY_CYCLES` take at `CORE_FREQ`, in microseconds.
+const EXPECTED_US: u32 = BUSY_CYCLES / (CORE_FREQ / 1_000_000);
+
+#[entry]
+fn main() -> ! {
+    let mut core = Peripherals::take().unwrap();
+    let profiler = ep_dwt::DwtProfiler::<CORE_FREQ>::new(&mut core.DCB, core.DWT, CORE_FREQ);
+
+    if !DWT::has_cycle_counter() || !profiler.clock_is_running() {
+        hprintln!("FAIL: the DWT cycle counter isn't counting on this machine");
+        debug::exit(debug::EXIT_FAILURE);
+    }
+
+    let start = profiler.start_snapshot("busy_loop");
+    cortex_m::asm::delay(BUSY_CYCLES);
+    let snapshot = profiler.end_snapshot(start, "busy_loop");
+
+    let passed = match snapshot {
+        Some(snapshot) => {
+            hprintln!("{} (expected {} us)", snapshot, EXPECTED_US);
+            let measured = u64::from(snapshot.duration.ticks());
+            let expected = u64::from(EXPECTED_US);
+            measured >= expected / 2 && measured <= expected * 2
+        }
+        None => {
+            hprintln!("the cycle counter went backwards");
+            false
+        }
+    };
+
+    if passed {
+        hprintln!("PASS");
+        debug::exit(debug::EXIT_SUCCESS);
+    } else {
+        hprintln!("FAIL: measured duration out of tolerance");
+        debug::exit(debug::EXIT_FAILURE);
+    }
+
+    loop {
+        cortex_m::asm::bkpt();
+    }
+}
@@ -0,0 +1,61 @@
+//! Profiles a delay counted on the SysTick with [`ep_systick::SysTickProfiler`] and checks the
+//! measured duration, reporting the result over semihosting.
+//!
+//! The delay and the profiler count the same timer, so the check doesn't depend on how fast
+//! the emulator runs compared to real time. It catches a counter that isn't enabled or read
+//! correctly and conversions that are off by orders of magnitude.
+#![no_std]
+#![no_main]
+
+use cortex_m::peripheral::Peripherals;
+use cortex_m_rt::entry;
+use cortex_m_semihosting::{debug, hprintln};
+use embedded_profiling::EmbeddedProfiler;
+use panic_semihosting as _;
+
+/// The core clock the profiler is configured with.
+const CORE_FREQ: u32 = 12_000_000;
+/// How long the profiled delay lasts, in microseconds.
+const DELAY_US: u32 = 10_000;
+
+#[entry]
+fn main() -> ! {
+    let core = Peripherals::take().unwrap();
+    let profiler = ep_systick::SysTickProfiler::<CORE_FREQ>::new(core.SYST, CORE_FREQ);
+
+    if !profiler.clock_is_running() {
+        hprintln!("FAIL: the SysTick counter isn't enabled");
+        debug::exit(debug::EXIT_FAILURE);
+    }
+
+    // the counter was just reloaded, so the span ends well before it wraps after 2**24 cycles
+    let start = profiler.start_snapshot("delay");
+    profiler.delay_us(DELAY_US);
+    let snapshot = profiler.end_snapshot(start, "delay");
+
+    let passed = match snapshot {
+        Some(snapshot) => {
+            hprintln!("{} (expected {} us)", snapshot, DELAY_US);
+            // the delay never waits less than asked for, but the emulator can be preempted
+            let measured = u64::from(snapshot.duration.ticks());
+            let expected = u64::from(DELAY_US);
+            measured >= expected && measured <= expected * 10
+        }
+        None => {
+            hprintln!("the SysTick counter wrapped during the delay");
+            false
+        }
+    };
+
+    if passed {
+        hprintln!("PASS");
+        debug::exit(debug::EXIT_SUCCESS);
+    } else {
+        hprintln!("FAIL: measured duration out of tolerance");
+        debug::exit(debug::EXIT_FAILURE);
+    }
+
+    loop {
+        cortex_m::asm::bkpt();
+    }
+}